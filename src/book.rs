@@ -0,0 +1,174 @@
+use rust_chess::engine::book::{engine_side, BookBuilder};
+use rust_chess::engine::game::GameResult;
+use rust_chess::engine::pgn::{PgnGame, PgnReader};
+use rust_chess::engine::polyglot::Book;
+
+use std::fs::File;
+
+const USAGE: &str = "usage: rust_chess book make <games.pgn> <book.bin> [plies <n>] [mingames <n>] [minscore <percent>]\n       \
+                     rust_chess book learn <book.bin> <games.pgn> <engine name>";
+
+const DEFAULT_PLIES: usize = 20;
+
+pub fn run(args: &[String]) -> i32 {
+    // returns the process exit code
+    let result = match args.first().map(|s| s.as_str()) {
+        Some("make") => {
+            parse_make_args(&args[1..]).map(|(pgn, out, builder)| make(&pgn, &out, builder))
+        }
+        Some("learn") if args.len() >= 4 => Ok(learn(&args[1], &args[2], &args[3..].join(" "))),
+        Some("learn") => Err(String::from(
+            "learn needs a book, a PGN file and an engine name",
+        )),
+        Some(other) => Err(format!("unknown command '{}'", other)),
+        None => Err(String::from("missing command")),
+    };
+    return match result {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            eprintln!("{}", e);
+            1
+        }
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            2
+        }
+    };
+}
+
+fn parse_make_args(args: &[String]) -> Result<(String, String, BookBuilder), String> {
+    if args.len() < 2 {
+        return Err(String::from("missing PGN or book file"));
+    }
+    let mut builder = BookBuilder::new(DEFAULT_PLIES);
+    let mut i = 2;
+    while i < args.len() {
+        let value = match args.get(i + 1) {
+            Some(v) => v,
+            None => return Err(format!("missing value for {}", args[i])),
+        };
+        let number = match value.parse::<u32>() {
+            Ok(n) => n,
+            _ => return Err(format!("invalid value '{}' for {}", value, args[i])),
+        };
+        match args[i].as_str() {
+            "plies" => builder.max_plies = number as usize,
+            "mingames" => builder.min_games = number,
+            "minscore" if number <= 100 => builder.min_score = number,
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+        i += 2;
+    }
+    return Ok((args[0].clone(), args[1].clone(), builder));
+}
+
+fn read_games(path: &str) -> Result<Vec<PgnGame>, String> {
+    // games that don't parse are reported and skipped
+    let file = File::open(path).map_err(|e| format!("can't open {}: {}", path, e))?;
+    let mut games = Vec::new();
+    for game in PgnReader::new(file) {
+        match game {
+            Ok(g) => games.push(g),
+            Err(e) => eprintln!("{}: skipped {}", path, e),
+        }
+    }
+    return Ok(games);
+}
+
+fn make(pgn: &str, out: &str, builder: BookBuilder) -> Result<(), String> {
+    let mut builder = builder;
+    let games = read_games(pgn)?;
+    for game in &games {
+        builder.add_game(game);
+    }
+    let book = builder.build();
+    book.save(out)?;
+    println!(
+        "{} games, {} entries written to {}",
+        builder.games(),
+        book.len(),
+        out
+    );
+    return Ok(());
+}
+
+fn learn(path: &str, pgn: &str, engine: &str) -> Result<(), String> {
+    let mut book = Book::open(path)?;
+    let mut changed = 0;
+    let mut learned = 0;
+    for game in read_games(pgn)? {
+        let (side, result) = match (engine_side(&game, engine), GameResult::parse(&game.result)) {
+            (Some(side), Some(result)) => (side, result),
+            _ => continue,
+        };
+        changed += book.learn(&game.start, &game.mainline(), side, result);
+        learned += 1;
+    }
+    book.save(path)?;
+    println!(
+        "learned from {} games of {}, {} entries updated",
+        learned, engine, changed
+    );
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rust_chess::engine::board::Board;
+
+    fn args(text: &str) -> Vec<String> {
+        return text.split_whitespace().map(|s| s.to_string()).collect();
+    }
+
+    #[test]
+    fn test_parse_make_args() {
+        let (pgn, out, builder) =
+            parse_make_args(&args("games.pgn book.bin plies 12 minscore 40")).unwrap();
+        assert_eq!((pgn.as_str(), out.as_str()), ("games.pgn", "book.bin"));
+        assert_eq!(builder.max_plies, 12);
+        assert_eq!(builder.min_score, 40);
+        assert_eq!(builder.min_games, 1);
+        assert!(parse_make_args(&args("games.pgn")).is_err());
+        assert!(parse_make_args(&args("games.pgn book.bin minscore 101")).is_err());
+        assert!(parse_make_args(&args("games.pgn book.bin depth 3")).is_err());
+        assert_eq!(run(&args("learn book.bin")), 2);
+    }
+
+    #[test]
+    fn test_make_and_learn() {
+        // a fresh directory of our own, so parallel runs don't share files
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "rust_chess_book_{}_{}",
+            std::process::id(),
+            nanos
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pgn = dir.join("games.pgn");
+        let bin = dir.join("book.bin");
+        let (pgn, bin) = (pgn.to_str().unwrap(), bin.to_str().unwrap());
+        std::fs::write(
+            pgn,
+            "[White \"engine\"]\n[Black \"human\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n\n\
+             [White \"human\"]\n[Black \"engine\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n",
+        )
+        .unwrap();
+        assert_eq!(run(&args(&format!("make {} {}", pgn, bin))), 0);
+        let e4 = Book::open(bin)
+            .unwrap()
+            .best_move(&Board::default())
+            .unwrap();
+        assert_eq!(e4.to_string(), "e2e4");
+        assert_eq!(run(&args(&format!("learn {} {} engine", bin, pgn))), 0);
+        // e4 won once and lost nothing, e5 only lost so it was never written
+        let book = Book::open(bin).unwrap();
+        assert_eq!(book.moves(&Board::default())[0].1, 6);
+        assert_eq!(book.len(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,351 @@
+use super::board::{Board, Move};
+use super::piece::{Piece, PieceColor, PieceType};
+
+pub const ROOK_DIRECTIONS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+pub const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pin {
+    // the piece that can't leave the line, and the slider behind it
+    pub piece: (usize, usize),
+    pub pinner: (usize, usize),
+    // the squares between the king and the pinner, and the pinner's own square
+    pub ray: Vec<(usize, usize)>,
+}
+
+fn offset(square: (usize, usize), d: (i8, i8)) -> Option<(usize, usize)> {
+    let row = square.0 as i8 + d.0;
+    let col = square.1 as i8 + d.1;
+    if !(0..8).contains(&row) || !(0..8).contains(&col) {
+        return None;
+    }
+    return Some((row as usize, col as usize));
+}
+
+fn bit(square: (usize, usize)) -> u64 {
+    return 1 << (square.0 * 8 + square.1);
+}
+
+fn slides_along(piece: Piece, d: (i8, i8)) -> bool {
+    // whether the piece moves any distance in this direction
+    let straight = d.0 == 0 || d.1 == 0;
+    return match piece.as_type() {
+        PieceType::Queen => true,
+        PieceType::Rook => straight,
+        PieceType::Bishop => !straight,
+        _ => false,
+    };
+}
+
+impl Board {
+    fn piece_at(&self, square: (usize, usize), removed: u64) -> Piece {
+        if removed & bit(square) != 0 {
+            return Piece::Blank;
+        }
+        return self.squares[square.0][square.1];
+    }
+
+    fn ray(&self, from: (usize, usize), d: (i8, i8), removed: u64) -> Vec<(usize, usize)> {
+        // squares along a direction up to and including the first piece
+        let mut squares = Vec::new();
+        let mut current = from;
+        while let Some(next) = offset(current, d) {
+            squares.push(next);
+            if self.piece_at(next, removed) != Piece::Blank {
+                break;
+            }
+            current = next;
+        }
+        return squares;
+    }
+
+    fn first_piece(
+        &self,
+        from: (usize, usize),
+        d: (i8, i8),
+        removed: u64,
+    ) -> Option<(usize, usize)> {
+        let mut current = from;
+        while let Some(next) = offset(current, d) {
+            if self.piece_at(next, removed) != Piece::Blank {
+                return Some(next);
+            }
+            current = next;
+        }
+        return None;
+    }
+
+    fn find_attackers(
+        &self,
+        square: (usize, usize),
+        color: PieceColor,
+        removed: u64,
+        found: &mut dyn FnMut((usize, usize)) -> bool,
+    ) -> bool {
+        // calls `found` for each attacker until it returns true, without allocating, as
+        // this is what every check test comes down to
+        let pawn_row = if color == PieceColor::White { 1 } else { -1 };
+        for col in [-1, 1] {
+            if let Some(from) = offset(square, (pawn_row, col)) {
+                if self.piece_at(from, removed) == Piece::from_type(PieceType::Pawn, color)
+                    && found(from)
+                {
+                    return true;
+                }
+            }
+        }
+        for (offsets, ptype) in [
+            (KNIGHT_OFFSETS, PieceType::Knight),
+            (KING_OFFSETS, PieceType::King),
+        ] {
+            for d in offsets {
+                if let Some(from) = offset(square, d) {
+                    if self.piece_at(from, removed) == Piece::from_type(ptype, color) && found(from)
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        for d in ROOK_DIRECTIONS.iter().chain(BISHOP_DIRECTIONS.iter()) {
+            if let Some(from) = self.first_piece(square, *d, removed) {
+                let piece = self.piece_at(from, removed);
+                if piece.as_color() == color && slides_along(piece, *d) && found(from) {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
+    pub(crate) fn attackers_through(
+        &self,
+        square: (usize, usize),
+        color: PieceColor,
+        removed: u64,
+    ) -> Vec<(usize, usize)> {
+        // as attackers_to, with the squares in `removed` taken to be empty, which is how
+        // x-ray attackers behind a piece show up once it has gone
+        let mut attackers = Vec::new();
+        self.find_attackers(square, color, removed, &mut |from| {
+            attackers.push(from);
+            false
+        });
+        return attackers;
+    }
+
+    pub fn attackers_to(&self, square: (usize, usize), color: PieceColor) -> Vec<(usize, usize)> {
+        // every piece of `color` that could capture on the square, checks aside
+        return self.attackers_through(square, color, 0);
+    }
+
+    pub fn is_square_attacked(&self, square: (usize, usize), color: PieceColor) -> bool {
+        return self.find_attackers(square, color, 0, &mut |_| true);
+    }
+
+    fn line_pieces(
+        &self,
+        king: (usize, usize),
+        middle: PieceColor,
+        slider: PieceColor,
+    ) -> Vec<Pin> {
+        // a lone `middle` piece between the king and a `slider` piece that moves along the line
+        let mut found = Vec::new();
+        for d in ROOK_DIRECTIONS.iter().chain(BISHOP_DIRECTIONS.iter()) {
+            let first = self.ray(king, *d, 0);
+            let piece = match first.last() {
+                Some(square) if self.piece_at(*square, 0).as_color() == middle => *square,
+                _ => continue,
+            };
+            let second = self.ray(piece, *d, 0);
+            if let Some(pinner) = second.last() {
+                let slider_piece = self.piece_at(*pinner, 0);
+                if slider_piece.as_color() == slider && slides_along(slider_piece, *d) {
+                    let mut ray: Vec<(usize, usize)> = first;
+                    ray.extend(second.iter().copied());
+                    ray.retain(|s| *s != piece);
+                    found.push(Pin {
+                        piece,
+                        pinner: *pinner,
+                        ray,
+                    });
+                }
+            }
+        }
+        return found;
+    }
+
+    pub fn pinned_pieces(&self, color: PieceColor) -> Vec<Pin> {
+        // pieces of `color` that may only move along the ray to the piece pinning them
+        return match self.king_position(color) {
+            Some(king) => self.line_pieces(king, color, color.opposite()),
+            None => Vec::new(),
+        };
+    }
+
+    pub fn discovered_check_candidates(&self, color: PieceColor) -> Vec<(usize, usize)> {
+        // pieces of `color` that would uncover a check on the other king by moving away
+        return match self.king_position(color.opposite()) {
+            Some(king) => self
+                .line_pieces(king, color, color)
+                .into_iter()
+                .map(|pin| pin.piece)
+                .collect(),
+            None => Vec::new(),
+        };
+    }
+
+    pub fn gives_check(&self, m: &Move) -> bool {
+        // direct, discovered, by castling or en passant, whatever the move does
+        let mut after = *self;
+        after.play_move(*m);
+        return after.in_check(m.piece.as_color().opposite());
+    }
+
+    pub fn defended_squares(&self, square: (usize, usize)) -> Vec<(usize, usize)> {
+        // squares the piece attacks, including those its own pieces stand on
+        let piece = self.squares[square.0][square.1];
+        let color = piece.as_color();
+        return match piece.as_type() {
+            PieceType::Blank => Vec::new(),
+            PieceType::Pawn => {
+                let forward = if color == PieceColor::White { -1 } else { 1 };
+                [-1, 1]
+                    .iter()
+                    .filter_map(|c| offset(square, (forward, *c)))
+                    .collect()
+            }
+            PieceType::Knight => KNIGHT_OFFSETS
+                .iter()
+                .filter_map(|d| offset(square, *d))
+                .collect(),
+            PieceType::King => KING_OFFSETS
+                .iter()
+                .filter_map(|d| offset(square, *d))
+                .collect(),
+            _ => ROOK_DIRECTIONS
+                .iter()
+                .chain(BISHOP_DIRECTIONS.iter())
+                .filter(|d| slides_along(piece, **d))
+                .flat_map(|d| self.ray(square, *d, 0))
+                .collect(),
+        };
+    }
+
+    pub fn xray_squares(&self, square: (usize, usize)) -> Vec<(usize, usize)> {
+        // for a slider, the squares it attacks through the first piece in its way, up to
+        // and including the next one
+        let piece = self.squares[square.0][square.1];
+        let mut squares = Vec::new();
+        for d in ROOK_DIRECTIONS.iter().chain(BISHOP_DIRECTIONS.iter()) {
+            if !slides_along(piece, *d) {
+                continue;
+            }
+            if let Some(blocker) = self.ray(square, *d, 0).last() {
+                if self.is_occupied(*blocker) {
+                    squares.extend(self.ray(*blocker, *d, 0));
+                }
+            }
+        }
+        return squares;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn squares(names: &[&str]) -> Vec<(usize, usize)> {
+        let mut squares: Vec<(usize, usize)> = names
+            .iter()
+            .map(|n| Board::parse_square(n).unwrap())
+            .collect();
+        squares.sort();
+        return squares;
+    }
+
+    fn sorted(mut v: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+        v.sort();
+        return v;
+    }
+
+    #[test]
+    fn test_attackers() {
+        let board = Board::from_fen(String::from("4k3/8/2n5/3p4/4P3/1B3N2/8/4RK2 w - - 0 1"));
+        let e5 = Board::parse_square("e5").unwrap();
+        assert_eq!(
+            sorted(board.attackers_to(e5, PieceColor::White)),
+            squares(&["f3"])
+        );
+        let d5 = Board::parse_square("d5").unwrap();
+        assert_eq!(
+            sorted(board.attackers_to(d5, PieceColor::White)),
+            squares(&["b3", "e4"])
+        );
+        let d4 = Board::parse_square("d4").unwrap();
+        assert_eq!(
+            sorted(board.attackers_to(d4, PieceColor::Black)),
+            squares(&["c6"])
+        );
+        assert!(board.is_square_attacked(Board::parse_square("e4").unwrap(), PieceColor::Black));
+        assert!(!board.is_square_attacked(Board::parse_square("h8").unwrap(), PieceColor::White));
+        // the e-pawn blocks the rook, which x-rays through to e8
+        let e1 = Board::parse_square("e1").unwrap();
+        assert!(board
+            .defended_squares(e1)
+            .contains(&Board::parse_square("f1").unwrap()));
+        assert_eq!(
+            sorted(board.xray_squares(e1)),
+            squares(&["e5", "e6", "e7", "e8", "g1", "h1"])
+        );
+    }
+
+    #[test]
+    fn test_pins_and_checks() {
+        let board = Board::from_fen(String::from("4k3/4r3/8/8/1b2R3/8/3N4/4K1B1 w - - 0 1"));
+        let pins = board.pinned_pieces(PieceColor::White);
+        assert_eq!(pins.len(), 2);
+        let knight = pins
+            .iter()
+            .find(|p| p.piece == Board::parse_square("d2").unwrap())
+            .unwrap();
+        assert_eq!(knight.pinner, Board::parse_square("b4").unwrap());
+        assert_eq!(sorted(knight.ray.clone()), squares(&["b4", "c3"]));
+        assert_eq!(board.pinned_pieces(PieceColor::Black).len(), 1);
+        // the black rook stands between the rook on e4 and its own king
+        assert!(board
+            .discovered_check_candidates(PieceColor::White)
+            .is_empty());
+        let m = board.parse_move("g1h2").unwrap();
+        assert!(!board.gives_check(&m));
+        let m = board.parse_move("e4e7").unwrap();
+        assert!(board.gives_check(&m));
+        let board = Board::from_fen(String::from("4k3/8/8/8/4N3/8/8/4RK2 w - - 0 1"));
+        assert_eq!(
+            board.discovered_check_candidates(PieceColor::White),
+            squares(&["e4"])
+        );
+        assert!(board.gives_check(&board.parse_move("e4c3").unwrap()));
+    }
+}
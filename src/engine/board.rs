@@ -2,8 +2,11 @@ use super::piece::Piece;
 use super::piece::Piece::*;
 use super::piece::PieceColor;
 use super::piece::PieceType;
+use super::variant::{self, Standard, Variant, VariantState, POCKET_TYPES};
+use super::zobrist;
 
 use std::cmp;
+use std::fmt;
 
 #[derive(Copy, Clone)]
 pub struct Board {
@@ -13,6 +16,12 @@ pub struct Board {
     pub can_castle: [char; 4],
     pub half_move: u32,
     pub en_passant: (char, char),
+    // the file of the rook each castling right belongs to, in KQkq order
+    pub castle_files: [usize; 4],
+    // Chess960 rules: castling is written as the king taking its own rook
+    pub chess960: bool,
+    // whatever else a variant needs to know about the position
+    pub state: VariantState,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -26,8 +35,17 @@ pub struct Move {
     pub piece: Piece,
     pub start: (usize, usize),
     pub end: (usize, usize),
+    // Piece::Blank unless this is a pawn promotion
+    pub promotion: Piece,
 }
 
+pub const PROMOTION_TYPES: [PieceType; 4] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+];
+
 impl Turn {
     pub fn to_string(&self) -> &'static str {
         match *self {
@@ -49,6 +67,60 @@ impl Turn {
     }
 }
 
+impl Move {
+    pub fn new(piece: Piece, start: (usize, usize), end: (usize, usize)) -> Move {
+        Move {
+            piece,
+            start,
+            end,
+            promotion: Piece::Blank,
+        }
+    }
+    pub fn drop(piece: Piece, square: (usize, usize)) -> Move {
+        // a piece put on the board from the pocket in Crazyhouse
+        Move::new(piece, square, square)
+    }
+    pub fn is_drop(&self) -> bool {
+        return self.start == self.end;
+    }
+    pub fn is_castle(&self) -> bool {
+        return self.piece.as_type() == PieceType::King
+            && (self.start.1 as i8 - self.end.1 as i8).abs() == 2;
+    }
+}
+
+impl fmt::Display for Move {
+    // Long algebraic notation, e.g. e2e4 or e7e8q, and drops as P@e4
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_drop() {
+            let letter = self.piece.as_type().as_upper();
+            return write!(f, "{}@{}", letter, Board::square_name(self.end));
+        }
+        write!(
+            f,
+            "{}{}",
+            Board::square_name(self.start),
+            Board::square_name(self.end)
+        )?;
+        if self.promotion != Piece::Blank {
+            write!(f, "{}", self.promotion.as_type().as_char())?;
+        }
+        return Ok(());
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.squares {
+            for col in row {
+                write!(f, "{}", col.as_char())?;
+            }
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
+
 impl Board {
     pub const fn default() -> Board {
         Board {
@@ -66,13 +138,67 @@ impl Board {
                     WRook, WKnight, WBishop, WQueen, WKing, WBishop, WKnight, WRook,
                 ],
             ],
-            move_number: 0,
+            move_number: 1,
             to_move: Turn::White,
             can_castle: ['K', 'Q', 'k', 'q'],
             en_passant: ('-', '-'),
             half_move: 0,
+            castle_files: [7, 0, 7, 0],
+            chess960: false,
+            state: VariantState::new(),
         }
     }
+    pub fn chess960_start(index: usize) -> Result<Board, String> {
+        // the Chess960 start positions in their standard numbering, 518 is the usual one
+        if index >= 960 {
+            return Err(format!(
+                "no Chess960 position {}, they go from 0 to 959",
+                index
+            ));
+        }
+        let mut rank = [PieceType::Blank; 8];
+        let place = |rank: &mut [PieceType; 8], free: usize, ptype: PieceType| {
+            let col = (0..8)
+                .filter(|c| rank[*c] == PieceType::Blank)
+                .nth(free)
+                .unwrap();
+            rank[col] = ptype;
+        };
+        let mut n = index;
+        rank[2 * (n % 4) + 1] = PieceType::Bishop;
+        n /= 4;
+        rank[2 * (n % 4)] = PieceType::Bishop;
+        n /= 4;
+        place(&mut rank, n % 6, PieceType::Queen);
+        n /= 6;
+        let knights = [
+            (0, 1),
+            (0, 2),
+            (0, 3),
+            (0, 4),
+            (1, 2),
+            (1, 3),
+            (1, 4),
+            (2, 3),
+            (2, 4),
+            (3, 4),
+        ][n];
+        place(&mut rank, knights.1, PieceType::Knight);
+        place(&mut rank, knights.0, PieceType::Knight);
+        for ptype in [PieceType::Rook, PieceType::King, PieceType::Rook].iter() {
+            place(&mut rank, 0, *ptype);
+        }
+        let mut board = Board::default();
+        for (col, ptype) in rank.iter().enumerate() {
+            board.squares[0][col] = Piece::from_type(*ptype, PieceColor::Black);
+            board.squares[7][col] = Piece::from_type(*ptype, PieceColor::White);
+        }
+        let rooks: Vec<usize> = (0..8).filter(|c| rank[*c] == PieceType::Rook).collect();
+        board.castle_files = [rooks[1], rooks[0], rooks[1], rooks[0]];
+        board.chess960 = true;
+        return Ok(board);
+    }
+
     pub fn print(&self) {
         println!("Move: {}", self.move_number);
         println!("To move: {}", self.to_move.to_string());
@@ -81,97 +207,243 @@ impl Board {
         }
         println!("---------------");
     }
-    pub fn to_string(&self) -> String {
-        let mut board_str = "".to_string();
-        for row in &self.squares {
-            for col in row {
-                board_str.push(col.as_char());
-            }
-            board_str.push('\n');
-        }
-        return board_str;
-    }
-    fn get_castling(s: String) -> [char; 4] {
-        let mut v = ['-'; 4];
-        for x in s.chars() {
-            let i = match x {
-                'K' => 0,
-                'Q' => 1,
-                'k' => 2,
-                'q' => 3,
-                '-' => 4,
-                _ => 4,
+    fn set_castling(&mut self, field: &str) -> Result<(), String> {
+        // KQkq, or the rook files as in Shredder-FEN (HAha) and X-FEN (Kq or Gb),
+        // where K and Q stand for the outermost rook on that side of the king
+        self.can_castle = ['-'; 4];
+        self.castle_files = [7, 0, 7, 0];
+        if field == "-" {
+            return Ok(());
+        }
+        for c in field.chars() {
+            let (row, first) = if c.is_ascii_uppercase() {
+                (7, 0)
+            } else {
+                (0, 2)
             };
-            if i != 4 {
-                v[i] = x;
+            let color = if row == 7 {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            let rook = Piece::from_type(PieceType::Rook, color);
+            let king = (0..8).find(|col| self.squares[row][*col].as_type() == PieceType::King);
+            let rooks = (0..8).filter(|col| self.squares[row][*col] == rook);
+            let (kingside, file) = match (c.to_ascii_lowercase(), king) {
+                ('k', Some(k)) => (true, rooks.filter(|col| *col > k).max().unwrap_or(7)),
+                ('q', Some(k)) => (false, rooks.filter(|col| *col < k).min().unwrap_or(0)),
+                ('k', None) => (true, 7),
+                ('q', None) => (false, 0),
+                (f @ 'a'..='h', _) => {
+                    // a rook file is only written when playing Chess960
+                    self.chess960 = true;
+                    let file = f as usize - 'a' as usize;
+                    (file > king.unwrap_or(4), file)
+                }
+                _ => return Err(format!("invalid castling rights '{}'", field)),
+            };
+            let i = if kingside { first } else { first + 1 };
+            self.can_castle[i] = ['K', 'Q', 'k', 'q'][i];
+            self.castle_files[i] = file;
+            let home = king == Some(4) && file == if kingside { 7 } else { 0 };
+            if king.is_some() && !home {
+                self.chess960 = true;
             }
         }
-        return v;
+        return Ok(());
+    }
+
+    fn castling_field(&self, shredder: bool) -> String {
+        // X-FEN keeps KQkq unless another rook stands further out on that side
+        let mut field = String::new();
+        for (i, right) in self.can_castle.iter().enumerate() {
+            if *right == '-' {
+                continue;
+            }
+            let (row, file) = (if i < 2 { 7 } else { 0 }, self.castle_files[i]);
+            let rook = self.squares[row][file];
+            let outside = if i.is_multiple_of(2) { file + 1..8 } else { 0..file };
+            let hidden = outside
+                .into_iter()
+                .any(|col| self.squares[row][col] == rook);
+            if self.chess960 && (shredder || hidden) {
+                let letter = (b'a' + file as u8) as char;
+                field.push(if i < 2 {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                });
+            } else {
+                field.push(*right);
+            }
+        }
+        if field.is_empty() {
+            field.push('-');
+        }
+        return field;
     }
 
     fn get_en_passant(string: String) -> (char, char) {
-        if string == String::from("-") {
+        if string == "-" {
             return ('-', '-');
         }
-        return (
-            string.chars().next().unwrap(),
-            string.chars().next().unwrap(),
-        );
+        let mut chars = string.chars();
+        return (chars.next().unwrap_or('-'), chars.next().unwrap_or('-'));
     }
+
     pub fn from_fen(string: String) -> Board {
+        match Board::parse_fen(&string) {
+            Ok(board) => board,
+            Err(e) => panic!("Invalid FEN: {}", e),
+        }
+    }
+
+    pub fn parse_fen(string: &str) -> Result<Board, String> {
+        // The move counters may be left off, as in EPD
         let mut x = string.split_whitespace();
         let board = match x.next() {
             Some(y) => y.to_string(),
-            None => panic!("Invalid FEN"),
+            None => return Err(String::from("empty FEN")),
         };
+        let (board, pocket, promoted) = Board::split_placement(&board)?;
+        Board::validate_placement(&board)?;
         let to_move = match x.next() {
-            Some(y) => match y {
-                "w" => Turn::White,
-                "b" => Turn::Black,
-                _ => Turn::White,
-            },
-            None => panic!("Invalid FEN"),
+            Some("w") => Turn::White,
+            Some("b") => Turn::Black,
+            Some(y) => return Err(format!("invalid side to move '{}'", y)),
+            None => return Err(String::from("missing side to move")),
         };
         let castle = match x.next() {
-            Some(y) => Board::get_castling(y.to_string()),
-            None => panic!("Invalid FEN"),
+            Some(y) => y,
+            None => return Err(String::from("missing castling rights")),
         };
         let en_passant = match x.next() {
-            Some(y) => Board::get_en_passant(y.to_string()),
-            None => panic!("Invalid FEN"),
+            Some(y) if y == "-" || Board::parse_square(y).is_some() => {
+                Board::get_en_passant(y.to_string())
+            }
+            Some(y) => return Err(format!("invalid en passant square '{}'", y)),
+            None => return Err(String::from("missing en passant square")),
         };
+        // Three-check counters come as 3+3 after the en passant square or +0+0 at the end
+        let mut checks = None;
+        let mut counters = Vec::new();
+        for field in x {
+            match variant::parse_check_counter(field) {
+                Some(c) => checks = Some(c),
+                None => counters.push(field),
+            }
+        }
+        let mut x = counters.into_iter();
         let half_move = match x.next() {
-            Some(y) => y.to_string().parse::<u32>().unwrap(),
-            None => panic!("Invalid FEN"),
+            Some(y) => match y.parse::<u32>() {
+                Ok(n) => n,
+                Err(_) => return Err(format!("invalid halfmove clock '{}'", y)),
+            },
+            None => 0,
         };
         let full_move = match x.next() {
-            Some(y) => y.to_string().parse::<u32>().unwrap(),
-            None => panic!("Invalid FEN"),
+            Some(y) => match y.parse::<u32>() {
+                Ok(n) => n,
+                Err(_) => return Err(format!("invalid move number '{}'", y)),
+            },
+            None => 1,
         };
         let pieces = Board::pieces_from_fen(board);
-        return Board {
+        let mut board = Board {
             squares: pieces,
             move_number: full_move,
-            to_move: to_move,
-            can_castle: castle,
-            half_move: half_move,
-            en_passant: en_passant,
+            to_move,
+            can_castle: ['-'; 4],
+            half_move,
+            en_passant,
+            castle_files: [7, 0, 7, 0],
+            chess960: false,
+            state: VariantState::new(),
         };
+        if let Some(checks) = checks {
+            board.state.checks = checks;
+        }
+        if let Some(pocket) = pocket {
+            for c in pocket.chars().filter(|c| *c != '-') {
+                let piece = Piece::from_char(c);
+                if !POCKET_TYPES.contains(&piece.as_type()) {
+                    return Err(format!("invalid piece in hand '{}'", c));
+                }
+                board.state.add_to_pocket(piece);
+            }
+        }
+        board.state.promoted = promoted;
+        board.set_castling(castle)?;
+        return Ok(board);
+    }
+
+    fn split_placement(text: &str) -> Result<(String, Option<String>, u64), String> {
+        // Crazyhouse pockets come in brackets, as in [Qn], or as a ninth rank, and a
+        // promoted piece is followed by a ~
+        let (placement, pocket) = if let Some(open) = text.find('[') {
+            if !text.ends_with(']') {
+                return Err(format!("unclosed pocket in '{}'", text));
+            }
+            (&text[..open], Some(&text[open + 1..text.len() - 1]))
+        } else if text.split('/').count() == 9 {
+            let cut = text.rfind('/').unwrap_or(text.len());
+            (&text[..cut], Some(&text[cut + 1..]))
+        } else {
+            (text, None)
+        };
+        let mut clean = String::new();
+        let mut promoted = 0u64;
+        let (mut row, mut col) = (0, 0);
+        for c in placement.chars() {
+            match c {
+                '~' if col > 0 && row < 8 => promoted |= 1 << (row * 8 + col - 1),
+                '~' => return Err(String::from("'~' must follow a piece")),
+                '/' => {
+                    row += 1;
+                    col = 0;
+                    clean.push(c);
+                }
+                _ => {
+                    col += c.to_digit(10).unwrap_or(1) as usize;
+                    clean.push(c);
+                }
+            }
+        }
+        return Ok((clean, pocket.map(String::from), promoted));
+    }
+
+    fn validate_placement(pieces: &str) -> Result<(), String> {
+        let lines: Vec<&str> = pieces.split('/').collect();
+        if lines.len() != 8 {
+            return Err(format!("expected 8 ranks, found {}", lines.len()));
+        }
+        for l in lines {
+            let mut count = 0;
+            for c in l.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    count += d;
+                } else if Piece::from_char(c) != Piece::Blank {
+                    count += 1;
+                } else {
+                    return Err(format!("invalid piece '{}'", c));
+                }
+            }
+            if count != 8 {
+                return Err(format!("rank '{}' does not have 8 squares", l));
+            }
+        }
+        return Ok(());
     }
 
     pub fn pieces_from_fen(pieces: String) -> [[Piece; 8]; 8] {
         let lines: Vec<&str> = pieces.split('/').collect();
         let mut squares = [[Piece::Blank; 8]; 8];
-        for (i, l) in lines.iter().enumerate() {
-            println!("{}", l);
+        for (i, l) in lines.iter().enumerate().take(8) {
             let mut j = 0;
             for c in l.chars() {
-                if c.is_digit(8) {
-                    for _ in 0..c.to_digit(8).unwrap() {
-                        squares[i][j] = Piece::Blank;
-                        j += 1;
-                    }
-                } else {
+                if c.is_ascii_digit() {
+                    j += c.to_digit(10).unwrap() as usize;
+                } else if j < 8 {
                     squares[i][j] = Piece::from_char(c);
                     j += 1;
                 }
@@ -180,64 +452,123 @@ impl Board {
         return squares;
     }
 
-    pub fn to_FEN(self) {
-        //TODO: impl
+    pub fn to_fen(self) -> String {
+        return self.fen(false);
     }
 
-    pub fn square_to_row_col(square_string: Option<&str>) -> (usize, usize) {
-        fn to_col(x: Option<&str>) -> usize {
-            match x {
-                Some(x) => match &*x {
-                    "a" => 0,
-                    "b" => 1,
-                    "c" => 2,
-                    "d" => 3,
-                    "e" => 4,
-                    "f" => 5,
-                    "g" => 6,
-                    "h" => 7,
-                    _ => panic!("Invalid square!"),
-                },
-                None => panic!("Invalid square!"),
-            }
-        }
+    pub fn to_shredder_fen(self) -> String {
+        // castling rights always given by the rook files, as in HAha
+        return self.fen(true);
+    }
 
-        fn to_row(x: Option<&str>) -> usize {
-            match x {
-                Some(x) => match &*x {
-                    // Reversed because index is at top
-                    "1" => 7,
-                    "2" => 6,
-                    "3" => 5,
-                    "4" => 4,
-                    "5" => 3,
-                    "6" => 2,
-                    "7" => 1,
-                    "8" => 0,
-                    _ => panic!("Invalid square!"),
-                },
-                None => panic!("Invalid square!"),
+    fn fen(self, shredder: bool) -> String {
+        let mut rows: Vec<String> = Vec::new();
+        for (i, row) in self.squares.iter().enumerate() {
+            let mut row_str = String::new();
+            let mut empty = 0;
+            for (j, piece) in row.iter().enumerate() {
+                if *piece == Piece::Blank {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    row_str.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                row_str.push(piece.as_char());
+                if self.state.is_promoted((i, j)) {
+                    row_str.push('~');
+                }
             }
+            if empty > 0 {
+                row_str.push_str(&empty.to_string());
+            }
+            rows.push(row_str);
         }
+        let to_move = match self.to_move {
+            Turn::White => "w",
+            Turn::Black => "b",
+        };
+        let placement = rows.join("/");
+        let castle = self.castling_field(shredder);
+        let en_passant = match self.en_passant_square() {
+            Some(square) => Board::square_name(square),
+            None => String::from("-"),
+        };
+        return format!(
+            "{} {} {} {} {} {}",
+            placement,
+            to_move,
+            castle,
+            en_passant,
+            self.half_move,
+            self.move_number
+        );
+    }
+
+    pub fn square_to_row_col(square_string: Option<&str>) -> (usize, usize) {
         // converts a 2 char board position into a tuple
         match square_string {
-            Some(s) => (to_row(s.get(1..2)), to_col(s.get(0..1))),
+            Some(s) => match Board::parse_square(s) {
+                Some(square) => square,
+                None => panic!("Invalid square!"),
+            },
             None => (0, 0),
         }
     }
 
+    pub fn parse_square(square_string: &str) -> Option<(usize, usize)> {
+        let mut chars = square_string.chars();
+        let col = match chars.next() {
+            Some(c) if ('a'..='h').contains(&c) => c as usize - 'a' as usize,
+            _ => return None,
+        };
+        // Reversed because index is at top
+        let row = match chars.next() {
+            Some(c) if ('1'..='8').contains(&c) => '8' as usize - c as usize,
+            _ => return None,
+        };
+        if chars.next().is_some() {
+            return None;
+        }
+        return Some((row, col));
+    }
+
+    pub fn square_name(square: (usize, usize)) -> String {
+        let file = (b'a' + square.1 as u8) as char;
+        let rank = (b'8' - square.0 as u8) as char;
+        return format!("{}{}", file, rank);
+    }
+
+    pub fn en_passant_square(&self) -> Option<(usize, usize)> {
+        if self.en_passant.0 == '-' {
+            return None;
+        }
+        let name: String = [self.en_passant.0, self.en_passant.1].iter().collect();
+        return Board::parse_square(&name);
+    }
+
+    pub fn hash(&self) -> u64 {
+        return zobrist::hash(self);
+    }
+
     pub fn is_occupied(self, target: (usize, usize)) -> bool {
         return self.squares[target.0][target.1] != Piece::Blank;
     }
 
     pub fn get_path(start: (usize, usize), target: (usize, usize)) -> Vec<(usize, usize)> {
+        // squares strictly between start and target along a straight line
         let mut path: Vec<(usize, usize)> = Vec::new();
         let ydiff = target.0 as i8 - start.0 as i8;
         let xdiff = target.1 as i8 - start.1 as i8;
-        for _ in 0..cmp::max(xdiff.abs(), ydiff.abs()) {
+        let steps = cmp::max(xdiff.abs(), ydiff.abs());
+        if steps == 0 {
+            return path;
+        }
+        for i in 1..steps {
             path.push((
-                (start.0 as i8 + ydiff / cmp::max(xdiff.abs(), ydiff.abs())) as usize,
-                (start.1 as i8 + xdiff / cmp::max(xdiff.abs(), ydiff.abs())) as usize,
+                (start.0 as i8 + i * ydiff / steps) as usize,
+                (start.1 as i8 + i * xdiff / steps) as usize,
             ))
         }
         return path;
@@ -249,7 +580,8 @@ impl Board {
         piece: Piece,
     ) -> bool {
         if piece.as_type() == PieceType::Knight {
-            return self.is_occupied(target);
+            // knights jump, only the target matters
+            return false;
         }
         let target_line = Board::get_path(start, target);
         for pos in target_line {
@@ -260,28 +592,217 @@ impl Board {
         return false;
     }
 
+    pub fn king_position(&self, color: PieceColor) -> Option<(usize, usize)> {
+        let king = Piece::from_type(PieceType::King, color);
+        for (i, row) in self.squares.iter().enumerate() {
+            for (j, piece) in row.iter().enumerate() {
+                if *piece == king {
+                    return Some((i, j));
+                }
+            }
+        }
+        return None;
+    }
+
+    pub fn is_attacked(self, target: (usize, usize), by: PieceColor) -> bool {
+        return self.is_square_attacked(target, by);
+    }
+
+    pub fn in_check(self, color: PieceColor) -> bool {
+        let king = match self.king_position(color) {
+            Some(king) => king,
+            None => return false,
+        };
+        return self.is_attacked(king, color.opposite());
+    }
+
+    pub fn is_in_check(self) -> bool {
+        return self.in_check(self.to_move.as_color());
+    }
+
+    fn castling_right(
+        self,
+        start: (usize, usize),
+        target: (usize, usize),
+        piece: Piece,
+    ) -> Option<usize> {
+        // the castling right a king move would use: two squares towards the rook
+        // normally, onto its own rook in Chess960
+        if piece.as_type() != PieceType::King {
+            return None;
+        }
+        let (row, first) = match piece.as_color() {
+            PieceColor::White => (7, 0),
+            _ => (0, 2),
+        };
+        if start.0 != row || target.0 != row {
+            return None;
+        }
+        return (first..first + 2).find(|i| {
+            if self.can_castle[*i] == '-' {
+                return false;
+            }
+            if self.chess960 {
+                return target.1 == self.castle_files[*i];
+            }
+            let end = if i.is_multiple_of(2) { 6 } else { 2 };
+            start.1 == 4 && target.1 == end
+        });
+    }
+
+    fn castling_squares(right: usize) -> (usize, usize) {
+        // where the king and rook end up, on the g and f or c and d files
+        if right.is_multiple_of(2) {
+            return (6, 5);
+        }
+        return (2, 3);
+    }
+
+    fn can_castle_through<V: Variant + ?Sized>(
+        self,
+        variant: &V,
+        start: (usize, usize),
+        right: usize,
+    ) -> bool {
+        // the rook must be home, the squares both pieces cross empty, and the king
+        // may not castle out of or through check
+        let row = start.0;
+        let color = self.squares[row][start.1].as_color();
+        let rook_col = self.castle_files[right];
+        if self.squares[row][rook_col] != Piece::from_type(PieceType::Rook, color) {
+            return false;
+        }
+        let (king_end, rook_end) = Board::castling_squares(right);
+        let low = start.1.min(rook_col).min(king_end).min(rook_end);
+        let high = start.1.max(rook_col).max(king_end).max(rook_end);
+        if (low..=high).any(|col| col != start.1 && col != rook_col && self.is_occupied((row, col)))
+        {
+            return false;
+        }
+        if variant.in_check(&self, color) {
+            return false;
+        }
+        // the square the king lands on is checked once the move is made
+        let passed = start.1.min(king_end)..=start.1.max(king_end);
+        return passed
+            .filter(|col| *col != king_end)
+            .all(|col| !self.is_attacked((row, col), color.opposite()));
+    }
+
+    fn castles(&self, start: (usize, usize), target: (usize, usize)) -> bool {
+        let moving = self.squares[start.0][start.1];
+        if moving.as_type() != PieceType::King {
+            return false;
+        }
+        let rook = Piece::from_type(PieceType::Rook, moving.as_color());
+        return self.squares[target.0][target.1] == rook
+            || (start.1 as i8 - target.1 as i8).abs() == 2;
+    }
+
+    pub fn is_castling(&self, m: &Move) -> bool {
+        // castling in either notation, the king moving two squares or taking its rook
+        return self.castles(m.start, m.end);
+    }
+
     pub fn validate_move(
         self,
         start: (usize, usize),
         target: (usize, usize),
         piece: Piece,
     ) -> bool {
+        return self.validate_move_for(&Standard, start, target, piece);
+    }
+
+    pub fn validate_move_for<V: Variant + ?Sized>(
+        self,
+        variant: &V,
+        start: (usize, usize),
+        target: (usize, usize),
+        piece: Piece,
+    ) -> bool {
+        // the moves of the normal pieces, with the variant deciding what counts as
+        // leaving the king in check
         if piece.as_char() == Piece::Blank.as_char() {
             // Cannot move an empty square
             return false;
         }
+        if start == target {
+            return self.validate_drop_for(variant, target, piece);
+        }
+        if self.squares[start.0][start.1] != piece {
+            // The piece has to actually be on the start square
+            return false;
+        }
         if self.to_move.as_color() != piece.as_color() {
             // Cannot move opponent's piece
             return false;
         }
-        if !piece.valid_move(start, target, self.can_castle) {
+        if let Some(right) = self.castling_right(start, target, piece) {
+            if !self.can_castle_through(variant, start, right) {
+                return false;
+            }
+            let mut after = self;
+            variant.play_move(&mut after, Move::new(piece, start, target));
+            return variant.is_safe_for(&after, piece.as_color());
+        }
+        let captured = self.squares[target.0][target.1];
+        if captured.as_color() == piece.as_color() {
+            // Cannot capture your own piece
+            return false;
+        }
+        // castling was dealt with above
+        if !piece.valid_move(start, target, ['-'; 4]) {
             return false;
         }
         if self.piece_in_path(start, target, piece) {
             return false;
         }
-        return true;
+        if piece.as_type() == PieceType::Pawn {
+            if start.1 == target.1 {
+                // pawns can't capture straight ahead
+                if captured != Piece::Blank {
+                    return false;
+                }
+            } else if captured == Piece::Blank && self.en_passant_square() != Some(target) {
+                // diagonal pawn moves must capture
+                return false;
+            }
+        }
+        // Cannot leave your own king in check
+        let mut after = self;
+        variant.play_move(&mut after, Move::new(piece, start, target));
+        return variant.is_safe_for(&after, piece.as_color());
     }
+
+    fn validate_drop_for<V: Variant + ?Sized>(
+        self,
+        variant: &V,
+        target: (usize, usize),
+        piece: Piece,
+    ) -> bool {
+        // a piece in hand goes on any empty square, except pawns on the first or last rank
+        if piece.as_color() != self.to_move.as_color()
+            || self.state.in_hand(piece) == 0
+            || self.is_occupied(target)
+        {
+            return false;
+        }
+        if piece.as_type() == PieceType::Pawn && (target.0 == 0 || target.0 == 7) {
+            return false;
+        }
+        let mut after = self;
+        variant.play_move(&mut after, Move::drop(piece, target));
+        return variant.is_safe_for(&after, piece.as_color());
+    }
+
+    fn drop_piece(&mut self, piece: Piece, target: (usize, usize)) {
+        self.squares[target.0][target.1] = piece;
+        self.state.take_from_pocket(piece);
+        self.en_passant = ('-', '-');
+        self.update_half_move(false);
+        self.increment_move();
+    }
+
     pub fn increment_move(&mut self) {
         if self.to_move == Turn::Black {
             self.move_number += 1;
@@ -290,7 +811,24 @@ impl Board {
     }
 
     fn update_castling(&mut self, piece: Piece, location: (usize, usize), target: (usize, usize)) {
-        //update can_castle
+        // moving the king loses both rights, moving or capturing a rook loses one
+        match piece {
+            Piece::WKing => {
+                self.can_castle[0] = '-';
+                self.can_castle[1] = '-';
+            }
+            Piece::BKing => {
+                self.can_castle[2] = '-';
+                self.can_castle[3] = '-';
+            }
+            _ => {}
+        }
+        for i in 0..4 {
+            let home = (if i < 2 { 7 } else { 0 }, self.castle_files[i]);
+            if location == home || target == home {
+                self.can_castle[i] = '-';
+            }
+        }
     }
 
     fn update_en_passant(
@@ -299,36 +837,172 @@ impl Board {
         location: (usize, usize),
         target: (usize, usize),
     ) {
-        // Update the en passant square
+        // Update the en passant square, only for a pawn leaving its second rank as a Horde
+        // pawn pushed from the first rank can't be taken en passant
+        let second_rank = if piece.as_color() == PieceColor::White {
+            6
+        } else {
+            1
+        };
+        if piece.as_type() == PieceType::Pawn
+            && location.0 == second_rank
+            && (location.0 as i8 - target.0 as i8).abs() == 2
+        {
+            let name = Board::square_name(((location.0 + target.0) / 2, location.1));
+            self.en_passant = Board::get_en_passant(name);
+        } else {
+            self.en_passant = ('-', '-');
+        }
     }
 
-    fn update_half_move(&mut self, piece_taken: bool) {
-        if piece_taken {
+    fn update_half_move(&mut self, reset: bool) {
+        // reset on captures and pawn moves
+        if reset {
             self.half_move = 0;
         } else {
             self.half_move += 1;
         }
     }
 
+    pub fn is_capture(&self, m: &Move) -> bool {
+        return (self.is_occupied(m.end) && !self.is_castling(m))
+            || (m.piece.as_type() == PieceType::Pawn && m.start.1 != m.end.1);
+    }
+
     pub fn move_piece(&mut self, piece: Piece, location: (usize, usize), target: (usize, usize)) {
-        self.update_half_move(self.squares[target.0][target.1] != Piece::Blank);
+        // `piece` is what ends up on the target square, so promotions pass the new piece
+        let moving = self.squares[location.0][location.1];
+        if self.castles(location, target) {
+            self.castle(location, target);
+            self.update_castling(moving, location, target);
+            self.update_en_passant(moving, location, target);
+            self.increment_move();
+            return;
+        }
+        let captured = self.squares[target.0][target.1];
+        let en_passant = moving.as_type() == PieceType::Pawn
+            && location.1 != target.1
+            && captured == Piece::Blank;
+        self.update_half_move(captured != Piece::Blank || moving.as_type() == PieceType::Pawn);
         self.squares[target.0][target.1] = piece;
         self.squares[location.0][location.1] = Piece::Blank;
-        self.update_castling(piece, location, target);
-        self.update_en_passant(piece, location, target);
+        if en_passant {
+            self.squares[location.0][target.1] = Piece::Blank;
+        }
+        self.update_castling(moving, location, target);
+        self.update_en_passant(moving, location, target);
         self.increment_move();
     }
 
-    pub fn make_move_from_string(&mut self, move_string: String) {
-        // Expecting a 4 char string, from original location to target location
-        let location = Board::square_to_row_col(move_string.get(0..2));
-        let target = Board::square_to_row_col(move_string.get(2..4));
-        let piece = self.squares[location.0][location.1];
+    pub fn remove_piece(&mut self, square: (usize, usize)) {
+        // as when a piece is lost other than by capture, taking its castling rights along
+        let piece = self.squares[square.0][square.1];
+        self.squares[square.0][square.1] = Piece::Blank;
+        self.update_castling(piece, square, square);
+    }
 
-        if self.validate_move(location, target, piece) {
-            self.move_piece(piece, location, target)
+    fn castle(&mut self, king: (usize, usize), target: (usize, usize)) {
+        // the king and rook both land on their usual files, whatever they started on
+        let row = king.0;
+        let first = if row == 7 { 0 } else { 2 };
+        let right = if target.1 > king.1 { first } else { first + 1 };
+        let rook_start = if self.is_occupied(target) {
+            target.1
         } else {
-            panic!("Invalid move!")
+            self.castle_files[right]
+        };
+        let (king_end, rook_end) = Board::castling_squares(right);
+        let (moving, rook) = (self.squares[row][king.1], self.squares[row][rook_start]);
+        self.squares[row][king.1] = Piece::Blank;
+        self.squares[row][rook_start] = Piece::Blank;
+        self.squares[row][king_end] = moving;
+        self.squares[row][rook_end] = rook;
+        self.update_half_move(false);
+    }
+
+    pub fn parse_move(&self, move_string: &str) -> Result<Move, String> {
+        return self.parse_move_for(&Standard, move_string);
+    }
+
+    pub fn parse_move_for<V: Variant + ?Sized>(
+        &self,
+        variant: &V,
+        move_string: &str,
+    ) -> Result<Move, String> {
+        // Long algebraic notation, a missing promotion piece means a queen
+        let move_string = move_string.trim();
+        if move_string.len() == 4 && move_string.is_ascii() && &move_string[1..2] == "@" {
+            return self.parse_drop(variant, move_string);
+        }
+        if move_string.len() < 4 || move_string.len() > 5 || !move_string.is_ascii() {
+            return Err(format!("'{}' is not a move like e2e4", move_string));
+        }
+        let location = match Board::parse_square(&move_string[0..2]) {
+            Some(s) => s,
+            None => return Err(format!("invalid square '{}'", &move_string[0..2])),
+        };
+        let target = match Board::parse_square(&move_string[2..4]) {
+            Some(s) => s,
+            None => return Err(format!("invalid square '{}'", &move_string[2..4])),
+        };
+        let promotion = match move_string[4..].chars().next() {
+            Some(c) => match variant
+                .promotion_types()
+                .iter()
+                .find(|t| t.as_char() == c.to_ascii_lowercase())
+            {
+                Some(t) => Some(*t),
+                None => return Err(format!("invalid promotion piece '{}'", c)),
+            },
+            None => None,
+        };
+        let piece = self.squares[location.0][location.1];
+        if piece == Piece::Blank {
+            return Err(format!("no piece on {}", &move_string[0..2]));
+        }
+        if piece.as_color() != self.to_move.as_color() {
+            return Err(format!("it is {}'s turn", self.to_move.to_string()));
+        }
+        if !self.validate_move_for(variant, location, target, piece) {
+            return Err(format!("{} is not a legal move", move_string));
+        }
+        let mut m = Move::new(piece, location, target);
+        if piece.as_type() == PieceType::Pawn && (target.0 == 0 || target.0 == 7) {
+            let ptype = promotion.unwrap_or(PieceType::Queen);
+            m.promotion = Piece::from_type(ptype, piece.as_color());
+        } else if promotion.is_some() {
+            return Err(format!("{} is not a promotion", move_string));
+        }
+        return Ok(m);
+    }
+
+    fn parse_drop<V: Variant + ?Sized>(
+        &self,
+        variant: &V,
+        move_string: &str,
+    ) -> Result<Move, String> {
+        // P@e4, the piece letter in either case
+        let letter = move_string.chars().next().unwrap_or(' ').to_ascii_lowercase();
+        let ptype = match POCKET_TYPES.iter().find(|t| t.as_char() == letter) {
+            Some(t) => *t,
+            None => return Err(format!("invalid piece to drop '{}'", letter)),
+        };
+        let target = match Board::parse_square(&move_string[2..]) {
+            Some(s) => s,
+            None => return Err(format!("invalid square '{}'", &move_string[2..])),
+        };
+        let piece = Piece::from_type(ptype, self.to_move.as_color());
+        if !self.validate_drop_for(variant, target, piece) {
+            return Err(format!("{} is not a legal move", move_string));
+        }
+        return Ok(Move::drop(piece, target));
+    }
+
+    pub fn make_move_from_string(&mut self, move_string: String) {
+        // Expecting a 4 char string, from original location to target location
+        match self.parse_move(&move_string) {
+            Ok(m) => self.make_move_from_move(m),
+            Err(_) => panic!("Invalid move!"),
         }
     }
 
@@ -338,45 +1012,144 @@ impl Board {
         let piece = _move.piece;
 
         if self.validate_move(location, target, piece) {
-            self.move_piece(piece, location, target)
+            self.play_move(_move)
         } else {
             panic!("Invalid move!")
         }
     }
 
+    pub fn play_move(&mut self, _move: Move) {
+        // Makes a move without validating it, for moves that came from generate_moves
+        if _move.is_drop() {
+            self.drop_piece(_move.piece, _move.end);
+            return;
+        }
+        let mut placed = _move.promotion;
+        if placed == Piece::Blank {
+            placed = _move.piece;
+            if placed.as_type() == PieceType::Pawn && (_move.end.0 == 0 || _move.end.0 == 7) {
+                placed = Piece::from_type(PieceType::Queen, placed.as_color());
+            }
+        }
+        self.move_piece(placed, _move.start, _move.end)
+    }
+
     pub fn generate_moves(self) -> Vec<Move> {
+        return self.generate_moves_for(&Standard);
+    }
+
+    pub fn generate_moves_for<V: Variant + ?Sized>(self, variant: &V) -> Vec<Move> {
         // Horrendously inneficient way to generate moves
         let mut v: Vec<Move> = Vec::new();
+        let color = self.to_move.as_color();
         for (i, row) in self.squares.iter().enumerate() {
             for (j, col) in row.iter().enumerate() {
                 let start = (i, j);
                 let piece = col;
+                if piece.as_color() != color {
+                    continue;
+                }
                 for (m, row2) in self.squares.iter().enumerate() {
                     for (n, _) in row2.iter().enumerate() {
                         let target = (m, n);
-                        if self.validate_move(start, target, *piece) {
-                            v.push(Move {
-                                piece: *piece,
-                                start: start,
-                                end: target,
-                            });
+                        if self.validate_move_for(variant, start, target, *piece) {
+                            if piece.as_type() == PieceType::Pawn && (m == 0 || m == 7) {
+                                for t in variant.promotion_types() {
+                                    let mut promotion = Move::new(*piece, start, target);
+                                    promotion.promotion = Piece::from_type(*t, color);
+                                    v.push(promotion);
+                                }
+                            } else {
+                                v.push(Move::new(*piece, start, target));
+                            }
                         }
                     }
                 }
             }
         }
+        v.extend(self.generate_drops(variant));
         return v;
     }
 
+    fn generate_drops<V: Variant + ?Sized>(self, variant: &V) -> Vec<Move> {
+        // out of check no drop can expose the king, so only then is each one tried
+        let color = self.to_move.as_color();
+        if POCKET_TYPES.iter().all(|t| self.state.in_hand(Piece::from_type(*t, color)) == 0) {
+            return Vec::new();
+        }
+        let in_check = variant.in_check(&self, color);
+        let mut drops = Vec::new();
+        for ptype in POCKET_TYPES.iter() {
+            let piece = Piece::from_type(*ptype, color);
+            if self.state.in_hand(piece) == 0 {
+                continue;
+            }
+            for row in 0..8 {
+                for col in 0..8 {
+                    let target = (row, col);
+                    if self.is_occupied(target)
+                        || (*ptype == PieceType::Pawn && (row == 0 || row == 7))
+                        || (in_check && !self.validate_drop_for(variant, target, piece))
+                    {
+                        continue;
+                    }
+                    drops.push(Move::drop(piece, target));
+                }
+            }
+        }
+        return drops;
+    }
+
     pub fn recurse_gen_moves(self) -> Vec<Vec<Move>> {
         let mut v: Vec<Vec<Move>> = Vec::new();
         for m in self.generate_moves() {
-            let mut temp: Board = self.clone();
+            let mut temp: Board = self;
             temp.make_move_from_move(m);
             v.push(temp.generate_moves());
         }
         return v;
     }
+
+    pub fn perft(self, depth: u32) -> u64 {
+        // counts the leaf nodes of the legal move tree, for checking move generation
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut count = 0;
+        for m in moves {
+            let mut temp: Board = self;
+            temp.play_move(m);
+            count += temp.perft(depth - 1);
+        }
+        return count;
+    }
+
+    pub fn is_checkmate(self) -> bool {
+        return self.is_in_check() && self.generate_moves().is_empty();
+    }
+
+    pub fn is_stalemate(self) -> bool {
+        return !self.is_in_check() && self.generate_moves().is_empty();
+    }
+
+    pub fn is_insufficient_material(&self) -> bool {
+        // only bare kings, or kings plus a single minor piece
+        let mut minors = 0;
+        for row in &self.squares {
+            for piece in row {
+                match piece.as_type() {
+                    PieceType::King | PieceType::Blank => {}
+                    PieceType::Knight | PieceType::Bishop => minors += 1,
+                    _ => return false,
+                }
+            }
+        }
+        return minors <= 1;
+    }
 }
 
 #[cfg(test)]
@@ -468,6 +1241,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
         ));
         assert_eq!(board1.to_string(), board2.to_string());
+        assert!(board2.en_passant == ('e', '3'));
         board1.make_move_from_string(String::from("c7c5"));
         board1.make_move_from_string(String::from("g1f3"));
         let board2 = Board::from_fen(String::from(
@@ -475,4 +1249,200 @@ mod tests {
         ));
         assert_eq!(board1.to_string(), board2.to_string());
     }
+
+    #[test]
+    fn test_to_fen() {
+        let mut board = Board::default();
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        board.make_move_from_string(String::from("e2e4"));
+        assert_eq!(
+            board.to_fen(),
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        for fen in [
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w Kq - 0 1",
+            "8/8/8/8/8/8/8/k6K w - - 99 120",
+        ]
+        .iter()
+        {
+            assert_eq!(Board::from_fen(fen.to_string()).to_fen(), *fen);
+        }
+    }
+
+    #[test]
+    fn test_parse_fen_errors() {
+        assert!(Board::parse_fen("").is_err());
+        assert!(Board::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1").is_err());
+        assert!(Board::parse_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").is_err());
+        assert!(Board::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x - - 0 1").is_err());
+        assert!(Board::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e9").is_err());
+        // move counters are optional
+        let board = Board::parse_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -");
+        assert_eq!(board.unwrap().move_number, 1);
+    }
+
+    #[test]
+    fn test_get_path() {
+        assert!(Board::get_path((7, 0), (0, 0)).len() == 6);
+        assert!(Board::get_path((7, 0), (6, 1)).is_empty());
+        assert!(Board::get_path((7, 2), (4, 5)) == vec![(6, 3), (5, 4)]);
+    }
+
+    #[test]
+    fn test_parse_move() {
+        let board = Board::default();
+        assert!(board.parse_move("g1f3").is_ok());
+        assert!(board.parse_move("g1g3").is_err());
+        assert!(board.parse_move("e2e4q").is_err());
+        assert!(board.parse_move("e2").is_err());
+        assert_eq!(board.parse_move("b1c3").unwrap().to_string(), "b1c3");
+    }
+
+    #[test]
+    fn test_capture() {
+        let mut board = Board::default();
+        for m in ["e2e4", "d7d5", "e4d5", "d8d5"].iter() {
+            board.make_move_from_string(m.to_string());
+        }
+        assert_eq!(
+            board.to_string(),
+            "rnb-kbnr\nppp-pppp\n--------\n---q----\n--------\n--------\nPPPP-PPP\nRNBQKBNR\n"
+        );
+        assert_eq!(board.half_move, 0);
+        // sliders can't jump over pieces
+        assert!(board.parse_move("d5d1").is_err());
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let mut board = Board::default();
+        for m in ["e2e4", "a7a6", "e4e5", "d7d5"].iter() {
+            board.make_move_from_string(m.to_string());
+        }
+        assert!(board.en_passant_square() == Some((2, 3)));
+        board.make_move_from_string(String::from("e5d6"));
+        assert!(board.squares[3][3] == Piece::Blank);
+        assert!(board.squares[2][3] == Piece::WPawn);
+        assert!(board.en_passant_square().is_none());
+    }
+
+    #[test]
+    fn test_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let mut board = Board::from_fen(String::from(fen));
+        board.make_move_from_string(String::from("e1g1"));
+        assert!(board.squares[7][6] == Piece::WKing);
+        assert!(board.squares[7][5] == Piece::WRook);
+        assert!(board.can_castle == ['-', '-', 'k', 'q']);
+        board.make_move_from_string(String::from("e8c8"));
+        assert!(board.squares[0][2] == Piece::BKing);
+        assert!(board.squares[0][3] == Piece::BRook);
+        assert!(board.can_castle == ['-'; 4]);
+
+        // no castling through an attacked square or out of check
+        let board = Board::from_fen(String::from("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1"));
+        assert!(board.parse_move("e1g1").is_err());
+        assert!(board.parse_move("e1c1").is_ok());
+        let board = Board::from_fen(String::from("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1"));
+        assert!(board.parse_move("e1c1").is_err());
+        // capturing a rook removes the right
+        let mut board = Board::from_fen(String::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"));
+        board.make_move_from_string(String::from("a1a8"));
+        assert!(board.can_castle == ['K', '-', 'k', '-']);
+    }
+
+    #[test]
+    fn test_chess960() {
+        assert_eq!(
+            Board::chess960_start(518).unwrap().to_fen(),
+            Board::default().to_fen()
+        );
+        assert_eq!(
+            Board::chess960_start(0).unwrap().to_shredder_fen(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+        );
+        assert!(Board::chess960_start(960).is_err());
+        // king-takes-rook castling, queenside the king moves right from b1 to c1
+        let fen = "rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK5R w HAha - 0 1";
+        let mut board = Board::parse_fen(fen).unwrap();
+        assert!(board.chess960);
+        assert!(board.parse_move("b1h1").is_ok());
+        let castle = board.parse_move("b1a1").unwrap();
+        assert_eq!(board.to_san(&castle), "O-O-O");
+        assert!(!board.is_capture(&castle));
+        board.play_move(castle);
+        assert_eq!(
+            board.to_fen(),
+            "rk5r/pppppppp/8/8/8/8/PPPPPPPP/2KR3R b kq - 1 1"
+        );
+        // X-FEN names the file when the right is not for the outermost rook
+        let board = Board::parse_fen("1r2k1rr/8/8/8/8/8/8/R3K1R1 w GAgb - 0 1").unwrap();
+        assert!(board.chess960);
+        assert_eq!(board.castle_files, [6, 0, 6, 1]);
+        assert_eq!(board.to_fen(), "1r2k1rr/8/8/8/8/8/8/R3K1R1 w KQgq - 0 1");
+        assert!(board.parse_move("e1a1").is_ok());
+        assert!(board.parse_move("e1c1").is_err());
+        assert!(Board::parse_fen("8/8/8/8/8/8/8/4K3 w X - 0 1").is_err());
+        let perft = Board::from_fen(String::from(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        ));
+        assert_eq!(perft.perft(2), 528);
+    }
+
+    #[test]
+    fn test_promotion() {
+        let mut board = Board::from_fen(String::from("8/P6k/8/8/8/8/8/4K3 w - - 0 1"));
+        assert_eq!(board.generate_moves().len(), 9);
+        board.make_move_from_string(String::from("a7a8n"));
+        assert!(board.squares[0][0] == Piece::WKnight);
+        let mut board = Board::from_fen(String::from("8/P6k/8/8/8/8/8/4K3 w - - 0 1"));
+        board.make_move_from_string(String::from("a7a8"));
+        assert!(board.squares[0][0] == Piece::WQueen);
+    }
+
+    #[test]
+    fn test_check() {
+        let mut board = Board::default();
+        for m in ["f2f3", "e7e5", "g2g4", "d8h4"].iter() {
+            board.make_move_from_string(m.to_string());
+        }
+        assert!(board.is_in_check());
+        assert!(board.is_checkmate());
+        let board = Board::from_fen(String::from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+        assert!(board.is_stalemate());
+        // pinned pieces can't move
+        let board = Board::from_fen(String::from("4r1k1/8/8/8/8/8/4N3/4K3 w - - 0 1"));
+        assert!(board.generate_moves().iter().all(|m| m.piece != Piece::WKnight));
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert!(Board::from_fen(String::from("8/8/4k3/8/8/2N5/8/4K3 w - - 0 1")).is_insufficient_material());
+        assert!(!Board::from_fen(String::from("8/8/4k3/8/8/2R5/8/4K3 w - - 0 1")).is_insufficient_material());
+        assert!(!Board::default().is_insufficient_material());
+    }
+
+    #[test]
+    fn test_perft() {
+        assert_eq!(Board::default().perft(3), 8902);
+        let kiwipete = Board::from_fen(String::from(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ));
+        assert_eq!(kiwipete.perft(1), 48);
+        assert_eq!(kiwipete.perft(2), 2039);
+        let endgame = Board::from_fen(String::from("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"));
+        assert_eq!(endgame.perft(3), 2812);
+        let promotions = Board::from_fen(String::from(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ));
+        assert_eq!(promotions.perft(2), 264);
+        let position5 = Board::from_fen(String::from(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ));
+        assert_eq!(position5.perft(2), 1486);
+    }
 }
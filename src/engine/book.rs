@@ -0,0 +1,183 @@
+use super::board::Turn;
+use super::game::GameResult;
+use super::pgn::PgnGame;
+use super::polyglot::{encode_move, polyglot_key, Book, BookEntry};
+
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct MoveStats {
+    // counted for the side that played the move
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+pub struct BookBuilder {
+    pub max_plies: usize,
+    pub min_games: u32,
+    // the least score in percent a move needs for the side playing it
+    pub min_score: u32,
+    stats: HashMap<(u64, u16), MoveStats>,
+    games: usize,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        return self.wins + self.draws + self.losses;
+    }
+
+    pub fn score(&self) -> u32 {
+        // percent, a draw counts half
+        if self.games() == 0 {
+            return 0;
+        }
+        return (200 * self.wins + 100 * self.draws) / (2 * self.games());
+    }
+
+    pub fn weight(&self) -> u32 {
+        // the usual Polyglot weighting, two points a win and one a draw
+        return 2 * self.wins + self.draws;
+    }
+}
+
+impl BookBuilder {
+    pub fn new(max_plies: usize) -> BookBuilder {
+        BookBuilder {
+            max_plies,
+            min_games: 1,
+            min_score: 0,
+            stats: HashMap::new(),
+            games: 0,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        return self.games;
+    }
+
+    pub fn add_game(&mut self, game: &PgnGame) -> bool {
+        // games without a result say nothing about the moves and are left out
+        let result = match GameResult::parse(&game.result) {
+            Some(r) => r,
+            None => return false,
+        };
+        let mut board = game.start;
+        for m in game.moves.iter().take(self.max_plies) {
+            let stats = self
+                .stats
+                .entry((polyglot_key(&board), encode_move(&m.mv)))
+                .or_default();
+            if result == GameResult::Draw {
+                stats.draws += 1;
+            } else if result == GameResult::win_for(board.to_move) {
+                stats.wins += 1;
+            } else {
+                stats.losses += 1;
+            }
+            board.play_move(m.mv);
+        }
+        self.games += 1;
+        return true;
+    }
+
+    pub fn stats(&self, key: u64, mv: u16) -> Option<MoveStats> {
+        return self.stats.get(&(key, mv)).copied();
+    }
+
+    pub fn build(&self) -> Book {
+        let kept: Vec<(&(u64, u16), &MoveStats)> = self
+            .stats
+            .iter()
+            .filter(|(_, s)| s.games() >= self.min_games && s.score() >= self.min_score)
+            .filter(|(_, s)| s.weight() > 0)
+            .collect();
+        // weights are scaled down to fit in 16 bits when a popular move would overflow
+        let max = kept.iter().map(|(_, s)| s.weight()).max().unwrap_or(0);
+        let scale = if max > u16::MAX as u32 {
+            u16::MAX as f64 / max as f64
+        } else {
+            1.0
+        };
+        let entries = kept
+            .iter()
+            .map(|((key, mv), s)| BookEntry {
+                key: *key,
+                mv: *mv,
+                weight: ((s.weight() as f64 * scale) as u16).max(1),
+                learn: 0,
+            })
+            .collect();
+        return Book::from_entries(entries);
+    }
+}
+
+pub fn engine_side(game: &PgnGame, name: &str) -> Option<Turn> {
+    // which colour the named engine played, for learning from its games
+    if game.tag("White") == Some(name) {
+        return Some(Turn::White);
+    }
+    if game.tag("Black") == Some(name) {
+        return Some(Turn::Black);
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::engine::board::Board;
+    use crate::engine::pgn::PgnReader;
+
+    const GAMES: &str = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n\
+                         [Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n\n\
+                         [Result \"0-1\"]\n\n1. d4 d5 0-1\n\n\
+                         [Result \"*\"]\n\n1. c4 *\n";
+
+    fn builder(max_plies: usize) -> BookBuilder {
+        let mut builder = BookBuilder::new(max_plies);
+        for game in PgnReader::new(GAMES.as_bytes()) {
+            builder.add_game(&game.unwrap());
+        }
+        return builder;
+    }
+
+    #[test]
+    fn test_stats() {
+        let builder = builder(2);
+        assert_eq!(builder.games(), 3);
+        let board = Board::default();
+        let key = polyglot_key(&board);
+        let e4 = encode_move(&board.parse_move("e2e4").unwrap());
+        let stats = builder.stats(key, e4).unwrap();
+        assert_eq!((stats.wins, stats.draws, stats.losses), (1, 1, 0));
+        assert_eq!(stats.score(), 75);
+        assert_eq!(stats.weight(), 3);
+        let c4 = encode_move(&board.parse_move("c2c4").unwrap());
+        assert!(builder.stats(key, c4).is_none());
+        // Nf3 is past the ply limit and the losing moves get no weight
+        assert_eq!(builder.build().len(), 3);
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut builder = builder(4);
+        let board = Board::default();
+        let book = builder.build();
+        let moves: Vec<(String, u16)> = book
+            .moves(&board)
+            .iter()
+            .map(|(m, w)| (m.to_string(), *w))
+            .collect();
+        // d4 lost its only game and gets no weight
+        assert_eq!(moves, vec![(String::from("e2e4"), 3)]);
+        builder.min_games = 2;
+        assert_eq!(builder.build().len(), 1);
+        builder.min_games = 1;
+        builder.min_score = 60;
+        let book = builder.build();
+        assert_eq!(book.len(), 3);
+        assert_eq!(Book::from_bytes(&book.to_bytes()).unwrap().len(), 3);
+    }
+}
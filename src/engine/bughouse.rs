@@ -0,0 +1,486 @@
+use super::board::{Board, Move, Turn};
+use super::game::Termination;
+use super::pgn::{format_tag, parse_tags};
+use super::piece::{Piece, PieceType};
+use super::variant::{Crazyhouse, Variant, POCKET_TYPES};
+
+// the players in the order BPGN lists them, board A before board B
+const PLAYER_TAGS: [&str; 4] = ["WhiteA", "BlackA", "WhiteB", "BlackB"];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Team {
+    // white on board A with black on board B
+    First,
+    // black on board A with white on board B
+    Second,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TeamResult {
+    Win(Team),
+    Draw,
+}
+
+#[derive(Clone)]
+pub struct BughouseMove {
+    // 0 for board A, 1 for board B
+    pub board: usize,
+    pub mv: Move,
+    pub san: String,
+    // milliseconds left on the mover's clock after the move
+    pub clock: u64,
+}
+
+#[derive(Clone)]
+pub struct Bughouse {
+    pub boards: [Board; 2],
+    // milliseconds left, by board and then white and black
+    pub clocks: [[u64; 2]; 2],
+    pub increment: u64,
+    pub moves: Vec<BughouseMove>,
+    pub tags: Vec<(String, String)>,
+    // a game without a time control never runs out of time
+    timed: bool,
+    result: Option<(TeamResult, Termination)>,
+}
+
+impl Team {
+    pub fn of(board: usize, side: Turn) -> Team {
+        // partners sit on different boards with different colours
+        if (board == 0) == (side == Turn::White) {
+            return Team::First;
+        }
+        return Team::Second;
+    }
+
+    pub fn opponent(&self) -> Team {
+        match *self {
+            Team::First => Team::Second,
+            Team::Second => Team::First,
+        }
+    }
+}
+
+impl TeamResult {
+    pub fn as_str(&self) -> &'static str {
+        // as seen from white on board A, the way BPGN writes it
+        match *self {
+            TeamResult::Win(Team::First) => "1-0",
+            TeamResult::Win(Team::Second) => "0-1",
+            TeamResult::Draw => "1/2-1/2",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<TeamResult> {
+        match text {
+            "1-0" => Some(TeamResult::Win(Team::First)),
+            "0-1" => Some(TeamResult::Win(Team::Second)),
+            "1/2-1/2" => Some(TeamResult::Draw),
+            _ => None,
+        }
+    }
+}
+
+fn side_index(side: Turn) -> usize {
+    return if side == Turn::White { 0 } else { 1 };
+}
+
+fn board_letter(board: usize, side: Turn) -> char {
+    // A and B for white's moves, a and b for black's
+    let letter = if board == 0 { 'A' } else { 'B' };
+    if side == Turn::White {
+        return letter;
+    }
+    return letter.to_ascii_lowercase();
+}
+
+impl Bughouse {
+    pub fn new(base: u64, increment: u64) -> Bughouse {
+        // base and increment in milliseconds
+        let start = Crazyhouse.start_position();
+        Bughouse {
+            boards: [start, start],
+            clocks: [[base; 2]; 2],
+            increment,
+            moves: Vec::new(),
+            tags: Vec::new(),
+            timed: base > 0,
+            result: None,
+        }
+    }
+
+    pub fn result(&self) -> Option<(TeamResult, Termination)> {
+        return self.result;
+    }
+
+    pub fn set_result(&mut self, result: TeamResult, termination: Termination) {
+        self.result = Some((result, termination));
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self
+            .tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str());
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn play(&mut self, board: usize, m: Move, elapsed: u64) -> Result<(), String> {
+        // elapsed is the time the mover took, in milliseconds
+        if self.result.is_some() {
+            return Err(String::from("the game is over"));
+        }
+        let position = self.boards[board];
+        if !position.validate_move_for(&Crazyhouse, m.start, m.end, m.piece) {
+            return Err(format!("{} is not a legal move", m));
+        }
+        let side = position.to_move;
+        let clock = &mut self.clocks[board][side_index(side)];
+        if self.timed {
+            if elapsed >= *clock {
+                *clock = 0;
+                let winner = Team::of(board, side).opponent();
+                self.result = Some((TeamResult::Win(winner), Termination::TimeForfeit));
+                return Err(format!(
+                    "{} on board {} is out of time",
+                    side.to_string(),
+                    board_letter(board, Turn::White)
+                ));
+            }
+            *clock = *clock - elapsed + self.increment;
+        }
+        let clock = *clock;
+
+        // the suffix is decided below, as a chess mate may not be a bughouse one
+        let mut san = position
+            .to_san_for(&Crazyhouse, &m)
+            .trim_end_matches(['+', '#'])
+            .to_string();
+        let before = position.state.pockets[side_index(side)];
+        Crazyhouse.play_move(&mut self.boards[board], m);
+        // whatever the capture put in the mover's pocket goes to the partner instead,
+        // who plays the colour it was taken from
+        let color = side.as_color();
+        for (kind, ptype) in POCKET_TYPES.iter().enumerate() {
+            let gained = self.boards[board].state.pockets[side_index(side)][kind]
+                .saturating_sub(before[kind]);
+            for _ in 0..gained {
+                self.boards[board]
+                    .state
+                    .take_from_pocket(Piece::from_type(*ptype, color));
+                self.boards[1 - board]
+                    .state
+                    .add_to_pocket(Piece::from_type(*ptype, color.opposite()));
+            }
+        }
+        let mate = is_mate(&self.boards[board]);
+        if mate {
+            san.push('#');
+        } else if self.boards[board].is_in_check() {
+            san.push('+');
+        }
+        self.moves.push(BughouseMove {
+            board,
+            mv: m,
+            san,
+            clock,
+        });
+
+        // a player with no move waits for their partner to send a piece, so stalemate
+        // and checks a drop could block don't end the game
+        if mate {
+            self.result = Some((
+                TeamResult::Win(Team::of(board, side)),
+                Termination::Checkmate,
+            ));
+        }
+        return Ok(());
+    }
+
+    pub fn play_str(&mut self, board: usize, text: &str, elapsed: u64) -> Result<Move, String> {
+        // SAN or coordinates, drops as N@f3
+        let position = &self.boards[board];
+        let m = position
+            .parse_san_for(&Crazyhouse, text)
+            .or_else(|_| Crazyhouse.parse_move(position, text))?;
+        self.play(board, m, elapsed)?;
+        return Ok(m);
+    }
+
+    pub fn to_bpgn(&self) -> String {
+        // moves are numbered per board, as in 1A. e4 1a. e5 1B. d4, each followed by
+        // the mover's clock in seconds
+        let mut text = String::new();
+        let result = self.result.map(|(r, _)| r.as_str()).unwrap_or("*");
+        for name in ["Event", "Site", "Date"] {
+            text.push_str(&format_tag(name, self.tag(name).unwrap_or("?")));
+        }
+        for name in PLAYER_TAGS {
+            text.push_str(&format_tag(name, self.tag(name).unwrap_or("?")));
+        }
+        let known = ["Event", "Site", "Date", "Result"];
+        for (name, value) in &self.tags {
+            if !known.contains(&name.as_str()) && !PLAYER_TAGS.contains(&name.as_str()) {
+                text.push_str(&format_tag(name, value));
+            }
+        }
+        text.push_str(&format_tag("Result", result));
+        text.push('\n');
+
+        let mut boards = [Crazyhouse.start_position(); 2];
+        let mut line = String::new();
+        let mut tokens: Vec<String> = Vec::new();
+        for m in &self.moves {
+            let board = &mut boards[m.board];
+            tokens.push(format!(
+                "{}{}. {}",
+                board.move_number,
+                board_letter(m.board, board.to_move),
+                m.san
+            ));
+            tokens.push(format!("{{{:.1}}}", m.clock as f64 / 1000.0));
+            board.to_move = board.to_move.opposite_turn();
+            if board.to_move == Turn::White {
+                board.move_number += 1;
+            }
+        }
+        tokens.push(result.to_string());
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        text.push_str(&line);
+        text.push('\n');
+        return text;
+    }
+
+    pub fn from_bpgn(text: &str) -> Result<Bughouse, String> {
+        // one game; a TimeControl tag like 180+2 sets the clocks, in seconds
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        for line in text.lines() {
+            if line.trim_start().starts_with('[') && movetext.trim().is_empty() {
+                parse_tags(line, &mut tags)?;
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+        let (base, increment) = match tags.iter().find(|(n, _)| n == "TimeControl") {
+            Some((_, value)) => parse_time_control(value)?,
+            None => (0, 0),
+        };
+        let mut game = Bughouse::new(base, increment);
+        game.tags = tags;
+
+        let mut board = None;
+        for word in movetext_tokens(&movetext)? {
+            let word = word.as_str();
+            if let Some(comment) = word.strip_prefix('{') {
+                // clocks are in seconds, other comments are skipped
+                let seconds = comment.trim_end_matches('}').trim().parse::<f64>();
+                if let (Ok(seconds), Some(last)) = (seconds, game.moves.last_mut()) {
+                    if base > 0 {
+                        let clock = (seconds * 1000.0).round() as u64;
+                        let side = game.boards[last.board].to_move.opposite_turn();
+                        game.clocks[last.board][side_index(side)] = clock;
+                        last.clock = clock;
+                    }
+                }
+                continue;
+            }
+            if let Some(result) = TeamResult::parse(word) {
+                game.result
+                    .get_or_insert((result, Termination::Adjudication));
+                break;
+            }
+            if word == "*" {
+                break;
+            }
+            if let Some(number) = word.strip_suffix('.') {
+                let letter = number.chars().last().unwrap_or(' ');
+                board = match letter.to_ascii_uppercase() {
+                    'A' => Some(0),
+                    'B' => Some(1),
+                    _ => return Err(format!("'{}' names no board", word)),
+                };
+                continue;
+            }
+            let b = match board.take() {
+                Some(b) => b,
+                None => return Err(format!("move '{}' has no board number", word)),
+            };
+            let ply = game.moves.len() + 1;
+            game.play_str(b, word, 0)
+                .map_err(|e| format!("ply {}: {}", ply, e))?;
+        }
+        return Ok(game);
+    }
+}
+
+pub fn is_mate(board: &Board) -> bool {
+    // checkmate whatever the partner may yet supply: no move now, and no empty square
+    // between the king and a lone checker to drop a piece on
+    if !board.is_in_check() || !board.generate_moves().is_empty() {
+        return false;
+    }
+    let color = board.to_move.as_color();
+    let king = match board.king_position(color) {
+        Some(king) => king,
+        None => return false,
+    };
+    let checkers = board.attackers_to(king, color.opposite());
+    if checkers.len() != 1 {
+        return true;
+    }
+    let checker = checkers[0];
+    if board.squares[checker.0][checker.1].as_type() == PieceType::Knight {
+        return true;
+    }
+    let distance = (checker.0 as i32 - king.0 as i32)
+        .abs()
+        .max((checker.1 as i32 - king.1 as i32).abs());
+    return distance == 1;
+}
+
+fn movetext_tokens(movetext: &str) -> Result<Vec<String>, String> {
+    // words and whole {comments}, split apart where they touch as pgn.rs does, so that
+    // 1A.e4{179.9} gives 1A. e4 {179.9}
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '{' {
+            let mut comment = String::from("{");
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => comment.push(c),
+                    None => return Err(String::from("unterminated comment")),
+                }
+            }
+            comment.push('}');
+            tokens.push(comment);
+        } else if !c.is_whitespace() {
+            let mut word = c.to_string();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '{' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            // a move number may be glued to its move
+            match word.find('.') {
+                Some(i) if i + 1 < word.len() => {
+                    let number = word[..=i].to_string();
+                    tokens.push(number);
+                    tokens.push(word[i + 1..].to_string());
+                }
+                _ => tokens.push(word),
+            }
+        }
+    }
+    return Ok(tokens);
+}
+
+fn parse_time_control(text: &str) -> Result<(u64, u64), String> {
+    let (base, increment) = text.split_once('+').unwrap_or((text, "0"));
+    let seconds = |s: &str| s.trim().parse::<u64>().map(|n| n * 1000);
+    return match (seconds(base), seconds(increment)) {
+        (Ok(base), Ok(increment)) => Ok((base, increment)),
+        _ => Err(format!("invalid time control '{}'", text)),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_partner_pocket() {
+        let mut game = Bughouse::new(180_000, 0);
+        for (board, text) in [(0, "e4"), (0, "d5"), (0, "exd5"), (1, "Nf3")] {
+            game.play_str(board, text, 1000).unwrap();
+        }
+        // white took a black pawn on board A, so black on board B can drop it
+        assert_eq!(game.boards[0].state.pockets, [[0; 5]; 2]);
+        assert_eq!(game.boards[1].state.pockets, [[0; 5], [1, 0, 0, 0, 0]]);
+        game.play_str(1, "P@e4", 2000).unwrap();
+        assert!(game.play_str(0, "P@e4", 0).is_err());
+        assert_eq!(game.clocks, [[178_000, 179_000], [179_000, 178_000]]);
+        assert_eq!(Team::of(0, Turn::White), Team::of(1, Turn::Black));
+
+        assert!(game.play_str(0, "Qxd5", 200_000).is_err());
+        assert_eq!(
+            game.result(),
+            Some((TeamResult::Win(Team::First), Termination::TimeForfeit))
+        );
+    }
+
+    #[test]
+    fn test_mate() {
+        let mate = |fen: &str| is_mate(&Crazyhouse.parse_fen(fen).unwrap());
+        // contact and knight checks can't be blocked
+        assert!(mate("4k3/8/8/8/8/5b2/5PqP/5RK1 w - - 0 1"));
+        assert!(mate("4k3/8/8/8/8/8/5nPP/6RK w - - 0 1"));
+        // mate in chess, but a piece dropped on the back rank would block it
+        let back_rank = Crazyhouse.parse_fen("6k1/8/8/8/8/8/5PPP/r5K1 w - - 0 1").unwrap();
+        assert!(back_rank.generate_moves().is_empty());
+        assert!(!is_mate(&back_rank));
+        // stalemate just waits
+        assert!(!mate("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+
+        // an untimed game ignores the time taken, and only a real mate gets a #
+        let mut game = Bughouse::new(0, 0);
+        for text in ["f3", "e5", "g4", "Qh4#"] {
+            game.play_str(0, text, 5000).unwrap();
+        }
+        assert_eq!(game.result(), None);
+        assert_eq!(game.moves[3].san, "Qh4+");
+        assert_eq!(game.clocks[0], [0, 0]);
+    }
+
+    #[test]
+    fn test_bpgn() {
+        let text = "[Event \"Club night\"]\n[WhiteA \"Ann\"]\n[TimeControl \"120+1\"]\n\n\
+            1A. f3 {119.0} 1a. e5 {118.5} 1B. e4 {120.1} 2A. g4 {118.0} 2a. Qh4# {117.0} 0-1\n";
+        let game = Bughouse::from_bpgn(text).unwrap();
+        // not mate in bughouse, as a piece could still be dropped on f2 or g3
+        assert_eq!(
+            game.result(),
+            Some((TeamResult::Win(Team::Second), Termination::Adjudication))
+        );
+        assert_eq!(game.moves.len(), 5);
+        assert_eq!(game.clocks[0], [118_000, 117_000]);
+        assert_eq!(game.tag("WhiteA"), Some("Ann"));
+
+        let written = game.to_bpgn();
+        assert!(written.contains("[Result \"0-1\"]"));
+        assert!(written.contains(
+            "1A. f3 {119.0} 1a. e5 {118.5} 1B. e4 {120.1} 2A. g4 {118.0} 2a. Qh4+ {117.0} 0-1"
+        ));
+        let again = Bughouse::from_bpgn(&written).unwrap();
+        assert_eq!(again.to_bpgn(), written);
+
+        // clock comments may touch the moves around them
+        let tight = "1A. e4{179.9} 1a.e5 {179.0}1B. d4{178.2} *";
+        let game = Bughouse::from_bpgn(tight).unwrap();
+        assert_eq!(game.moves.len(), 3);
+        assert!(Bughouse::from_bpgn("1A. e4 {179.9").is_err());
+    }
+}
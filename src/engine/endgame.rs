@@ -0,0 +1,499 @@
+use super::board::Board;
+use super::eval::piece_value;
+use super::piece::{Piece, PieceColor, PieceType};
+
+use std::sync::OnceLock;
+
+// a won ending, above any material count but well below the mate scores
+pub const KNOWN_WIN: i32 = 10000;
+// scale factors are in 64ths of the normal evaluation
+pub const SCALE_NORMAL: i32 = 64;
+const SCALE_OPPOSITE_BISHOPS: i32 = 16;
+const SCALE_OPPOSITE_BISHOPS_PAWNS_UP: i32 = 32;
+const SCALE_ROOK_VS_BISHOP: i32 = 8;
+
+// the KPK bitbase, by pawn row (from the 7th rank down), pawn file a-d, side to move and kings
+const KPK_SIZE: usize = 6 * 4 * 2 * 64 * 64;
+const INVALID: u8 = 0;
+const UNKNOWN: u8 = 1;
+const DRAW: u8 = 2;
+const WIN: u8 = 3;
+
+static KPK: OnceLock<Vec<u64>> = OnceLock::new();
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Recognized {
+    // a score for white that replaces the evaluation
+    Score(i32),
+    // the evaluation is multiplied by this many 64ths
+    Scale(i32),
+}
+
+// squares are row * 8 + col like Board::squares, a white pawn moves towards row 0
+fn row(square: usize) -> i32 {
+    return (square / 8) as i32;
+}
+
+fn col(square: usize) -> i32 {
+    return (square % 8) as i32;
+}
+
+fn distance(a: usize, b: usize) -> i32 {
+    return (row(a) - row(b)).abs().max((col(a) - col(b)).abs());
+}
+
+fn is_light(square: usize) -> bool {
+    // a8 is a light square
+    return (row(square) + col(square)) % 2 == 0;
+}
+
+fn king_steps(square: usize) -> impl Iterator<Item = usize> {
+    let (r, c) = (row(square), col(square));
+    return (-1..=1)
+        .flat_map(move |dr| (-1..=1).map(move |dc| (r + dr, c + dc)))
+        .filter(move |(nr, nc)| (*nr, *nc) != (r, c) && (0..8).contains(nr) && (0..8).contains(nc))
+        .map(|(nr, nc)| (nr * 8 + nc) as usize);
+}
+
+fn pawn_attacks(pawn: usize, square: usize) -> bool {
+    // for a white pawn
+    return row(square) == row(pawn) - 1 && (col(square) - col(pawn)).abs() == 1;
+}
+
+fn kpk_index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn_row = (row(pawn) - 1) as usize;
+    let pawn_file = col(pawn) as usize;
+    let side = if white_to_move { 0 } else { 1 };
+    return (((pawn_row * 4 + pawn_file) * 2 + side) * 64 + black_king) * 64 + white_king;
+}
+
+fn kpk_initial(white_to_move: bool, wk: usize, bk: usize, pawn: usize) -> u8 {
+    if wk == bk || wk == pawn || bk == pawn || distance(wk, bk) <= 1 {
+        return INVALID;
+    }
+    if white_to_move && pawn_attacks(pawn, bk) {
+        return INVALID;
+    }
+    let queening = pawn.wrapping_sub(8);
+    if white_to_move
+        && row(pawn) == 1
+        && wk != queening
+        && bk != queening
+        && (distance(bk, queening) > 1 || distance(wk, queening) == 1)
+    {
+        // the pawn promotes and the queen can't be taken
+        return WIN;
+    }
+    if !white_to_move {
+        let mut escapes = king_steps(bk)
+            .filter(|s| distance(*s, wk) > 1 && !pawn_attacks(pawn, *s) && *s != pawn);
+        if escapes.next().is_none() {
+            let takes = distance(bk, pawn) == 1 && distance(wk, pawn) > 1;
+            if !takes {
+                return if pawn_attacks(pawn, bk) { WIN } else { DRAW };
+            }
+        }
+        if distance(bk, pawn) == 1 && distance(wk, pawn) > 1 {
+            return DRAW;
+        }
+    }
+    return UNKNOWN;
+}
+
+fn kpk_classify(results: &[u8], white_to_move: bool, wk: usize, bk: usize, pawn: usize) -> u8 {
+    // white needs one winning move, black one drawing move
+    let mut seen_unknown = false;
+    let mut seen = |result: u8| -> Option<u8> {
+        match (white_to_move, result) {
+            (true, WIN) => Some(WIN),
+            (false, DRAW) => Some(DRAW),
+            (_, UNKNOWN) => {
+                seen_unknown = true;
+                None
+            }
+            _ => None,
+        }
+    };
+    if white_to_move {
+        for s in king_steps(wk) {
+            if distance(s, bk) > 1 && s != pawn {
+                if let Some(r) = seen(results[kpk_index(false, s, bk, pawn)]) {
+                    return r;
+                }
+            }
+        }
+        let push = pawn - 8;
+        if row(pawn) > 1 && push != wk && push != bk {
+            if let Some(r) = seen(results[kpk_index(false, wk, bk, push)]) {
+                return r;
+            }
+            let double = push - 8;
+            if row(pawn) == 6 && double != wk && double != bk {
+                if let Some(r) = seen(results[kpk_index(false, wk, bk, double)]) {
+                    return r;
+                }
+            }
+        }
+    } else {
+        for s in king_steps(bk) {
+            if distance(s, wk) > 1 && !pawn_attacks(pawn, s) && s != pawn {
+                if let Some(r) = seen(results[kpk_index(true, wk, s, pawn)]) {
+                    return r;
+                }
+            }
+        }
+    }
+    if seen_unknown {
+        return UNKNOWN;
+    }
+    return if white_to_move { DRAW } else { WIN };
+}
+
+fn build_kpk() -> Vec<u64> {
+    let mut results = vec![INVALID; KPK_SIZE];
+    let mut positions = Vec::new();
+    for pawn_row in 1..7 {
+        for pawn_file in 0..4 {
+            let pawn = pawn_row * 8 + pawn_file;
+            for white_to_move in [true, false] {
+                for bk in 0..64 {
+                    for wk in 0..64 {
+                        let index = kpk_index(white_to_move, wk, bk, pawn);
+                        results[index] = kpk_initial(white_to_move, wk, bk, pawn);
+                        if results[index] == UNKNOWN {
+                            positions.push((white_to_move, wk, bk, pawn));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // keep going over the open positions until none of them changes
+    loop {
+        let mut changed = false;
+        positions.retain(|(white_to_move, wk, bk, pawn)| {
+            let result = kpk_classify(&results, *white_to_move, *wk, *bk, *pawn);
+            if result == UNKNOWN {
+                return true;
+            }
+            results[kpk_index(*white_to_move, *wk, *bk, *pawn)] = result;
+            changed = true;
+            false
+        });
+        if !changed {
+            break;
+        }
+    }
+    let mut wins = vec![0u64; KPK_SIZE / 64];
+    for (index, result) in results.iter().enumerate() {
+        if *result == WIN {
+            wins[index / 64] |= 1 << (index % 64);
+        }
+    }
+    return wins;
+}
+
+pub fn kpk_wins(strong_king: usize, pawn: usize, weak_king: usize, strong_to_move: bool) -> bool {
+    // squares as row * 8 + col with the pawn going up the board, the tables fill on first use
+    let wins = KPK.get_or_init(build_kpk);
+    let mirror = |s: usize| {
+        if col(pawn) > 3 {
+            s - col(s) as usize + 7 - col(s) as usize
+        } else {
+            s
+        }
+    };
+    let index = kpk_index(
+        strong_to_move,
+        mirror(strong_king),
+        mirror(weak_king),
+        mirror(pawn),
+    );
+    return wins[index / 64] & (1 << (index % 64)) != 0;
+}
+
+struct Side {
+    king: usize,
+    pieces: Vec<(PieceType, usize)>,
+}
+
+impl Side {
+    fn count(&self, ptype: PieceType) -> usize {
+        return self.pieces.iter().filter(|(t, _)| *t == ptype).count();
+    }
+
+    fn non_pawn(&self) -> usize {
+        return self.pieces.len() - self.count(PieceType::Pawn);
+    }
+
+    fn material(&self) -> i32 {
+        return self.pieces.iter().map(|(t, _)| piece_value(*t)).sum();
+    }
+
+    fn find(&self, ptype: PieceType) -> Option<usize> {
+        return self
+            .pieces
+            .iter()
+            .find(|(t, _)| *t == ptype)
+            .map(|(_, s)| *s);
+    }
+}
+
+fn push_to_edge(square: usize) -> i32 {
+    // 0 in the centre up to 60 in a corner
+    let rank_distance = (3 - row(square)).max(row(square) - 4);
+    let file_distance = (3 - col(square)).max(col(square) - 4);
+    return (rank_distance + file_distance) * 10;
+}
+
+fn push_close(a: usize, b: usize) -> i32 {
+    return (7 - distance(a, b)) * 10;
+}
+
+fn can_mate(side: &Side) -> bool {
+    let bishops = side.count(PieceType::Bishop);
+    let knights = side.count(PieceType::Knight);
+    return side.count(PieceType::Queen) > 0
+        || side.count(PieceType::Rook) > 0
+        || (bishops > 0 && knights > 0)
+        || (bishops > 1
+            && side
+                .pieces
+                .iter()
+                .any(|(t, s)| *t == PieceType::Bishop && is_light(*s))
+            && side
+                .pieces
+                .iter()
+                .any(|(t, s)| *t == PieceType::Bishop && !is_light(*s)));
+}
+
+fn lone_king(strong: &Side, weak: &Side) -> Option<i32> {
+    // the weak side has a bare king, mating it is a matter of technique
+    if !weak.pieces.is_empty() || !can_mate(strong) {
+        return None;
+    }
+    let mut score = KNOWN_WIN + strong.material() + push_close(strong.king, weak.king);
+    let bishop = strong
+        .find(PieceType::Bishop)
+        .filter(|_| strong.pieces.len() == 2 && strong.count(PieceType::Knight) == 1);
+    if let Some(bishop) = bishop {
+        // KBNK: the mate only works in a corner the bishop can reach
+        let corners: [usize; 2] = if is_light(bishop) { [0, 63] } else { [7, 56] };
+        let corner = corners
+            .iter()
+            .map(|c| distance(weak.king, *c))
+            .min()
+            .unwrap();
+        score += (7 - corner) * 20;
+    } else {
+        score += push_to_edge(weak.king);
+    }
+    return Some(score);
+}
+
+fn king_and_pawn(
+    strong: &Side,
+    weak: &Side,
+    white_strong: bool,
+    white_to_move: bool,
+) -> Option<i32> {
+    if strong.pieces.len() != 1 || !weak.pieces.is_empty() {
+        return None;
+    }
+    let pawn = strong.find(PieceType::Pawn)?;
+    // the bitbase has the pawn going up the board, so black's pieces are mirrored
+    let flip = |s: usize| {
+        if white_strong {
+            s
+        } else {
+            (7 - row(s) as usize) * 8 + col(s) as usize
+        }
+    };
+    let strong_to_move = white_to_move == white_strong;
+    if !kpk_wins(
+        flip(strong.king),
+        flip(pawn),
+        flip(weak.king),
+        strong_to_move,
+    ) {
+        return Some(0);
+    }
+    let advanced = 7 - row(flip(pawn));
+    return Some(KNOWN_WIN + piece_value(PieceType::Pawn) + advanced * 10);
+}
+
+fn wrong_bishop(strong: &Side, weak: &Side, white_strong: bool) -> bool {
+    // bishop and rook pawns against a king in the corner the bishop doesn't cover
+    if !weak.pieces.is_empty() || strong.non_pawn() != 1 {
+        return false;
+    }
+    let bishop = match strong.find(PieceType::Bishop) {
+        Some(s) => s,
+        None => return false,
+    };
+    let pawns: Vec<usize> = strong
+        .pieces
+        .iter()
+        .filter(|(t, _)| *t == PieceType::Pawn)
+        .map(|(_, s)| *s)
+        .collect();
+    let file = match pawns.first() {
+        Some(p) if col(*p) == 0 || col(*p) == 7 => col(*p),
+        _ => return false,
+    };
+    if pawns.iter().any(|p| col(*p) != file) {
+        return false;
+    }
+    let queening = if white_strong { file } else { 56 + file } as usize;
+    return is_light(queening) != is_light(bishop) && distance(weak.king, queening) <= 1;
+}
+
+fn bishops_only(side: &Side) -> Option<usize> {
+    // the square of the side's one piece when that is a bishop, pawns aside
+    if side.non_pawn() != 1 {
+        return None;
+    }
+    return side.find(PieceType::Bishop);
+}
+
+pub fn recognize(board: &Board) -> Option<Recognized> {
+    // known endings that the usual evaluation gets wrong, under the normal rules
+    let mut white = Side {
+        king: 64,
+        pieces: Vec::new(),
+    };
+    let mut black = Side {
+        king: 64,
+        pieces: Vec::new(),
+    };
+    let mut count = 0;
+    for (r, squares) in board.squares.iter().enumerate() {
+        for (c, piece) in squares.iter().enumerate() {
+            if *piece == Piece::Blank {
+                continue;
+            }
+            count += 1;
+            let side = match piece.as_color() {
+                PieceColor::White => &mut white,
+                _ => &mut black,
+            };
+            match piece.as_type() {
+                PieceType::King => side.king = r * 8 + c,
+                t => side.pieces.push((t, r * 8 + c)),
+            }
+            // opposite bishops can come with any number of pawns, nothing else does
+            if count > 6 && white.non_pawn() + black.non_pawn() > 2 {
+                return None;
+            }
+        }
+    }
+    if white.king == 64 || black.king == 64 {
+        return None;
+    }
+    let white_to_move = board.to_move == super::board::Turn::White;
+    for (strong, weak, white_strong) in [(&white, &black, true), (&black, &white, false)] {
+        let sign = if white_strong { 1 } else { -1 };
+        if let Some(score) =
+            lone_king(strong, weak).or(king_and_pawn(strong, weak, white_strong, white_to_move))
+        {
+            return Some(Recognized::Score(sign * score));
+        }
+        if wrong_bishop(strong, weak, white_strong) {
+            return Some(Recognized::Score(0));
+        }
+        if strong.pieces.len() == 1
+            && strong.count(PieceType::Rook) == 1
+            && weak.pieces.len() == 1
+            && weak.count(PieceType::Bishop) == 1
+        {
+            return Some(Recognized::Scale(SCALE_ROOK_VS_BISHOP));
+        }
+    }
+    if let (Some(a), Some(b)) = (bishops_only(&white), bishops_only(&black)) {
+        if is_light(a) != is_light(b) {
+            let pawns_up =
+                (white.count(PieceType::Pawn) as i32 - black.count(PieceType::Pawn) as i32).abs();
+            if pawns_up <= 1 {
+                return Some(Recognized::Scale(SCALE_OPPOSITE_BISHOPS));
+            }
+            return Some(Recognized::Scale(SCALE_OPPOSITE_BISHOPS_PAWNS_UP));
+        }
+    }
+    return None;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn recognized(fen: &str) -> Option<Recognized> {
+        return recognize(&Board::parse_fen(fen).unwrap());
+    }
+
+    fn square(name: &str) -> usize {
+        let (r, c) = Board::parse_square(name).unwrap();
+        return r * 8 + c;
+    }
+
+    #[test]
+    fn test_kpk() {
+        let kpk = |k: &str, p: &str, weak: &str, to_move: bool| {
+            kpk_wins(square(k), square(p), square(weak), to_move)
+        };
+        // the king in front of its pawn on the sixth rank always wins
+        assert!(kpk("e6", "e5", "e8", true));
+        assert!(kpk("e6", "e5", "e8", false));
+        // opposition: whoever has to move loses it
+        assert!(!kpk("e5", "e4", "e7", true));
+        assert!(kpk("e5", "e4", "e7", false));
+        // the rook pawn and the king in the corner
+        assert!(!kpk("b1", "a2", "a8", true));
+        assert!(kpk("c7", "h5", "a1", true));
+        // the mirrored files give the same answers
+        assert!(kpk("d6", "d5", "d8", false) == kpk("e6", "e5", "e8", false));
+        assert_eq!(
+            recognized("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"),
+            Some(Recognized::Score(KNOWN_WIN + 140))
+        );
+        assert_eq!(
+            recognized("8/8/8/8/4p3/4k3/8/4K3 w - - 0 1"),
+            Some(Recognized::Score(-KNOWN_WIN - 140))
+        );
+        assert_eq!(
+            recognized("k7/8/8/8/8/8/P7/1K6 w - - 0 1"),
+            Some(Recognized::Score(0))
+        );
+    }
+
+    #[test]
+    fn test_recognizers() {
+        // KBNK is driven into the bishop's corner, a dark square bishop wants a1 or h8
+        let right = recognized("8/8/8/8/8/2K5/8/k1BN4 w - - 0 1").unwrap();
+        let wrong = recognized("8/8/8/8/8/5K2/8/2BN3k w - - 0 1").unwrap();
+        match (right, wrong) {
+            (Recognized::Score(a), Recognized::Score(b)) => assert!(a > b && b > KNOWN_WIN),
+            _ => panic!("KBNK should have a score"),
+        }
+        // the light squared bishop can't cover h8
+        assert_eq!(
+            recognized("7k/8/6KP/8/8/8/8/5B2 w - - 0 1"),
+            Some(Recognized::Score(0))
+        );
+        assert_eq!(recognized("7k/8/6KP/8/8/8/8/6B1 w - - 0 1"), None);
+        assert_eq!(
+            recognized("8/1b4k1/8/8/8/8/2K5/7R w - - 0 1"),
+            Some(Recognized::Scale(SCALE_ROOK_VS_BISHOP))
+        );
+        assert_eq!(
+            recognized("8/5pk1/6p1/4b3/8/1P3B2/P4PK1/8 w - - 0 1"),
+            Some(Recognized::Scale(SCALE_OPPOSITE_BISHOPS))
+        );
+        assert_eq!(recognized("8/5pk1/6p1/4b3/8/1P2B3/P4PK1/8 w - - 0 1"), None);
+        assert_eq!(
+            recognized("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"),
+            Some(Recognized::Score(KNOWN_WIN + 900 + 30))
+        );
+        assert_eq!(recognize(&Board::default()), None);
+    }
+}
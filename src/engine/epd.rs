@@ -0,0 +1,281 @@
+use super::board::{Board, Move};
+
+#[derive(Clone)]
+pub struct Epd {
+    pub board: Board,
+    // opcodes with their operands, in the order they were read
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    // opcode operand ...; where an operand in quotes may hold spaces and semicolons
+    let mut operations = Vec::new();
+    let mut words: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut word = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => return Err(String::from("unterminated string")),
+                        },
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(String::from("unterminated string")),
+                    }
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if words.is_empty() {
+                    continue;
+                }
+                let opcode = words.remove(0);
+                operations.push((opcode, std::mem::take(&mut words)));
+            }
+            c if c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        // the last operation is often missing its semicolon
+        let opcode = words.remove(0);
+        operations.push((opcode, words));
+    }
+    return Ok(operations);
+}
+
+fn format_operand(operand: &str) -> String {
+    if operand.is_empty() || operand.contains([' ', ';', '"']) {
+        return format!("\"{}\"", operand.replace('\\', "\\\\").replace('"', "\\\""));
+    }
+    return operand.to_string();
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Epd, String> {
+        // four FEN fields and then the operations, counters come from hmvc and fmvn
+        let line = line.trim();
+        let mut fields: Vec<&str> = Vec::new();
+        let mut rest = line;
+        while fields.len() < 4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("'{}' has fewer than four fields", line));
+            }
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        // some files carry the FEN move counters as well, those are skipped over
+        let mut counters = Vec::new();
+        while counters.len() < 2 {
+            let trimmed = rest.trim_start();
+            let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+            if end == 0 || trimmed[..end].parse::<u32>().is_err() {
+                break;
+            }
+            counters.push(&trimmed[..end]);
+            rest = &trimmed[end..];
+        }
+        let operations = parse_operations(rest)?;
+        let mut epd = Epd {
+            board: Board::parse_fen(&fields.join(" "))?,
+            operations,
+        };
+        if let Some(n) = epd.operand("hmvc").or(counters.first().copied()) {
+            let n = n.parse().map_err(|_| format!("invalid hmvc '{}'", n))?;
+            epd.board.half_move = n;
+        }
+        if let Some(n) = epd.operand("fmvn").or(counters.get(1).copied()) {
+            let n = n.parse().map_err(|_| format!("invalid fmvn '{}'", n))?;
+            epd.board.move_number = n;
+        }
+        return Ok(epd);
+    }
+
+    pub fn new(board: Board) -> Epd {
+        Epd {
+            board,
+            operations: Vec::new(),
+        }
+    }
+
+    pub fn to_epd(&self) -> String {
+        let fen = self.board.to_fen();
+        let mut text = fen.split(' ').take(4).collect::<Vec<&str>>().join(" ");
+        for (opcode, operands) in &self.operations {
+            text.push(' ');
+            text.push_str(opcode);
+            for operand in operands {
+                text.push(' ');
+                text.push_str(&format_operand(operand));
+            }
+            text.push(';');
+        }
+        return text;
+    }
+
+    pub fn get(&self, opcode: &str) -> Option<&[String]> {
+        return self
+            .operations
+            .iter()
+            .find(|(o, _)| o == opcode)
+            .map(|(_, operands)| operands.as_slice());
+    }
+
+    pub fn operand(&self, opcode: &str) -> Option<&str> {
+        // the first operand, for opcodes that only take one
+        return self.get(opcode)?.first().map(|s| s.as_str());
+    }
+
+    pub fn set(&mut self, opcode: &str, operands: Vec<String>) {
+        match self.operations.iter_mut().find(|(o, _)| o == opcode) {
+            Some(operation) => operation.1 = operands,
+            None => self.operations.push((opcode.to_string(), operands)),
+        }
+    }
+
+    pub fn remove(&mut self, opcode: &str) {
+        self.operations.retain(|(o, _)| o != opcode);
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        return self.operand("id");
+    }
+
+    fn moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        return match self.get(opcode) {
+            Some(operands) => operands.iter().map(|m| self.board.parse_san(m)).collect(),
+            None => Ok(Vec::new()),
+        };
+    }
+
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        return self.moves("bm");
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        return self.moves("am");
+    }
+
+    pub fn centipawns(&self) -> Option<i32> {
+        return self.operand("ce")?.parse().ok();
+    }
+
+    pub fn direct_mate(&self) -> Option<u32> {
+        return self.operand("dm")?.parse().ok();
+    }
+
+    pub fn depth(&self) -> Option<u32> {
+        return self.operand("acd")?.parse().ok();
+    }
+
+    pub fn move_points(&self) -> Vec<(Move, u32)> {
+        // STS scoring, either c0 "Nf3=10, Nd2=5" or c8 "10 5" with c9 "g1f3 b1d2"
+        let mut points = Vec::new();
+        if let Some(c0) = self.operand("c0") {
+            for part in c0.split(',') {
+                if let Some((san, value)) = part.trim().split_once('=') {
+                    if let (Ok(m), Ok(value)) = (self.board.parse_san(san), value.parse()) {
+                        points.push((m, value));
+                    }
+                }
+            }
+        }
+        if points.is_empty() {
+            if let (Some(values), Some(moves)) = (self.operand("c8"), self.operand("c9")) {
+                for (value, lan) in values.split_whitespace().zip(moves.split_whitespace()) {
+                    if let (Ok(value), Ok(m)) = (value.parse(), self.board.parse_move(lan)) {
+                        points.push((m, value));
+                    }
+                }
+            }
+        }
+        return points;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let epd = Epd::parse(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.best_moves().unwrap()[0].to_string(), "g3g6");
+        assert!(epd.avoid_moves().unwrap().is_empty());
+        assert_eq!(
+            epd.board.to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+
+        let epd = Epd::parse(
+            "8/8/8/8/8/8/8/K1k5 b - - 12 40 ce -25; acd 18; dm 3; am Kb1 Kc2; c0 \"a; b\"; hmvc 7",
+        )
+        .unwrap();
+        assert_eq!(epd.centipawns(), Some(-25));
+        assert_eq!(epd.depth(), Some(18));
+        assert_eq!(epd.direct_mate(), Some(3));
+        assert_eq!(epd.get("am").unwrap(), ["Kb1", "Kc2"]);
+        assert_eq!(epd.operand("c0"), Some("a; b"));
+        assert_eq!(epd.board.half_move, 7);
+        assert_eq!(epd.board.move_number, 40);
+
+        assert!(Epd::parse("8/8/8 w").is_err());
+        assert!(Epd::parse("8/8/8/8/8/8/8/K1k5 w - - c0 \"open").is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let mut epd = Epd::new(Board::default());
+        epd.set("bm", vec![String::from("e4"), String::from("d4")]);
+        epd.set("id", vec![String::from("start \"position\"")]);
+        epd.set("noop", Vec::new());
+        let text = epd.to_epd();
+        assert_eq!(
+            text,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - bm e4 d4; id \"start \\\"position\\\"\"; noop;"
+        );
+        let again = Epd::parse(&text).unwrap();
+        assert_eq!(again.id(), Some("start \"position\""));
+        assert_eq!(again.to_epd(), text);
+        epd.remove("noop");
+        assert!(epd.get("noop").is_none());
+    }
+
+    #[test]
+    fn test_move_points() {
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8+; c0 \"Ra8+=10, O-O-O=4, Kd2=x\";")
+            .unwrap();
+        let points: Vec<(String, u32)> = epd
+            .move_points()
+            .iter()
+            .map(|(m, p)| (m.to_string(), *p))
+            .collect();
+        assert_eq!(
+            points,
+            vec![(String::from("a1a8"), 10), (String::from("e1c1"), 4)]
+        );
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - c8 \"10 3\"; c9 \"a1a8 e1d2\";").unwrap();
+        assert_eq!(epd.move_points().len(), 2);
+    }
+}
@@ -0,0 +1,224 @@
+use super::board::{Board, Turn};
+use super::endgame::{self, Recognized, SCALE_NORMAL};
+use super::piece::{Piece, PieceColor, PieceType};
+
+// Piece-square tables from white's point of view, laid out like Board::squares
+// (first row is the 8th rank). Black uses the same tables mirrored vertically.
+#[rustfmt::skip]
+const PAWN_TABLE: [[i32; 8]; 8] = [
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+    [ 50, 50, 50, 50, 50, 50, 50, 50],
+    [ 10, 10, 20, 30, 30, 20, 10, 10],
+    [  5,  5, 10, 25, 25, 10,  5,  5],
+    [  0,  0,  0, 20, 20,  0,  0,  0],
+    [  5, -5,-10,  0,  0,-10, -5,  5],
+    [  5, 10, 10,-20,-20, 10, 10,  5],
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [[i32; 8]; 8] = [
+    [-50,-40,-30,-30,-30,-30,-40,-50],
+    [-40,-20,  0,  0,  0,  0,-20,-40],
+    [-30,  0, 10, 15, 15, 10,  0,-30],
+    [-30,  5, 15, 20, 20, 15,  5,-30],
+    [-30,  0, 15, 20, 20, 15,  0,-30],
+    [-30,  5, 10, 15, 15, 10,  5,-30],
+    [-40,-20,  0,  5,  5,  0,-20,-40],
+    [-50,-40,-30,-30,-30,-30,-40,-50],
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [[i32; 8]; 8] = [
+    [-20,-10,-10,-10,-10,-10,-10,-20],
+    [-10,  0,  0,  0,  0,  0,  0,-10],
+    [-10,  0,  5, 10, 10,  5,  0,-10],
+    [-10,  5,  5, 10, 10,  5,  5,-10],
+    [-10,  0, 10, 10, 10, 10,  0,-10],
+    [-10, 10, 10, 10, 10, 10, 10,-10],
+    [-10,  5,  0,  0,  0,  0,  5,-10],
+    [-20,-10,-10,-10,-10,-10,-10,-20],
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [[i32; 8]; 8] = [
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+    [  5, 10, 10, 10, 10, 10, 10,  5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [  0,  0,  0,  5,  5,  0,  0,  0],
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [[i32; 8]; 8] = [
+    [-20,-10,-10, -5, -5,-10,-10,-20],
+    [-10,  0,  0,  0,  0,  0,  0,-10],
+    [-10,  0,  5,  5,  5,  5,  0,-10],
+    [ -5,  0,  5,  5,  5,  5,  0, -5],
+    [  0,  0,  5,  5,  5,  5,  0, -5],
+    [-10,  5,  5,  5,  5,  5,  0,-10],
+    [-10,  0,  5,  0,  0,  0,  0,-10],
+    [-20,-10,-10, -5, -5,-10,-10,-20],
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [[i32; 8]; 8] = [
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-20,-30,-30,-40,-40,-30,-30,-20],
+    [-10,-20,-20,-20,-20,-20,-20,-10],
+    [ 20, 20,  0,  0,  0,  0, 20, 20],
+    [ 20, 30, 10,  0,  0, 10, 30, 20],
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [[i32; 8]; 8] = [
+    [-50,-40,-30,-20,-20,-30,-40,-50],
+    [-30,-20,-10,  0,  0,-10,-20,-30],
+    [-30,-10, 20, 30, 30, 20,-10,-30],
+    [-30,-10, 30, 40, 40, 30,-10,-30],
+    [-30,-10, 30, 40, 40, 30,-10,-30],
+    [-30,-10, 20, 30, 30, 20,-10,-30],
+    [-30,-30,  0,  0,  0,  0,-30,-30],
+    [-50,-30,-30,-30,-30,-30,-30,-50],
+];
+
+// Non-pawn material (both sides) at which the king table fully switches to the endgame one
+const ENDGAME_MATERIAL: i32 = 1300;
+const OPENING_MATERIAL: i32 = 6200;
+
+pub fn piece_value(ptype: PieceType) -> i32 {
+    match ptype {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+        PieceType::Blank => 0,
+    }
+}
+
+fn table_value(piece: Piece, square: (usize, usize), endgame_weight: i32) -> i32 {
+    let row = match piece.as_color() {
+        PieceColor::Black => 7 - square.0,
+        _ => square.0,
+    };
+    let col = square.1;
+    match piece.as_type() {
+        PieceType::Pawn => PAWN_TABLE[row][col],
+        PieceType::Knight => KNIGHT_TABLE[row][col],
+        PieceType::Bishop => BISHOP_TABLE[row][col],
+        PieceType::Rook => ROOK_TABLE[row][col],
+        PieceType::Queen => QUEEN_TABLE[row][col],
+        PieceType::King => {
+            (KING_TABLE[row][col] * (256 - endgame_weight)
+                + KING_ENDGAME_TABLE[row][col] * endgame_weight)
+                / 256
+        }
+        PieceType::Blank => 0,
+    }
+}
+
+pub fn non_pawn_material(board: &Board) -> i32 {
+    let mut total = 0;
+    for row in &board.squares {
+        for piece in row {
+            if piece.as_type() != PieceType::Pawn {
+                total += piece_value(piece.as_type());
+            }
+        }
+    }
+    return total;
+}
+
+pub fn evaluate(board: &Board) -> i32 {
+    // Static evaluation in centipawns, from the point of view of the side to move
+    return evaluate_with(board, true);
+}
+
+pub fn evaluate_with(board: &Board, known_endings: bool) -> i32 {
+    // known endings are only known under the normal rules, variants where the same
+    // material plays out differently leave them out
+    let recognized = if known_endings {
+        endgame::recognize(board)
+    } else {
+        None
+    };
+    if let Some(Recognized::Score(score)) = recognized {
+        return if board.to_move == Turn::Black {
+            -score
+        } else {
+            score
+        };
+    }
+    let material = non_pawn_material(board);
+    let endgame_weight = if material <= ENDGAME_MATERIAL {
+        256
+    } else if material >= OPENING_MATERIAL {
+        0
+    } else {
+        (OPENING_MATERIAL - material) * 256 / (OPENING_MATERIAL - ENDGAME_MATERIAL)
+    };
+    let mut score = 0;
+    for (i, row) in board.squares.iter().enumerate() {
+        for (j, piece) in row.iter().enumerate() {
+            let value = piece_value(piece.as_type()) + table_value(*piece, (i, j), endgame_weight);
+            match piece.as_color() {
+                PieceColor::White => score += value,
+                PieceColor::Black => score -= value,
+                PieceColor::None => {}
+            }
+        }
+    }
+    if let Some(Recognized::Scale(scale)) = recognized {
+        score = score * scale / SCALE_NORMAL;
+    }
+    if board.to_move == Turn::Black {
+        return -score;
+    }
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_start_position() {
+        assert_eq!(evaluate(&Board::default()), 0);
+    }
+
+    #[test]
+    fn test_side_to_move() {
+        let white = Board::from_fen(String::from("4k3/8/8/8/8/8/8/3QK3 w - - 0 1"));
+        let black = Board::from_fen(String::from("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"));
+        assert!(evaluate(&white) > 800);
+        assert_eq!(evaluate(&white), -evaluate(&black));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        // mirroring the board and swapping colors gives the same score
+        let board = Board::from_fen(String::from(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        ));
+        let mirrored = Board::from_fen(String::from(
+            "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3",
+        ));
+        assert_eq!(evaluate(&board), evaluate(&mirrored));
+    }
+
+    #[test]
+    fn test_centralisation() {
+        let center = Board::from_fen(String::from("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1"));
+        let corner = Board::from_fen(String::from("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"));
+        assert!(evaluate(&center) > evaluate(&corner));
+    }
+}
@@ -0,0 +1,968 @@
+use super::board::Turn;
+use super::game::GameResult;
+
+use std::sync::Arc;
+
+// the board is at most 16 files by 16 ranks, squares are numbered rank * width + file
+pub const MAX_SIZE: usize = 16;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Mode {
+    Any,
+    Move,
+    Capture,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+struct Step {
+    // file and rank offset, the rank counting towards the opponent
+    dx: i32,
+    dy: i32,
+    // how often the step may be repeated, 0 for as far as the board goes
+    range: usize,
+    mode: Mode,
+    // hops over the first piece in the way and lands just behind it
+    hop: bool,
+    // only from the side's second rank, as with a pawn's double step
+    initial: bool,
+    // a leaper that can be blocked on the squares it passes
+    lame: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct PieceDef {
+    pub name: String,
+    // uppercase, white pieces use it as is and black ones in lowercase
+    pub letter: char,
+    pub betza: String,
+    // a royal piece may not be left attacked, and losing all moves with it attacked is mate
+    pub royal: bool,
+    // letters of what the piece may become on the last rank
+    pub promotes_to: Vec<char>,
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub pieces: Vec<PieceDef>,
+    pub start: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FairyMove {
+    pub from: usize,
+    pub to: usize,
+    // index into Rules::pieces
+    pub promotion: Option<usize>,
+    // the royal piece's side of castling; the corner piece moves along with it
+    pub castling: bool,
+}
+
+#[derive(Clone)]
+pub struct FairyBoard {
+    pub rules: Arc<Rules>,
+    // index into Rules::pieces and the owner
+    pub squares: Vec<Option<(usize, Turn)>>,
+    pub to_move: Turn,
+    // white's kingside and queenside, then black's, with the corner pieces as the rooks
+    pub castling: [bool; 4],
+    // the square a pawn-like piece passed with its double step
+    pub en_passant: Option<usize>,
+    pub half_move: u32,
+    pub move_number: u32,
+}
+
+const ATOMS: [(char, i32, i32); 9] = [
+    ('W', 1, 0),
+    ('F', 1, 1),
+    ('D', 2, 0),
+    ('N', 2, 1),
+    ('A', 2, 2),
+    ('H', 3, 0),
+    ('C', 3, 1),
+    ('Z', 3, 2),
+    ('G', 3, 3),
+];
+
+fn atom_offsets(atom: char) -> Option<Vec<(char, usize)>> {
+    // shorthands stand for one or more basic atoms with a range
+    let parts = match atom {
+        'K' => vec![('W', 1), ('F', 1)],
+        'Q' => vec![('W', 0), ('F', 0)],
+        'R' => vec![('W', 0)],
+        'B' => vec![('F', 0)],
+        a if ATOMS.iter().any(|(c, _, _)| *c == a) => vec![(a, 1)],
+        _ => return None,
+    };
+    return Some(parts);
+}
+
+fn directions(dx: i32, dy: i32) -> Vec<(i32, i32)> {
+    // every reflection of the offset, without repeats
+    let mut all = Vec::new();
+    for (x, y) in [(dx, dy), (dy, dx)] {
+        for (sx, sy) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+            let d = (x * sx, y * sy);
+            if !all.contains(&d) {
+                all.push(d);
+            }
+        }
+    }
+    return all;
+}
+
+fn direction_allowed(d: (i32, i32), modifiers: &[char]) -> bool {
+    // f, b, l and r pick out directions, fl and the like their intersection, v and s
+    // the vertical and sideways ones; with none every direction goes
+    let groups: Vec<Vec<char>> = {
+        let mut groups: Vec<Vec<char>> = Vec::new();
+        for c in modifiers.iter().filter(|c| "fblrvs".contains(**c)) {
+            match groups.last_mut() {
+                Some(g) if "fb".contains(g[0]) && "lr".contains(*c) && g.len() == 1 => g.push(*c),
+                _ => groups.push(vec![*c]),
+            }
+        }
+        groups
+    };
+    if groups.is_empty() {
+        return true;
+    }
+    return groups.iter().any(|g| {
+        g.iter().all(|c| match c {
+            'f' => d.1 > 0,
+            'b' => d.1 < 0,
+            'l' => d.0 < 0,
+            'r' => d.0 > 0,
+            'v' => d.0 == 0 || (d.1.abs() > d.0.abs()),
+            's' => d.1 == 0 || (d.0.abs() > d.1.abs()),
+            _ => true,
+        })
+    });
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        return a.abs();
+    }
+    return gcd(b, a % b);
+}
+
+fn parse_betza(betza: &str) -> Result<Vec<Step>, String> {
+    // atoms W F D N A H C Z G and the shorthands K Q R B; a doubled atom or a 0 after
+    // it makes a rider and another number limits its range; lowercase prefixes are
+    // m (move only), c (capture only), g (grasshopper), i (initial), n (lame) and the
+    // directions f b l r v s
+    let chars: Vec<char> = betza.chars().collect();
+    let mut steps = Vec::new();
+    let mut modifiers = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        if c.is_ascii_lowercase() {
+            if !"mcginfblrvs".contains(c) {
+                return Err(format!("unknown Betza modifier '{}' in {}", c, betza));
+            }
+            modifiers.push(c);
+            continue;
+        }
+        let parts = match atom_offsets(c) {
+            Some(p) => p,
+            None => return Err(format!("unknown Betza atom '{}' in {}", c, betza)),
+        };
+        let mut range = None;
+        if i < chars.len() && chars[i] == c {
+            range = Some(0);
+            i += 1;
+        } else if i < chars.len() && chars[i].is_ascii_digit() {
+            let mut n = 0;
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                n = n * 10 + chars[i].to_digit(10).unwrap_or(0) as usize;
+                i += 1;
+            }
+            range = Some(n);
+        }
+        let mode = match (modifiers.contains(&'m'), modifiers.contains(&'c')) {
+            (true, false) => Mode::Move,
+            (false, true) => Mode::Capture,
+            _ => Mode::Any,
+        };
+        for (atom, default_range) in parts {
+            let (_, dx, dy) = ATOMS
+                .iter()
+                .find(|(a, _, _)| *a == atom)
+                .copied()
+                .unwrap_or(('W', 1, 0));
+            let lame = modifiers.contains(&'n');
+            if lame && gcd(dx, dy) == 1 {
+                return Err(format!("n only works on straight leapers in {}", betza));
+            }
+            for d in directions(dx, dy) {
+                if !direction_allowed(d, &modifiers) {
+                    continue;
+                }
+                steps.push(Step {
+                    dx: d.0,
+                    dy: d.1,
+                    range: range.unwrap_or(default_range),
+                    mode,
+                    hop: modifiers.contains(&'g'),
+                    initial: modifiers.contains(&'i'),
+                    lame,
+                });
+            }
+        }
+        modifiers.clear();
+    }
+    if !modifiers.is_empty() {
+        return Err(format!("Betza string {} ends in modifiers", betza));
+    }
+    return Ok(steps);
+}
+
+impl PieceDef {
+    pub fn new(name: &str, letter: char, betza: &str) -> Result<PieceDef, String> {
+        if !letter.is_ascii_alphabetic() {
+            return Err(format!("'{}' can't be used as a piece letter", letter));
+        }
+        return Ok(PieceDef {
+            name: name.to_string(),
+            letter: letter.to_ascii_uppercase(),
+            betza: betza.to_string(),
+            royal: false,
+            promotes_to: Vec::new(),
+            steps: parse_betza(betza)?,
+        });
+    }
+
+    pub fn royal(mut self) -> PieceDef {
+        self.royal = true;
+        return self;
+    }
+
+    pub fn promotes_to(mut self, letters: &str) -> PieceDef {
+        self.promotes_to = letters.chars().map(|c| c.to_ascii_uppercase()).collect();
+        return self;
+    }
+}
+
+fn piece(name: &str, letter: char, betza: &str) -> PieceDef {
+    // for the built in pieces, whose Betza strings are known to parse
+    return PieceDef::new(name, letter, betza).unwrap_or_else(|e| panic!("{}", e));
+}
+
+impl Rules {
+    pub fn new(
+        name: &str,
+        width: usize,
+        height: usize,
+        pieces: Vec<PieceDef>,
+        start: &str,
+    ) -> Result<Rules, String> {
+        if !(4..=MAX_SIZE).contains(&width) || !(4..=MAX_SIZE).contains(&height) {
+            return Err(format!("a {}x{} board is not supported", width, height));
+        }
+        for (i, p) in pieces.iter().enumerate() {
+            if pieces[..i].iter().any(|q| q.letter == p.letter) {
+                return Err(format!("two pieces use the letter {}", p.letter));
+            }
+        }
+        let rules = Rules {
+            name: name.to_string(),
+            width,
+            height,
+            pieces,
+            start: start.to_string(),
+        };
+        // the start position has to fit the rules
+        FairyBoard::parse_fen(Arc::new(rules.clone()), start)?;
+        return Ok(rules);
+    }
+
+    fn orthodox_pieces(promotions: &str) -> Vec<PieceDef> {
+        return vec![
+            piece("King", 'K', "K").royal(),
+            piece("Queen", 'Q', "Q"),
+            piece("Rook", 'R', "R"),
+            piece("Bishop", 'B', "B"),
+            piece("Knight", 'N', "N"),
+            piece("Pawn", 'P', "fmWfcFifmnD").promotes_to(promotions),
+        ];
+    }
+
+    pub fn standard() -> Rules {
+        // the usual pieces on the usual board
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        return Rules::new("chess", 8, 8, Rules::orthodox_pieces("QRBN"), start)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    fn capablanca_pieces() -> Vec<PieceDef> {
+        let mut pieces = Rules::orthodox_pieces("QCARBN");
+        pieces.push(piece("Archbishop", 'A', "BN"));
+        pieces.push(piece("Chancellor", 'C', "RN"));
+        return pieces;
+    }
+
+    pub fn capablanca() -> Rules {
+        // the 10x8 pieces and start; the king castles to the c or i file
+        let start = "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1";
+        return Rules::new("capablanca", 10, 8, Rules::capablanca_pieces(), start)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn gothic() -> Rules {
+        // Capablanca's pieces in another order
+        let start = "rnbqckabnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNBQCKABNR w KQkq - 0 1";
+        return Rules::new("gothic", 10, 8, Rules::capablanca_pieces(), start)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn fairy_pieces() -> Vec<PieceDef> {
+        // a few common ones to build rules from
+        return vec![
+            piece("Archbishop", 'A', "BN"),
+            piece("Chancellor", 'C', "RN"),
+            piece("Amazon", 'M', "QN"),
+            piece("Camel", 'L', "C"),
+            piece("Grasshopper", 'G', "gQ"),
+        ];
+    }
+
+    pub fn piece_index(&self, letter: char) -> Option<usize> {
+        let letter = letter.to_ascii_uppercase();
+        return self.pieces.iter().position(|p| p.letter == letter);
+    }
+}
+
+impl FairyBoard {
+    pub fn new(rules: Arc<Rules>) -> FairyBoard {
+        let start = rules.start.clone();
+        return FairyBoard::parse_fen(rules, &start).unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    pub fn parse_fen(rules: Arc<Rules>, fen: &str) -> Result<FairyBoard, String> {
+        // ranks may hold numbers above 9, as in 10 for an empty rank of a 10x8 board
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or("empty FEN")?;
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != rules.height {
+            return Err(format!(
+                "expected {} ranks, found {}",
+                rules.height,
+                ranks.len()
+            ));
+        }
+        let mut squares = vec![None; rules.width * rules.height];
+        for (i, text) in ranks.iter().enumerate() {
+            let rank = rules.height - 1 - i;
+            let mut file = 0;
+            let mut empty = 0;
+            for c in text.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    empty = empty * 10 + d as usize;
+                    continue;
+                }
+                file += empty;
+                empty = 0;
+                let index = match rules.piece_index(c) {
+                    Some(index) => index,
+                    None => return Err(format!("invalid piece '{}'", c)),
+                };
+                if file >= rules.width {
+                    return Err(format!("rank '{}' is too long", text));
+                }
+                let side = if c.is_ascii_uppercase() {
+                    Turn::White
+                } else {
+                    Turn::Black
+                };
+                squares[rank * rules.width + file] = Some((index, side));
+                file += 1;
+            }
+            if file + empty != rules.width {
+                return Err(format!(
+                    "rank '{}' does not have {} squares",
+                    text, rules.width
+                ));
+            }
+        }
+        let to_move = match fields.next() {
+            Some("w") | None => Turn::White,
+            Some("b") => Turn::Black,
+            Some(y) => return Err(format!("invalid side to move '{}'", y)),
+        };
+        // K and Q are the corner pieces on the last and the first file
+        let mut castling = [false; 4];
+        let rights = fields.next().unwrap_or("-");
+        if rights != "-" {
+            for c in rights.chars() {
+                match "KQkq".find(c) {
+                    Some(i) => castling[i] = true,
+                    None => return Err(format!("invalid castling rights '{}'", rights)),
+                }
+            }
+        }
+        let passed = fields.next().unwrap_or("-");
+        let counters: Vec<u32> = fields.filter_map(|f| f.parse().ok()).collect();
+        let mut board = FairyBoard {
+            rules,
+            squares,
+            to_move,
+            castling,
+            en_passant: None,
+            half_move: counters.first().copied().unwrap_or(0),
+            move_number: counters.get(1).copied().unwrap_or(1),
+        };
+        if passed != "-" {
+            match board.parse_square(passed) {
+                Some(square) => board.en_passant = Some(square),
+                None => return Err(format!("invalid en passant square '{}'", passed)),
+            }
+        }
+        return Ok(board);
+    }
+
+    pub fn to_fen(&self) -> String {
+        let width = self.rules.width;
+        let mut ranks = Vec::new();
+        for rank in (0..self.rules.height).rev() {
+            let mut text = String::new();
+            let mut empty = 0;
+            for file in 0..width {
+                match self.squares[rank * width + file] {
+                    None => empty += 1,
+                    Some((index, side)) => {
+                        if empty > 0 {
+                            text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = self.rules.pieces[index].letter;
+                        text.push(if side == Turn::White {
+                            letter
+                        } else {
+                            letter.to_ascii_lowercase()
+                        });
+                    }
+                }
+            }
+            if empty > 0 {
+                text.push_str(&empty.to_string());
+            }
+            ranks.push(text);
+        }
+        let side = if self.to_move == Turn::White {
+            "w"
+        } else {
+            "b"
+        };
+        let mut rights: String = "KQkq"
+            .chars()
+            .zip(self.castling.iter())
+            .filter(|(_, allowed)| **allowed)
+            .map(|(c, _)| c)
+            .collect();
+        if rights.is_empty() {
+            rights.push('-');
+        }
+        let passed = match self.en_passant {
+            Some(square) => self.square_name(square),
+            None => String::from("-"),
+        };
+        return format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            side,
+            rights,
+            passed,
+            self.half_move,
+            self.move_number
+        );
+    }
+
+    pub fn square_name(&self, square: usize) -> String {
+        let file = (b'a' + (square % self.rules.width) as u8) as char;
+        return format!("{}{}", file, square / self.rules.width + 1);
+    }
+
+    pub fn parse_square(&self, text: &str) -> Option<usize> {
+        let mut chars = text.chars();
+        let file = chars.next()? as usize;
+        let file = file
+            .checked_sub('a' as usize)
+            .filter(|f| *f < self.rules.width)?;
+        let rank = chars
+            .as_str()
+            .parse::<usize>()
+            .ok()
+            .filter(|r| (1..=self.rules.height).contains(r))?;
+        return Some((rank - 1) * self.rules.width + file);
+    }
+
+    pub fn move_name(&self, m: &FairyMove) -> String {
+        // coordinates, then the promotion letter in lowercase
+        let mut text = format!("{}{}", self.square_name(m.from), self.square_name(m.to));
+        if let Some(index) = m.promotion {
+            text.push(self.rules.pieces[index].letter.to_ascii_lowercase());
+        }
+        return text;
+    }
+
+    pub fn parse_move(&self, text: &str) -> Result<FairyMove, String> {
+        return self
+            .generate_moves()
+            .into_iter()
+            .find(|m| self.move_name(m) == text.trim())
+            .ok_or(format!("{} is not a legal move", text));
+    }
+
+    fn targets(
+        &self,
+        from: usize,
+        side: Turn,
+        step: &Step,
+        out: &mut Vec<usize>,
+        captures_only: bool,
+    ) {
+        let (w, h) = (self.rules.width as i32, self.rules.height as i32);
+        let (x, y) = (
+            (from % self.rules.width) as i32,
+            (from / self.rules.width) as i32,
+        );
+        if step.initial {
+            let second = if side == Turn::White { 1 } else { h - 2 };
+            if y != second {
+                return;
+            }
+        }
+        let dy = if side == Turn::White {
+            step.dy
+        } else {
+            -step.dy
+        };
+        let dx = step.dx;
+        let on_board = |x: i32, y: i32| x >= 0 && y >= 0 && x < w && y < h;
+        let index = |x: i32, y: i32| (y * w + x) as usize;
+        let (mut cx, mut cy) = (x, y);
+        let mut count = 0;
+        let mut hopped = false;
+        loop {
+            cx += dx;
+            cy += dy;
+            count += 1;
+            if !on_board(cx, cy) {
+                return;
+            }
+            if step.lame {
+                let g = gcd(dx, dy);
+                let blocked = (1..g).any(|j| {
+                    self.squares[index(cx - dx + j * dx / g, cy - dy + j * dy / g)].is_some()
+                });
+                if blocked {
+                    return;
+                }
+            }
+            let occupant = self.squares[index(cx, cy)];
+            if step.hop && !hopped {
+                // the grasshopper needs a hurdle, and lands right behind it
+                if occupant.is_some() {
+                    hopped = true;
+                }
+                continue;
+            }
+            match occupant {
+                Some((_, owner)) if owner == side => return,
+                Some(_) => {
+                    if step.mode != Mode::Move {
+                        out.push(index(cx, cy));
+                    }
+                    return;
+                }
+                None => {
+                    if step.mode != Mode::Capture && !captures_only {
+                        out.push(index(cx, cy));
+                    }
+                }
+            }
+            if step.hop || (step.range != 0 && count >= step.range) {
+                return;
+            }
+        }
+    }
+
+    pub fn is_attacked(&self, square: usize, by: Turn) -> bool {
+        // whether a piece of `by` could capture on the square, were there something to take
+        let mut board = self.clone();
+        if board.squares[square].is_none_or(|(_, owner)| owner == by) {
+            board.squares[square] = Some((0, by.opposite_turn()));
+        }
+        let mut targets = Vec::new();
+        for (from, occupant) in board.squares.iter().enumerate() {
+            if let Some((index, owner)) = occupant {
+                if *owner != by {
+                    continue;
+                }
+                for step in &board.rules.pieces[*index].steps {
+                    targets.clear();
+                    board.targets(from, by, step, &mut targets, true);
+                    if targets.contains(&square) {
+                        return true;
+                    }
+                }
+            }
+        }
+        return false;
+    }
+
+    pub fn in_check(&self, side: Turn) -> bool {
+        let royal = self.squares.iter().enumerate().filter(|(_, s)| {
+            s.is_some_and(|(index, owner)| owner == side && self.rules.pieces[index].royal)
+        });
+        for (square, _) in royal {
+            if self.is_attacked(square, side.opposite_turn()) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn is_royal(&self, square: usize) -> bool {
+        return self.squares[square].is_some_and(|(index, _)| self.rules.pieces[index].royal);
+    }
+
+    fn castling_moves(&self, moves: &mut Vec<FairyMove>) {
+        // the royal piece goes to the c file or the one next to the last, and the corner
+        // piece lands beside it on the inside; neither may jump over anything, and the
+        // royal piece may not start in, pass through or land in check
+        let side = self.to_move;
+        let w = self.rules.width;
+        let (rank, first_right) = if side == Turn::White {
+            (0, 0)
+        } else {
+            (self.rules.height - 1, 2)
+        };
+        let king = match (1..w - 1)
+            .map(|file| rank * w + file)
+            .find(|s| self.squares[*s].is_some_and(|(_, owner)| owner == side) && self.is_royal(*s))
+        {
+            Some(king) => king,
+            None => return,
+        };
+        if !self.castling[first_right..first_right + 2].contains(&true) || self.in_check(side) {
+            return;
+        }
+        let from = king % w;
+        for (right, corner, to, rook_to) in [
+            (first_right, w - 1, w - 2, w - 3),
+            (first_right + 1, 0, 2, 3),
+        ] {
+            let rook = rank * w + corner;
+            if !self.castling[right]
+                || from == to
+                || !self.squares[rook].is_some_and(|(_, owner)| owner == side)
+                || self.is_royal(rook)
+            {
+                continue;
+            }
+            let low = from.min(to).min(rook_to).min(corner);
+            let high = from.max(to).max(rook_to).max(corner);
+            let blocked = (low..=high)
+                .map(|file| rank * w + file)
+                .any(|s| s != king && s != rook && self.squares[s].is_some());
+            let crossed = if to > from { from + 1..to } else { to + 1..from };
+            let attacked = crossed
+                .map(|file| rank * w + file)
+                .any(|s| self.is_attacked(s, side.opposite_turn()));
+            if !blocked && !attacked {
+                moves.push(FairyMove {
+                    from: king,
+                    to: rank * w + to,
+                    promotion: None,
+                    castling: true,
+                });
+            }
+        }
+    }
+
+    pub fn play_move(&mut self, m: &FairyMove) {
+        let w = self.rules.width;
+        let moving = self.squares[m.from];
+        let mut capture = self.squares[m.to].is_some();
+        let is_pawn =
+            moving.is_some_and(|(index, _)| !self.rules.pieces[index].promotes_to.is_empty());
+        if m.castling {
+            let first = m.from - m.from % w;
+            let (rook, rook_to) = if m.to > m.from {
+                (first + w - 1, m.to - 1)
+            } else {
+                (first, m.to + 1)
+            };
+            let corner = self.squares[rook];
+            self.squares[rook] = None;
+            self.squares[m.from] = None;
+            self.squares[m.to] = moving;
+            self.squares[rook_to] = corner;
+        } else {
+            if is_pawn && Some(m.to) == self.en_passant && m.from % w != m.to % w {
+                // the pawn taken en passant stands just past the square it skipped
+                let taken = if self.to_move == Turn::White {
+                    m.to - w
+                } else {
+                    m.to + w
+                };
+                self.squares[taken] = None;
+                capture = true;
+            }
+            self.squares[m.to] = match m.promotion {
+                Some(index) => Some((index, self.to_move)),
+                None => moving,
+            };
+            self.squares[m.from] = None;
+        }
+        self.en_passant = None;
+        if is_pawn && m.from % w == m.to % w && (m.from / w).abs_diff(m.to / w) == 2 {
+            self.en_passant = Some((m.from + m.to) / 2);
+        }
+        // moving the royal piece gives up both sides, and moving or taking a corner
+        // piece its own
+        if moving.is_some_and(|(index, _)| self.rules.pieces[index].royal) {
+            let first = if self.to_move == Turn::White { 0 } else { 2 };
+            self.castling[first] = false;
+            self.castling[first + 1] = false;
+        }
+        let top = (self.rules.height - 1) * w;
+        for (right, corner) in [w - 1, 0, top + w - 1, top].iter().enumerate() {
+            if m.from == *corner || m.to == *corner {
+                self.castling[right] = false;
+            }
+        }
+        self.half_move = if capture || is_pawn {
+            0
+        } else {
+            self.half_move + 1
+        };
+        if self.to_move == Turn::Black {
+            self.move_number += 1;
+        }
+        self.to_move = self.to_move.opposite_turn();
+    }
+
+    pub fn generate_moves(&self) -> Vec<FairyMove> {
+        let side = self.to_move;
+        let last_rank = if side == Turn::White {
+            self.rules.height - 1
+        } else {
+            0
+        };
+        let mut moves = Vec::new();
+        let mut targets = Vec::new();
+        for (from, occupant) in self.squares.iter().enumerate() {
+            let (index, owner) = match occupant {
+                Some(o) => *o,
+                None => continue,
+            };
+            if owner != side {
+                continue;
+            }
+            let def = &self.rules.pieces[index];
+            targets.clear();
+            for step in &def.steps {
+                self.targets(from, side, step, &mut targets, false);
+            }
+            if let Some(passed) = self.en_passant.filter(|_| !def.promotes_to.is_empty()) {
+                // a pawn-like piece may take the one that skipped past it, as though it had
+                // only made a single step
+                let mut board = self.clone();
+                board.squares[passed] = Some((index, side.opposite_turn()));
+                for step in def.steps.iter().filter(|s| s.mode == Mode::Capture) {
+                    board.targets(from, side, step, &mut targets, true);
+                }
+            }
+            targets.sort_unstable();
+            targets.dedup();
+            for to in targets.iter() {
+                if !def.promotes_to.is_empty() && to / self.rules.width == last_rank {
+                    for letter in &def.promotes_to {
+                        if let Some(p) = self.rules.piece_index(*letter) {
+                            moves.push(FairyMove {
+                                from,
+                                to: *to,
+                                promotion: Some(p),
+                                castling: false,
+                            });
+                        }
+                    }
+                } else {
+                    moves.push(FairyMove {
+                        from,
+                        to: *to,
+                        promotion: None,
+                        castling: false,
+                    });
+                }
+            }
+        }
+        self.castling_moves(&mut moves);
+        moves.retain(|m| {
+            let mut after = self.clone();
+            after.play_move(m);
+            !after.in_check(side)
+        });
+        return moves;
+    }
+
+    pub fn result(&self) -> Option<GameResult> {
+        // mate or stalemate; repetition and the 50 move rule are left to the caller
+        if !self.generate_moves().is_empty() {
+            return None;
+        }
+        if self.in_check(self.to_move) {
+            return Some(GameResult::win_for(self.to_move.opposite_turn()));
+        }
+        return Some(GameResult::Draw);
+    }
+
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.generate_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        let mut count = 0;
+        for m in moves {
+            let mut next = self.clone();
+            next.play_move(&m);
+            count += next.perft(depth - 1);
+        }
+        return count;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn lone_piece(betza: &str, square: &str) -> usize {
+        // how many moves one piece has on an otherwise empty 8x8 board
+        let mut pieces = vec![PieceDef::new("Test", 'T', betza).unwrap()];
+        pieces.push(PieceDef::new("King", 'K', "K").unwrap().royal());
+        let rules =
+            Arc::new(Rules::new("test", 8, 8, pieces, "8/8/8/8/8/8/8/8 w - - 0 1").unwrap());
+        let mut board = FairyBoard::new(rules);
+        let square = board.parse_square(square).unwrap();
+        board.squares[square] = Some((0, Turn::White));
+        return board.generate_moves().len();
+    }
+
+    #[test]
+    fn test_betza() {
+        assert_eq!(lone_piece("N", "d4"), 8);
+        assert_eq!(lone_piece("C", "d4"), 8);
+        assert_eq!(lone_piece("C", "a1"), 2);
+        assert_eq!(lone_piece("BN", "d4"), 13 + 8);
+        assert_eq!(lone_piece("RN", "a1"), 14 + 2);
+        assert_eq!(lone_piece("QN", "d4"), 27 + 8);
+        assert_eq!(lone_piece("W2", "a1"), 4);
+        assert_eq!(lone_piece("fmW", "d4"), 1);
+        assert_eq!(lone_piece("flF", "d4"), 1);
+        assert_eq!(lone_piece("vR", "d4"), 7);
+        // a grasshopper needs something to hop over
+        assert_eq!(lone_piece("gQ", "d4"), 0);
+        assert!(PieceDef::new("Bad", 'X', "Y").is_err());
+        assert!(PieceDef::new("Bad", 'X', "nN").is_err());
+        assert!(PieceDef::new("Bad", 'X', "fm").is_err());
+    }
+
+    #[test]
+    fn test_grasshopper() {
+        let mut pieces = Rules::orthodox_pieces("Q");
+        pieces.push(PieceDef::new("Grasshopper", 'G', "gQ").unwrap());
+        let rules = Arc::new(
+            Rules::new("test", 8, 8, pieces, "4k3/8/8/8/1p1G2P1/8/3P4/K7 w - - 0 1").unwrap(),
+        );
+        let board = FairyBoard::new(rules);
+        let moves: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .filter(|m| board.square_name(m.from) == "d4")
+            .map(|m| board.move_name(m))
+            .collect();
+        // over the black pawn, and over its own pawns as well
+        assert_eq!(moves.len(), 3);
+        for m in ["d4a4", "d4h4", "d4d1"] {
+            assert!(moves.contains(&String::from(m)));
+        }
+        // it only checks the king from right behind a hurdle
+        let board =
+            FairyBoard::parse_fen(board.rules.clone(), "4k3/8/4p3/4G3/8/8/8/K7 b - - 0 1").unwrap();
+        assert!(!board.in_check(Turn::Black));
+        let board =
+            FairyBoard::parse_fen(board.rules.clone(), "4k3/4p3/8/4G3/8/8/8/K7 b - - 0 1").unwrap();
+        assert!(board.in_check(Turn::Black));
+    }
+
+    #[test]
+    fn test_perft() {
+        let board = FairyBoard::new(Arc::new(Rules::standard()));
+        assert_eq!(board.perft(3), 8902);
+        // castling, en passant and promotions agree with the 8x8 board
+        let fens = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 2, 2039),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4, 43238),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 3, 9467),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 2, 1486),
+        ];
+        for (fen, depth, count) in fens.iter() {
+            let board = FairyBoard::parse_fen(Arc::new(Rules::standard()), fen).unwrap();
+            assert_eq!(board.to_fen(), *fen);
+            assert_eq!(board.perft(*depth), *count);
+        }
+        let board = FairyBoard::new(Arc::new(Rules::capablanca()));
+        assert_eq!(
+            board.to_fen(),
+            "rnabqkbcnr/pppppppppp/10/10/10/10/PPPPPPPPPP/RNABQKBCNR w KQkq - 0 1"
+        );
+        assert_eq!(board.perft(4), 805128);
+        let board = FairyBoard::new(Arc::new(Rules::gothic()));
+        assert_eq!(board.perft(1), 28);
+        let m = board.parse_move("e2e4").unwrap();
+        let mut next = board.clone();
+        next.play_move(&m);
+        assert!(next
+            .to_fen()
+            .starts_with("rnbqckabnr/pppppppppp/10/10/4P5/10/PPPP1PPPPP/RNBQCKABNR b KQkq e3"));
+    }
+
+    #[test]
+    fn test_castling_and_en_passant() {
+        // on the 10x8 board the king goes three files either way, from f to c or i
+        let rules = Arc::new(Rules::capablanca());
+        let board =
+            FairyBoard::parse_fen(rules, "r4k3r/10/10/10/4Pp4/10/10/R4K3R b KQkq e3 0 1").unwrap();
+        // two pawn moves, 11 and 10 for the rooks, five king steps and both castlings
+        assert_eq!(board.perft(1), 30);
+        let after = |text: &str| {
+            let mut next = board.clone();
+            next.play_move(&board.parse_move(text).unwrap());
+            next.to_fen()
+        };
+        assert_eq!(after("f8i8"), "r6rk1/10/10/10/4Pp4/10/10/R4K3R w KQ - 1 2");
+        assert_eq!(after("f8c8"), "2kr5r/10/10/10/4Pp4/10/10/R4K3R w KQ - 1 2");
+        assert_eq!(after("f4e3"), "r4k3r/10/10/10/10/4p5/10/R4K3R w KQkq - 0 2");
+        assert_eq!(after("a8a1"), "5k3r/10/10/10/4Pp4/10/10/r4K3R w Kk - 0 2");
+        // not through an attacked square
+        let board = FairyBoard::parse_fen(
+            board.rules.clone(),
+            "r4k3r/10/10/10/10/10/10/R2R1K3R b KQkq - 0 1",
+        )
+        .unwrap();
+        assert!(board.parse_move("f8c8").is_err());
+        assert!(board.parse_move("f8i8").is_ok());
+    }
+}
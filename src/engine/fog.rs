@@ -0,0 +1,319 @@
+use super::board::{Board, Move, Turn};
+use super::game::{self, GameResult, Termination};
+use super::piece::{Piece, PieceColor, PieceType};
+
+// squares as bits, row * 8 + col like everywhere else
+fn bit(square: (usize, usize)) -> u64 {
+    return 1 << (square.0 * 8 + square.1);
+}
+
+fn reaches(board: &Board, start: (usize, usize), target: (usize, usize)) -> bool {
+    // a move the piece could make if checks didn't count, as in Dark chess
+    let piece = board.squares[start.0][start.1];
+    let captured = board.squares[target.0][target.1];
+    if start == target || captured.as_color() == piece.as_color() {
+        return false;
+    }
+    if !piece.valid_move(start, target, ['-'; 4]) || board.piece_in_path(start, target, piece) {
+        return false;
+    }
+    if piece.as_type() == PieceType::Pawn {
+        if start.1 == target.1 {
+            return captured == Piece::Blank;
+        }
+        return captured != Piece::Blank || board.en_passant_square() == Some(target);
+    }
+    return true;
+}
+
+pub fn visible_squares(board: &Board, color: PieceColor) -> u64 {
+    // a player sees the squares of their own pieces and every square those can move to
+    let mut visible = 0;
+    for i in 0..8 {
+        for j in 0..8 {
+            if board.squares[i][j].as_color() != color {
+                continue;
+            }
+            visible |= bit((i, j));
+            for m in 0..8 {
+                for n in 0..8 {
+                    if reaches(board, (i, j), (m, n)) {
+                        visible |= bit((m, n));
+                    }
+                }
+            }
+        }
+    }
+    return visible;
+}
+
+pub fn redacted(board: &Board, color: PieceColor) -> Board {
+    // the board as the player sees it: hidden squares look empty, and the other side's
+    // castling rights and an unseen en passant square are dropped
+    let visible = visible_squares(board, color);
+    let mut view = *board;
+    for i in 0..8 {
+        for j in 0..8 {
+            if visible & bit((i, j)) == 0 {
+                view.squares[i][j] = Piece::Blank;
+            }
+        }
+    }
+    let theirs = if color == PieceColor::White {
+        2..4
+    } else {
+        0..2
+    };
+    for i in theirs {
+        view.can_castle[i] = '-';
+    }
+    if board
+        .en_passant_square()
+        .is_some_and(|s| visible & bit(s) == 0)
+    {
+        view.en_passant = ('-', '-');
+    }
+    return view;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CheckDirection {
+    Rank,
+    File,
+    // the longer and shorter of the two diagonals through the king
+    LongDiagonal,
+    ShortDiagonal,
+    Knight,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Capture {
+    pub square: (usize, usize),
+    // Kriegspiel only tells a pawn from a piece
+    pub pawn: bool,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Report {
+    pub capture: Option<Capture>,
+    pub checks: Vec<CheckDirection>,
+    // how many pawn captures the side to move now has, the "tries"
+    pub pawn_tries: usize,
+    pub result: Option<(GameResult, Termination)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Verdict {
+    // the move was not played and the player tries again
+    Illegal,
+    Legal(Report),
+}
+
+pub struct Umpire {
+    board: Board,
+    history: Vec<u64>,
+}
+
+fn check_direction(king: (usize, usize), attacker: (usize, usize), piece: Piece) -> CheckDirection {
+    if piece.as_type() == PieceType::Knight {
+        return CheckDirection::Knight;
+    }
+    if king.0 == attacker.0 {
+        return CheckDirection::Rank;
+    }
+    if king.1 == attacker.1 {
+        return CheckDirection::File;
+    }
+    // a diagonal is long if it holds more squares than the other one through the king
+    let (row, col) = (king.0 as i32, king.1 as i32);
+    let falling = 8 - (row - col).abs();
+    let rising = 8 - (row + col - 7).abs();
+    let on_falling = attacker.0 as i32 - attacker.1 as i32 == row - col;
+    let (this, other) = if on_falling {
+        (falling, rising)
+    } else {
+        (rising, falling)
+    };
+    if this > other {
+        return CheckDirection::LongDiagonal;
+    }
+    return CheckDirection::ShortDiagonal;
+}
+
+impl Umpire {
+    pub fn new(board: Board) -> Umpire {
+        Umpire {
+            history: vec![board.hash()],
+            board,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        // the real position, for the umpire's eyes only
+        return &self.board;
+    }
+
+    pub fn view(&self, color: PieceColor) -> Board {
+        // in Kriegspiel a player sees nothing but their own pieces
+        let mut view = self.board;
+        for square in view.squares.iter_mut().flatten() {
+            if square.as_color() != color {
+                *square = Piece::Blank;
+            }
+        }
+        return view;
+    }
+
+    pub fn pawn_tries(&self) -> usize {
+        return self
+            .board
+            .generate_moves()
+            .iter()
+            .filter(|m| m.piece.as_type() == PieceType::Pawn && self.board.is_capture(m))
+            .count();
+    }
+
+    fn checks(&self) -> Vec<CheckDirection> {
+        let color = self.board.to_move.as_color();
+        let king = match self.board.king_position(color) {
+            Some(king) => king,
+            None => return Vec::new(),
+        };
+        let mut checks = Vec::new();
+        for i in 0..8 {
+            for j in 0..8 {
+                let piece = self.board.squares[i][j];
+                if piece.as_color() == color.opposite() && reaches(&self.board, (i, j), king) {
+                    checks.push(check_direction(king, (i, j), piece));
+                }
+            }
+        }
+        return checks;
+    }
+
+    pub fn try_move(&mut self, m: Move) -> Verdict {
+        // legality comes from the real board, and an illegal try changes nothing
+        if !self.board.validate_move(m.start, m.end, m.piece) {
+            return Verdict::Illegal;
+        }
+        let capture = if self.board.is_capture(&m) {
+            let en_passant = !self.board.is_occupied(m.end);
+            let pawn =
+                en_passant || self.board.squares[m.end.0][m.end.1].as_type() == PieceType::Pawn;
+            let square = if en_passant {
+                (m.start.0, m.end.1)
+            } else {
+                m.end
+            };
+            Some(Capture { square, pawn })
+        } else {
+            None
+        };
+        self.board.play_move(m);
+        let result = game::adjudicate(&self.board, &self.history);
+        self.history.push(self.board.hash());
+        return Verdict::Legal(Report {
+            capture,
+            checks: self.checks(),
+            pawn_tries: self.pawn_tries(),
+            result,
+        });
+    }
+
+    pub fn try_str(&mut self, text: &str) -> Result<Verdict, String> {
+        // coordinates such as e2e4 or e7e8q; only a malformed move is an error
+        let text = text.trim();
+        let square = |s: Option<&str>| s.and_then(Board::parse_square);
+        let start = square(text.get(0..2)).ok_or(format!("'{}' is not a move like e2e4", text))?;
+        let end = square(text.get(2..4)).ok_or(format!("'{}' is not a move like e2e4", text))?;
+        let piece = self.board.squares[start.0][start.1];
+        if piece.as_color() != self.board.to_move.as_color() {
+            return Ok(Verdict::Illegal);
+        }
+        let mut m = Move::new(piece, start, end);
+        if piece.as_type() == PieceType::Pawn && (end.0 == 0 || end.0 == 7) {
+            let ptype = match text.get(4..5) {
+                Some("r") => PieceType::Rook,
+                Some("b") => PieceType::Bishop,
+                Some("n") => PieceType::Knight,
+                _ => PieceType::Queen,
+            };
+            m.promotion = Piece::from_type(ptype, piece.as_color());
+        }
+        return Ok(self.try_move(m));
+    }
+
+    pub fn to_move(&self) -> Turn {
+        return self.board.to_move;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_visibility() {
+        let board = Board::default();
+        let visible = visible_squares(&board, PieceColor::White);
+        assert_eq!(visible.count_ones(), 32);
+        let view = redacted(&board, PieceColor::Black);
+        let pieces = view
+            .squares
+            .iter()
+            .flatten()
+            .filter(|p| **p != Piece::Blank)
+            .count();
+        assert_eq!(pieces, 16);
+        assert_eq!(view.can_castle, ['-', '-', 'k', 'q']);
+        // a pawn sees what it could take, not what blocks it
+        let board = Board::from_fen(String::from("4k3/8/8/3q4/2pP4/8/8/4K3 w - - 0 1"));
+        let view = redacted(&board, PieceColor::White);
+        assert!(view.squares[3][3] == Piece::Blank);
+        assert!(view.squares[4][2] == Piece::Blank);
+        let board = Board::from_fen(String::from("4k3/8/8/2q5/3P4/8/8/4K3 w - - 0 1"));
+        assert!(redacted(&board, PieceColor::White).squares[3][2] == Piece::BQueen);
+    }
+
+    #[test]
+    fn test_umpire() {
+        let mut umpire = Umpire::new(Board::default());
+        assert_eq!(umpire.try_str("e1e3").unwrap(), Verdict::Illegal);
+        assert!(umpire.try_str("e9").is_err());
+        assert!(umpire.try_str("e2e4").unwrap() != Verdict::Illegal);
+        let report = match umpire.try_str("d7d5").unwrap() {
+            Verdict::Legal(report) => report,
+            Verdict::Illegal => panic!("d7d5 is legal"),
+        };
+        assert_eq!(report.pawn_tries, 1);
+        assert_eq!(report.capture, None);
+        let report = match umpire.try_str("e4d5").unwrap() {
+            Verdict::Legal(report) => report,
+            Verdict::Illegal => panic!("exd5 is legal"),
+        };
+        assert_eq!(
+            report.capture,
+            Some(Capture {
+                square: (3, 3),
+                pawn: true
+            })
+        );
+        umpire.try_str("d8d5").unwrap();
+        let report = match umpire.try_str("f1b5").unwrap() {
+            Verdict::Legal(report) => report,
+            Verdict::Illegal => panic!("Bb5 is legal"),
+        };
+        assert_eq!(report.checks, vec![CheckDirection::LongDiagonal]);
+        assert!(umpire.to_move() == Turn::Black);
+        assert_eq!(umpire.try_str("g8f6").unwrap(), Verdict::Illegal);
+        let pieces = umpire
+            .view(PieceColor::Black)
+            .squares
+            .iter()
+            .flatten()
+            .filter(|p| **p != Piece::Blank)
+            .count();
+        assert_eq!(pieces, 15);
+    }
+}
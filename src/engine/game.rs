@@ -0,0 +1,413 @@
+use super::board::{Board, Move, Turn};
+use super::pgn::PgnGame;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    Resignation,
+    TimeForfeit,
+    Agreement,
+    Adjudication,
+}
+
+#[derive(Clone)]
+pub struct Game {
+    start: Board,
+    moves: Vec<Move>,
+    // boards[i] is the position after i moves, so there is always one more board than moves
+    boards: Vec<Board>,
+    hashes: Vec<u64>,
+    ply: usize,
+    pub tags: Vec<(String, String)>,
+    result: Option<(GameResult, Termination)>,
+}
+
+impl GameResult {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            GameResult::WhiteWins => "1-0",
+            GameResult::BlackWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<GameResult> {
+        match text {
+            "1-0" => Some(GameResult::WhiteWins),
+            "0-1" => Some(GameResult::BlackWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            _ => None,
+        }
+    }
+
+    pub fn win_for(turn: Turn) -> GameResult {
+        match turn {
+            Turn::White => GameResult::WhiteWins,
+            Turn::Black => GameResult::BlackWins,
+        }
+    }
+}
+
+impl Termination {
+    pub fn reason(&self, result: GameResult) -> String {
+        // a short human readable explanation, as used in xboard result lines
+        let (winner, loser) = match result {
+            GameResult::BlackWins => ("Black", "White"),
+            _ => ("White", "Black"),
+        };
+        match *self {
+            Termination::Checkmate => format!("{} mates", winner),
+            Termination::Stalemate => String::from("Stalemate"),
+            Termination::Repetition => String::from("Draw by repetition"),
+            Termination::FiftyMoves => String::from("50 move rule"),
+            Termination::InsufficientMaterial => String::from("Insufficient material"),
+            Termination::Resignation => format!("{} resigns", loser),
+            Termination::TimeForfeit => format!("{} forfeits on time", loser),
+            Termination::Agreement => String::from("Draw by agreement"),
+            Termination::Adjudication => String::from("Adjudication"),
+        }
+    }
+}
+
+pub fn adjudicate(board: &Board, history: &[u64]) -> Option<(GameResult, Termination)> {
+    // the result the rules force on this position, history holds the earlier positions
+    if board.generate_moves().is_empty() {
+        if board.is_in_check() {
+            let winner = GameResult::win_for(board.to_move.opposite_turn());
+            return Some((winner, Termination::Checkmate));
+        }
+        return Some((GameResult::Draw, Termination::Stalemate));
+    }
+    if board.half_move >= 100 {
+        return Some((GameResult::Draw, Termination::FiftyMoves));
+    }
+    if board.is_insufficient_material() {
+        return Some((GameResult::Draw, Termination::InsufficientMaterial));
+    }
+    let hash = board.hash();
+    if history.iter().filter(|h| **h == hash).count() >= 2 {
+        return Some((GameResult::Draw, Termination::Repetition));
+    }
+    return None;
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        return Game::new(Board::default());
+    }
+}
+
+impl Game {
+    pub fn new(start: Board) -> Game {
+        let mut game = Game {
+            start,
+            moves: Vec::new(),
+            boards: vec![start],
+            hashes: vec![start.hash()],
+            ply: 0,
+            tags: Vec::new(),
+            result: None,
+        };
+        game.result = adjudicate(&start, &[]);
+        return game;
+    }
+
+    pub fn start(&self) -> &Board {
+        return &self.start;
+    }
+
+    pub fn board(&self) -> &Board {
+        // the position at the current ply, which is not always the end of the game
+        return &self.boards[self.ply];
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        return &self.moves;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.moves.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.moves.is_empty();
+    }
+
+    pub fn ply(&self) -> usize {
+        return self.ply;
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        return self.ply == self.moves.len();
+    }
+
+    pub fn history(&self) -> &[u64] {
+        // hashes of the positions before the current one, as the search wants them
+        return &self.hashes[..self.ply];
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        if self.ply == 0 {
+            return None;
+        }
+        return Some(self.moves[self.ply - 1]);
+    }
+
+    pub fn result(&self) -> Option<(GameResult, Termination)> {
+        return self.result;
+    }
+
+    pub fn set_result(&mut self, result: GameResult, termination: Termination) {
+        // for results the board can't see: resignations, time forfeits and agreed draws
+        self.result = Some((result, termination));
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self
+            .tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str());
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn parse_move(&self, text: &str) -> Result<Move, String> {
+        // anything starting with two squares is a coordinate move, the rest is SAN
+        let text = text.trim();
+        let coordinate = text.len() >= 4
+            && text.is_ascii()
+            && Board::parse_square(&text[0..2]).is_some()
+            && Board::parse_square(&text[2..4]).is_some();
+        if coordinate {
+            return self.board().parse_move(text);
+        }
+        return self.board().parse_san(text);
+    }
+
+    pub fn play(&mut self, m: Move) -> Result<(), String> {
+        // playing before the end of the game replaces the moves after the current ply
+        if self.is_at_end() && self.result.is_some() {
+            return Err(String::from("the game is over"));
+        }
+        let board = *self.board();
+        if !board.generate_moves().contains(&m) {
+            return Err(format!("{} is not a legal move", m));
+        }
+        if self.moves.get(self.ply) == Some(&m) {
+            self.ply += 1;
+            return Ok(());
+        }
+        self.truncate();
+        let mut after = board;
+        after.play_move(m);
+        self.moves.push(m);
+        self.boards.push(after);
+        self.hashes.push(after.hash());
+        self.ply += 1;
+        self.result = adjudicate(&after, self.history());
+        return Ok(());
+    }
+
+    pub fn play_str(&mut self, text: &str) -> Result<Move, String> {
+        let m = self.parse_move(text)?;
+        self.play(m)?;
+        return Ok(m);
+    }
+
+    pub fn take_back(&mut self) -> Option<Move> {
+        // removes the last move of the game, not just the one before the cursor
+        let m = self.moves.pop()?;
+        self.boards.pop();
+        self.hashes.pop();
+        self.ply = self.ply.min(self.moves.len());
+        let history = &self.hashes[..self.moves.len()];
+        self.result = adjudicate(&self.boards[self.moves.len()], history);
+        return Some(m);
+    }
+
+    pub fn jump_to(&mut self, ply: usize) -> Result<(), String> {
+        if ply > self.moves.len() {
+            return Err(format!("the game has only {} plies", self.moves.len()));
+        }
+        self.ply = ply;
+        return Ok(());
+    }
+
+    pub fn branch(&self) -> Game {
+        // a copy of the game that stops at the current ply, to try other moves on
+        let mut game = self.clone();
+        game.truncate();
+        return game;
+    }
+
+    fn truncate(&mut self) {
+        if self.is_at_end() {
+            return;
+        }
+        self.moves.truncate(self.ply);
+        self.boards.truncate(self.ply + 1);
+        self.hashes.truncate(self.ply + 1);
+        self.result = adjudicate(self.board(), self.history());
+    }
+
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::from_moves(self.start, &self.moves);
+        pgn.tags = self.tags.clone();
+        if let Some((result, termination)) = self.result {
+            pgn.result = result.as_str().to_string();
+            let tag = match termination {
+                Termination::TimeForfeit => "time forfeit",
+                Termination::Adjudication => "adjudication",
+                _ => "normal",
+            };
+            pgn.set_tag("Termination", tag);
+        }
+        return pgn;
+    }
+
+    pub fn from_pgn(pgn: &PgnGame) -> Result<Game, String> {
+        // only the mainline is kept, variations and comments need a move tree
+        let mut game = Game::new(pgn.start);
+        game.tags = pgn.tags.clone();
+        for (i, m) in pgn.moves.iter().enumerate() {
+            if let Err(e) = game.play(m.mv) {
+                return Err(format!("ply {}: {}", i + 1, e));
+            }
+        }
+        if game.result.is_none() {
+            if let Some(result) = GameResult::parse(&pgn.result) {
+                // the PGN only says how the game ended, so guess the most likely reason
+                let termination = match (pgn.tag("Termination"), result) {
+                    (Some("time forfeit"), _) => Termination::TimeForfeit,
+                    (Some("adjudication"), _) => Termination::Adjudication,
+                    (_, GameResult::Draw) => Termination::Agreement,
+                    _ => Termination::Resignation,
+                };
+                game.result = Some((result, termination));
+            }
+        }
+        return Ok(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::engine::pgn::PgnReader;
+
+    fn play_all(game: &mut Game, moves: &str) {
+        for text in moves.split_whitespace() {
+            game.play_str(text).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_play_and_take_back() {
+        let mut game = Game::default();
+        play_all(&mut game, "e4 e5 Nf3");
+        assert_eq!(game.len(), 3);
+        assert_eq!(game.ply(), 3);
+        assert!(game.board().to_move == Turn::Black);
+        assert_eq!(game.history().len(), 3);
+        assert!(game.play_str("e4").is_err());
+        assert!(game.play_str("e7e5").is_err());
+
+        assert_eq!(game.take_back().unwrap().to_string(), "g1f3");
+        assert_eq!(
+            game.board().to_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"
+        );
+        game.take_back();
+        game.take_back();
+        assert!(game.take_back().is_none());
+        assert_eq!(game.board().to_fen(), Board::default().to_fen());
+    }
+
+    #[test]
+    fn test_jump_and_branch() {
+        let mut game = Game::default();
+        play_all(&mut game, "e4 e5 Nf3 Nc6");
+        game.jump_to(2).unwrap();
+        assert_eq!(game.len(), 4);
+        assert_eq!(game.last_move().unwrap().to_string(), "e7e5");
+        assert!(game.jump_to(5).is_err());
+
+        // replaying the stored move keeps the rest of the game
+        game.play_str("Nf3").unwrap();
+        assert_eq!(game.len(), 4);
+        game.jump_to(2).unwrap();
+
+        let mut branch = game.branch();
+        assert_eq!(branch.len(), 2);
+        branch.play_str("Bc4").unwrap();
+        assert_eq!(game.len(), 4);
+
+        // a different move replaces the rest of the game
+        game.play_str("d4").unwrap();
+        assert_eq!(game.len(), 3);
+        assert!(game.is_at_end());
+    }
+
+    #[test]
+    fn test_results() {
+        let mut game = Game::default();
+        play_all(&mut game, "f3 e5 g4 Qh4#");
+        assert!(game.result() == Some((GameResult::BlackWins, Termination::Checkmate)));
+        assert!(game.play_str("a3").is_err());
+        game.take_back();
+        assert!(game.result().is_none());
+
+        let mut game = Game::default();
+        play_all(&mut game, "Nf3 Nf6 Ng1 Ng8 Nf3 Nf6 Ng1");
+        assert!(game.result().is_none());
+        game.play_str("Ng8").unwrap();
+        assert!(game.result() == Some((GameResult::Draw, Termination::Repetition)));
+
+        let mut game = Game::default();
+        game.set_result(GameResult::WhiteWins, Termination::Resignation);
+        assert_eq!(
+            Termination::Resignation.reason(GameResult::WhiteWins),
+            "Black resigns"
+        );
+        assert!(game.play_str("e4").is_err());
+
+        let stalemate = Board::from_fen(String::from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+        assert!(Game::new(stalemate).result() == Some((GameResult::Draw, Termination::Stalemate)));
+    }
+
+    #[test]
+    fn test_pgn_conversion() {
+        let text = "[White \"a\"]\n[Result \"1-0\"]\n\n1. e4 (1. d4) e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n";
+        let pgn = PgnReader::new(text.as_bytes()).next().unwrap().unwrap();
+        let game = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(game.len(), 7);
+        assert_eq!(game.tag("White"), Some("a"));
+        assert!(game.result() == Some((GameResult::WhiteWins, Termination::Checkmate)));
+
+        let resigned = "[Result \"0-1\"]\n\n1. e4 e5 0-1\n";
+        let pgn = PgnReader::new(resigned.as_bytes()).next().unwrap().unwrap();
+        let game = Game::from_pgn(&pgn).unwrap();
+        assert!(game.result() == Some((GameResult::BlackWins, Termination::Resignation)));
+        let out = game.to_pgn().to_pgn();
+        assert!(out.contains("[Result \"0-1\"]"));
+        assert!(out.ends_with("1. e4 e5 0-1\n"));
+    }
+}
@@ -1,2 +1,22 @@
+pub mod attacks;
 pub mod board;
+pub mod book;
+pub mod bughouse;
+pub mod endgame;
+pub mod epd;
+pub mod eval;
+pub mod fairy;
+pub mod fog;
+pub mod game;
+pub mod pgn;
 pub mod piece;
+pub mod polyglot;
+pub mod san;
+pub mod search;
+pub mod see;
+pub mod tablebase;
+pub mod time;
+pub mod tree;
+pub mod tt;
+pub mod variant;
+pub mod zobrist;
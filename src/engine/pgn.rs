@@ -0,0 +1,746 @@
+use super::board::{Board, Move, Turn};
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+const LINE_WIDTH: usize = 80;
+
+#[derive(Clone)]
+pub struct PgnMove {
+    pub san: String,
+    pub mv: Move,
+    pub nags: Vec<u8>,
+    // comments written before the move, only used at the start of a game or variation
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    // alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub start: Board,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Move(String),
+    Nag(u8),
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+pub struct PgnReader<R: Read> {
+    reader: BufReader<R>,
+    pending: Option<String>,
+    games: usize,
+}
+
+impl PgnMove {
+    pub fn new(san: String, mv: Move) -> PgnMove {
+        PgnMove {
+            san,
+            mv,
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+}
+
+impl PgnGame {
+    pub fn new(start: Board) -> PgnGame {
+        PgnGame {
+            tags: Vec::new(),
+            start,
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    pub fn from_moves(start: Board, moves: &[Move]) -> PgnGame {
+        // moves must be legal, they are not checked again
+        let mut game = PgnGame::new(start);
+        let mut board = start;
+        for m in moves {
+            game.moves.push(PgnMove::new(board.to_san(m), *m));
+            board.play_move(*m);
+        }
+        return game;
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        return self
+            .tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str());
+    }
+
+    pub fn mainline(&self) -> Vec<Move> {
+        return self.moves.iter().map(|m| m.mv).collect();
+    }
+
+    pub fn end_position(&self) -> Board {
+        let mut board = self.start;
+        for m in &self.moves {
+            board.play_move(m.mv);
+        }
+        return board;
+    }
+
+    pub fn to_pgn(&self) -> String {
+        // export format: the Seven Tag Roster first, then movetext wrapped at 80 columns
+        let mut text = String::new();
+        for (name, default) in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or(default),
+            };
+            text.push_str(&format_tag(name, value));
+        }
+        let custom_start = self.start.to_fen() != Board::default().to_fen();
+        for (name, value) in &self.tags {
+            let skip = SEVEN_TAG_ROSTER.iter().any(|(n, _)| n == name)
+                || ((name == "SetUp" || name == "FEN") && custom_start);
+            if !skip {
+                text.push_str(&format_tag(name, value));
+            }
+        }
+        if custom_start {
+            text.push_str(&format_tag("SetUp", "1"));
+            text.push_str(&format_tag("FEN", &self.start.to_fen()));
+        }
+        text.push('\n');
+
+        let mut tokens = Vec::new();
+        line_tokens(&self.moves, self.start, &mut tokens);
+        tokens.push(self.result.clone());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+                text.push_str(&line);
+                text.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        text.push_str(&line);
+        text.push('\n');
+        return text;
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        // games are separated by an empty line
+        writer.write_all(self.to_pgn().as_bytes())?;
+        return writer.write_all(b"\n");
+    }
+}
+
+impl<R: Read> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader: BufReader::new(reader),
+            pending: None,
+            games: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<String> {
+        // archives are not always UTF-8, so bad bytes are replaced rather than fatal
+        if let Some(line) = self.pending.take() {
+            return Some(line);
+        }
+        let mut bytes = Vec::new();
+        match self.reader.read_until(b'\n', &mut bytes) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(String::from_utf8_lossy(&bytes).trim_end().to_string()),
+        }
+    }
+
+    fn read_game_text(&mut self) -> Option<(Vec<String>, String)> {
+        // tag lines, then movetext until the next tag line outside of a comment
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        let mut in_comment = false;
+        while let Some(line) = self.next_line() {
+            if line.starts_with('%') {
+                continue;
+            }
+            let trimmed = line.trim_start();
+            if !in_comment && trimmed.starts_with('[') {
+                if !movetext.trim().is_empty() {
+                    self.pending = Some(line);
+                    break;
+                }
+                tags.push(trimmed.to_string());
+                continue;
+            }
+            if tags.is_empty() && movetext.trim().is_empty() && trimmed.is_empty() {
+                continue;
+            }
+            for c in line.chars() {
+                match c {
+                    '{' if !in_comment => in_comment = true,
+                    '}' if in_comment => in_comment = false,
+                    ';' if !in_comment => break,
+                    _ => {}
+                }
+            }
+            movetext.push_str(&line);
+            movetext.push('\n');
+        }
+        if tags.is_empty() && movetext.trim().is_empty() {
+            return None;
+        }
+        return Some((tags, movetext));
+    }
+}
+
+impl<R: Read> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Result<PgnGame, String>> {
+        // one game at a time, a broken game is returned as an error and the next one read
+        let (tag_lines, movetext) = self.read_game_text()?;
+        self.games += 1;
+        let game = self.games;
+        return Some(
+            parse_game(&tag_lines, &movetext).map_err(|e| format!("game {}: {}", game, e)),
+        );
+    }
+}
+
+pub(crate) fn format_tag(name: &str, value: &str) -> String {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    return format!("[{} \"{}\"]\n", name, value);
+}
+
+fn comment_tokens(comment: &str, tokens: &mut Vec<String>) {
+    // one token per word, so that long comments wrap like the moves do
+    let comment = comment.replace('}', ")");
+    let words: Vec<&str> = comment.split_whitespace().collect();
+    if words.is_empty() {
+        tokens.push(String::from("{}"));
+        return;
+    }
+    for (i, word) in words.iter().enumerate() {
+        let mut token = word.to_string();
+        if i == 0 {
+            token.insert(0, '{');
+        }
+        if i == words.len() - 1 {
+            token.push('}');
+        }
+        tokens.push(token);
+    }
+}
+
+fn line_tokens(moves: &[PgnMove], start: Board, tokens: &mut Vec<String>) {
+    // black moves get a number like 4... at the start of a line and after comments or variations
+    let mut board = start;
+    let mut need_number = true;
+    for m in moves {
+        for comment in &m.comments_before {
+            comment_tokens(comment, tokens);
+            need_number = true;
+        }
+        if board.to_move == Turn::White {
+            tokens.push(format!("{}.", board.move_number));
+        } else if need_number {
+            tokens.push(format!("{}...", board.move_number));
+        }
+        tokens.push(m.san.clone());
+        need_number = false;
+        for nag in &m.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for comment in &m.comments {
+            comment_tokens(comment, tokens);
+            need_number = true;
+        }
+        for variation in &m.variations {
+            let mut inner = Vec::new();
+            line_tokens(variation, board, &mut inner);
+            if inner.is_empty() {
+                continue;
+            }
+            inner[0].insert(0, '(');
+            inner.last_mut().unwrap().push(')');
+            tokens.append(&mut inner);
+            need_number = true;
+        }
+        board.play_move(m.mv);
+    }
+}
+
+pub(crate) fn parse_tags(line: &str, tags: &mut Vec<(String, String)>) -> Result<(), String> {
+    // [Name "Value"], where the value may contain \" and \\
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c != '[' {
+            return Err(format!("malformed tag '{}'", line));
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == '"' {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if name.is_empty() || chars.next() != Some('"') {
+            return Err(format!("malformed tag '{}'", line));
+        }
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('\\') => match chars.next() {
+                    Some(c) => value.push(c),
+                    None => return Err(format!("malformed tag '{}'", line)),
+                },
+                Some('"') => break,
+                Some(c) => value.push(c),
+                None => return Err(format!("unterminated tag value in '{}'", line)),
+            }
+        }
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.next() != Some(']') {
+            return Err(format!("malformed tag '{}'", line));
+        }
+        tags.push((name, value));
+    }
+    return Ok(());
+}
+
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+fn word_tokens(word: &str, tokens: &mut Vec<Token>) -> Result<(), String> {
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        tokens.push(Token::Result(word.to_string()));
+        return Ok(());
+    }
+    // move numbers like 12. or 12... may be glued to the move
+    let mut word = word;
+    if !word.starts_with("0-0") {
+        let digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+        if digits.len() < word.len() && digits.starts_with('.') {
+            word = digits.trim_start_matches('.');
+        }
+    }
+    let word = word.trim_start_matches('.');
+    if word.is_empty() {
+        return Ok(());
+    }
+    let san = word.trim_end_matches(['!', '?']);
+    if san.is_empty() {
+        return Err(format!("unexpected '{}'", word));
+    }
+    tokens.push(Token::Move(san.to_string()));
+    let suffix = &word[san.len()..];
+    if !suffix.is_empty() {
+        match suffix_nag(suffix) {
+            Some(nag) => tokens.push(Token::Nag(nag)),
+            None => return Err(format!("unknown annotation '{}'", suffix)),
+        }
+    }
+    return Ok(());
+}
+
+fn tokenize(movetext: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = movetext.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut comment = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => comment.push(c),
+                        None => return Err(String::from("unterminated comment")),
+                    }
+                }
+                tokens.push(Token::Comment(
+                    comment.split_whitespace().collect::<Vec<&str>>().join(" "),
+                ));
+            }
+            ';' => {
+                let mut comment = String::new();
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                    comment.push(c);
+                }
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+                match digits.parse::<u8>() {
+                    Ok(nag) => tokens.push(Token::Nag(nag)),
+                    Err(_) => return Err(format!("invalid NAG '${}'", digits)),
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();$".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                word_tokens(&word, &mut tokens)?;
+            }
+        }
+    }
+    return Ok(tokens);
+}
+
+struct MoveParser {
+    tokens: Vec<Token>,
+    index: usize,
+    result: Option<String>,
+}
+
+impl MoveParser {
+    fn parse_line(
+        &mut self,
+        start: Board,
+        ply: usize,
+        depth: usize,
+    ) -> Result<Vec<PgnMove>, String> {
+        // a line of moves from start, up to the closing bracket of a variation
+        let mut moves: Vec<PgnMove> = Vec::new();
+        let mut board = start;
+        let mut before = start;
+        let mut comments = Vec::new();
+        while self.index < self.tokens.len() {
+            let token = self.tokens[self.index].clone();
+            self.index += 1;
+            match token {
+                Token::Comment(text) => match moves.last_mut() {
+                    Some(m) => m.comments.push(text),
+                    None => comments.push(text),
+                },
+                Token::Nag(nag) => match moves.last_mut() {
+                    Some(m) => m.nags.push(nag),
+                    None => return Err(String::from("NAG before the first move")),
+                },
+                Token::Open => {
+                    if moves.is_empty() {
+                        return Err(String::from("variation before the first move"));
+                    }
+                    let variation = self.parse_line(before, ply + moves.len() - 1, depth + 1)?;
+                    if !variation.is_empty() {
+                        moves.last_mut().unwrap().variations.push(variation);
+                    }
+                }
+                Token::Close => {
+                    if depth == 0 {
+                        return Err(String::from("unmatched ')'"));
+                    }
+                    return Ok(moves);
+                }
+                Token::Move(san) => {
+                    let ply = ply + moves.len() + 1;
+                    let mv = match board.parse_san(&san) {
+                        Ok(m) => m,
+                        Err(e) => return Err(format!("ply {}: {}", ply, e)),
+                    };
+                    let mut pgn_move = PgnMove::new(board.to_san(&mv), mv);
+                    pgn_move.comments_before = std::mem::take(&mut comments);
+                    moves.push(pgn_move);
+                    before = board;
+                    board.play_move(mv);
+                }
+                Token::Result(result) => {
+                    // results inside variations are tolerated and ignored
+                    if depth == 0 {
+                        self.result = Some(result);
+                        return Ok(moves);
+                    }
+                }
+            }
+        }
+        if depth > 0 {
+            return Err(String::from("unterminated variation"));
+        }
+        return Ok(moves);
+    }
+}
+
+fn parse_game(tag_lines: &[String], movetext: &str) -> Result<PgnGame, String> {
+    let mut tags = Vec::new();
+    for line in tag_lines {
+        parse_tags(line, &mut tags)?;
+    }
+    let mut game = PgnGame {
+        tags,
+        start: Board::default(),
+        moves: Vec::new(),
+        result: String::from("*"),
+    };
+    if let Some(fen) = game.tag("FEN") {
+        game.start = Board::parse_fen(fen)?;
+    }
+    let mut parser = MoveParser {
+        tokens: tokenize(movetext)?,
+        index: 0,
+        result: None,
+    };
+    game.moves = parser.parse_line(game.start, 0, 0)?;
+    game.result = match parser.result {
+        Some(r) => r,
+        None => game.tag("Result").unwrap_or("*").to_string(),
+    };
+    return Ok(game);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const GAMES: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[White "Anderssen"]
+[Black "Kieseritzky"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. f4 exf4 3. Bc4 $1 Qh4+ 4. Kf1 b5?! (4... d6 {quieter}
+(4... Nf6)) 5.Bxb5 Nf6 ; rest of the line
+6. Nf3 1-0
+
+[Event "Broken"]
+[Result "*"]
+
+1. e4 e5 2. Ke3 *
+
+[Event "From FEN"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+[Result "1-0"]
+
+1. O-O-O Kf7 2. Rd7+ 1-0
+"#;
+
+    #[test]
+    fn test_read_games() {
+        let games: Vec<Result<PgnGame, String>> = PgnReader::new(GAMES.as_bytes()).collect();
+        assert_eq!(games.len(), 3);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.tag("White"), Some("Anderssen"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.moves.len(), 11);
+        assert_eq!(game.moves[0].comments_before, vec!["Opening comment"]);
+        assert_eq!(game.moves[4].san, "Bc4");
+        assert_eq!(game.moves[4].nags, vec![1]);
+        assert_eq!(game.moves[5].san, "Qh4+");
+        assert_eq!(game.moves[7].nags, vec![6]);
+        assert_eq!(game.moves[9].comments, vec!["rest of the line"]);
+
+        let variations = &game.moves[7].variations;
+        assert_eq!(variations.len(), 1);
+        assert_eq!(variations[0][0].san, "d6");
+        assert_eq!(variations[0][0].comments, vec!["quieter"]);
+        assert_eq!(variations[0][0].variations[0][0].san, "Nf6");
+        assert_eq!(
+            game.end_position().to_fen(),
+            "rnb1kb1r/p1pp1ppp/5n2/1B6/4Pp1q/5N2/PPPP2PP/RNBQ1K1R b kq - 2 6"
+        );
+
+        let error = games[1].as_ref().err().unwrap();
+        assert!(error.starts_with("game 2: ply 3:"), "{}", error);
+
+        let game = games[2].as_ref().unwrap();
+        assert_eq!(game.start.to_fen(), "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+        assert_eq!(game.moves[0].mv.to_string(), "e1c1");
+        assert_eq!(game.moves[2].san, "Rd7+");
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut tags = Vec::new();
+        parse_tags(r#"[White "a \\ b"] [Black "c"]"#, &mut tags).unwrap();
+        assert_eq!(tags[0], (String::from("White"), String::from("a \\ b")));
+        assert_eq!(tags[1], (String::from("Black"), String::from("c")));
+        assert!(parse_tags(r#"[White "open"#, &mut tags).is_err());
+        assert!(parse_tags("[White]", &mut tags).is_err());
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("1.e4 e5!? 2. Nf3 $14 1/2-1/2").unwrap();
+        assert!(
+            tokens
+                == vec![
+                    Token::Move(String::from("e4")),
+                    Token::Move(String::from("e5")),
+                    Token::Nag(5),
+                    Token::Move(String::from("Nf3")),
+                    Token::Nag(14),
+                    Token::Result(String::from("1/2-1/2")),
+                ]
+        );
+        assert!(tokenize("0-0-0 1... O-O").unwrap().len() == 2);
+        assert!(tokenize("e4 {never closed").is_err());
+    }
+
+    #[test]
+    fn test_malformed_games_are_skipped() {
+        let text = "[Event \"x\"\n\n1. e4 *\n\n[Event \"ok\"]\n\n1. e4 (1. d4 d5 *\n\n[Event \"last\"]\n\n1. d4 { [not a tag] } d5 *\n";
+        let games: Vec<Result<PgnGame, String>> = PgnReader::new(text.as_bytes()).collect();
+        assert_eq!(games.len(), 3);
+        assert!(games[0].as_ref().err().unwrap().contains("game 1"));
+        assert!(games[1]
+            .as_ref()
+            .err()
+            .unwrap()
+            .contains("unterminated variation"));
+        let game = games[2].as_ref().unwrap();
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.moves[0].comments, vec!["[not a tag]"]);
+    }
+
+    #[test]
+    fn test_latin1_and_no_tags() {
+        let mut bytes = b"[White \"M\xfcller\"]\n\n1. e4 *\n".to_vec();
+        bytes.extend_from_slice(b"\n");
+        let game = PgnReader::new(&bytes[..]).next().unwrap().unwrap();
+        assert!(game.tag("White").unwrap().starts_with('M'));
+        let game = PgnReader::new("1. d4 d5 2. c4".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(game.mainline().len(), 3);
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn test_write_game() {
+        let board = Board::default();
+        let moves: Vec<Move> = ["e2e4", "e7e5", "g1f3"]
+            .iter()
+            .scan(board, |b, lan| {
+                let m = b.parse_move(lan).unwrap();
+                b.play_move(m);
+                Some(m)
+            })
+            .collect();
+        let mut game = PgnGame::from_moves(board, &moves);
+        game.set_tag("White", "A \"quoted\" name");
+        game.set_tag("Annotator", "me");
+        game.moves[1].comments.push(String::from("a reply"));
+        game.moves[2].nags.push(1);
+        let mut before = board;
+        before.play_move(moves[0]);
+        before.play_move(moves[1]);
+        let bc4 = before.parse_san("Bc4").unwrap();
+        game.moves[2]
+            .variations
+            .push(vec![PgnMove::new(String::from("Bc4"), bc4)]);
+        game.result = String::from("1/2-1/2");
+        assert_eq!(
+            game.to_pgn(),
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n\
+             [White \"A \\\"quoted\\\" name\"]\n[Black \"?\"]\n[Result \"1/2-1/2\"]\n\
+             [Annotator \"me\"]\n\n1. e4 e5 {a reply} 2. Nf3 $1 (2. Bc4) 1/2-1/2\n"
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let games: Vec<PgnGame> = PgnReader::new(GAMES.as_bytes())
+            .filter_map(|g| g.ok())
+            .collect();
+        let mut out = Vec::new();
+        for game in &games {
+            game.write(&mut out).unwrap();
+        }
+        let text = String::from_utf8(out).unwrap();
+        assert!(text
+            .replace('\n', " ")
+            .contains("4. Kf1 b5 $6 (4... d6 {quieter} (4... Nf6)) 5. Bxb5"));
+        assert!(text.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n"));
+        let again: Vec<PgnGame> = PgnReader::new(text.as_bytes())
+            .filter_map(|g| g.ok())
+            .collect();
+        assert_eq!(again.len(), 2);
+        for (a, b) in games.iter().zip(again.iter()) {
+            assert_eq!(a.to_pgn(), b.to_pgn());
+        }
+    }
+
+    #[test]
+    fn test_line_wrapping() {
+        let mut game = PgnGame::new(Board::default());
+        let mut board = Board::default();
+        for _ in 0..40 {
+            let m = board.generate_moves()[0];
+            game.moves.push(PgnMove::new(board.to_san(&m), m));
+            board.play_move(m);
+        }
+        game.moves[3].comments.push("word ".repeat(30));
+        let text = game.to_pgn();
+        assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(text.lines().count() > 10);
+        assert!(text.trim_end().ends_with('*'));
+    }
+}
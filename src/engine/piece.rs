@@ -40,6 +40,13 @@ pub enum Piece {
 }
 
 impl PieceColor {
+    pub fn opposite(&self) -> PieceColor {
+        match *self {
+            PieceColor::White => PieceColor::Black,
+            PieceColor::Black => PieceColor::White,
+            PieceColor::None => PieceColor::None,
+        }
+    }
     pub fn as_string(&self) -> String {
         match *self {
             PieceColor::White => "White",
@@ -73,10 +80,28 @@ impl PieceObj {
         if start == end {
             return false;
         }
-        if (self.as_type() == PieceType::Pawn || self.as_type() == PieceType::King)
-            && self.as_color() == PieceColor::Black
-        {
-            // pawn + king moves can depend on color
+        if self.as_type() == PieceType::King {
+            // only pass on the castling rights of this king's color, as if it were white
+            let rights = match self.as_color() {
+                PieceColor::Black => [
+                    can_castle[2].to_ascii_uppercase(),
+                    can_castle[3].to_ascii_uppercase(),
+                    '-',
+                    '-',
+                ],
+                _ => [can_castle[0], can_castle[1], '-', '-'],
+            };
+            if self.as_color() == PieceColor::Black {
+                return self.as_type().valid_move(
+                    PieceObj::flip(start),
+                    PieceObj::flip(end),
+                    rights,
+                );
+            }
+            return self.as_type().valid_move(start, end, rights);
+        }
+        if self.as_type() == PieceType::Pawn && self.as_color() == PieceColor::Black {
+            // pawn moves depend on color
             return self.as_type().valid_move(
                 PieceObj::flip(start),
                 PieceObj::flip(end),
@@ -145,6 +170,23 @@ impl Piece {
             },
         }
     }
+    pub fn from_type(ptype: PieceType, pcolor: PieceColor) -> Piece {
+        match (ptype, pcolor) {
+            (PieceType::King, PieceColor::White) => Piece::WKing,
+            (PieceType::Queen, PieceColor::White) => Piece::WQueen,
+            (PieceType::Rook, PieceColor::White) => Piece::WRook,
+            (PieceType::Knight, PieceColor::White) => Piece::WKnight,
+            (PieceType::Bishop, PieceColor::White) => Piece::WBishop,
+            (PieceType::Pawn, PieceColor::White) => Piece::WPawn,
+            (PieceType::King, PieceColor::Black) => Piece::BKing,
+            (PieceType::Queen, PieceColor::Black) => Piece::BQueen,
+            (PieceType::Rook, PieceColor::Black) => Piece::BRook,
+            (PieceType::Knight, PieceColor::Black) => Piece::BKnight,
+            (PieceType::Bishop, PieceColor::Black) => Piece::BBishop,
+            (PieceType::Pawn, PieceColor::Black) => Piece::BPawn,
+            _ => Piece::Blank,
+        }
+    }
     pub fn from_char(c: char) -> Piece {
         match c {
             'K' => Piece::WKing,
@@ -238,27 +280,14 @@ impl PieceType {
             end: (usize, usize),
             can_castle: [char; 4],
         ) -> bool {
+            // Castling: the king has to be on its home square, and can_castle
+            // only holds the rights of the king's own color (see PieceObj::valid_move)
             return (((start.0 as i8 - end.0 as i8).abs() <= 1)
                 && ((start.1 as i8 - end.1 as i8).abs() <= 1))
-                // Castle
-                // gross
-                || (start.0 == end.0
-                    && (start.1 as i8 - end.1 as i8).abs() == 2
-                    && (
-                        (start.0 == 0
-                        && (
-                            (can_castle[0] == 'K' && end.1 == 6)
-                            || (can_castle[1] == 'Q' && end.1 == 2)
-                            )
-                        )
-                        || (start.0 == 7
-                            && (
-                                (can_castle[2] == 'k' && end.1 == 6)
-                                || (can_castle[3] == 'q' && end.1 == 2)
-                            )
-                        )
-                    )
-                );
+                || (start == (7, 4)
+                    && end.0 == 7
+                    && ((can_castle[0] == 'K' && end.1 == 6)
+                        || (can_castle[1] == 'Q' && end.1 == 2)));
         }
         fn queen_valid_move(start: (usize, usize), end: (usize, usize)) -> bool {
             return rook_valid_move(start, end) || bishop_valid_move(start, end);
@@ -279,7 +308,13 @@ impl PieceType {
                 && (start.0 as i8 - end.0 as i8).abs() == (start.1 as i8 - end.1 as i8).abs();
         }
         fn pawn_valid_move(start: (usize, usize), end: (usize, usize)) -> bool {
-            // temporarily ignoring captures
+            // Diagonal steps are only captures, the board checks what is on the target
+            if start.0 == 0 {
+                return false;
+            }
+            if start.0 - 1 == end.0 && (start.1 as i8 - end.1 as i8).abs() == 1 {
+                return true;
+            }
             if start.0 == 6 {
                 return start.1 == end.1 && (start.0 - 1 == end.0 || start.0 - 2 == end.0);
             }
@@ -319,153 +354,93 @@ mod tests {
     #[test]
     fn test_pawn_move() {
         let piece = Piece::BPawn;
-        assert_eq!(
-            piece.valid_move((1, 1), (1, 2), ['K', 'Q', 'k', 'q']),
-            false
-        ); // sideways pawn move
-        assert_eq!(piece.valid_move((1, 1), (2, 1), ['K', 'Q', 'k', 'q']), true); // forward pawn move
-        assert_eq!(piece.valid_move((1, 1), (3, 1), ['K', 'Q', 'k', 'q']), true); // double pawn move
+        assert!(!piece.valid_move((1, 1), (1, 2), ['K', 'Q', 'k', 'q'])); // sideways pawn move
+        assert!(piece.valid_move((1, 1), (2, 1), ['K', 'Q', 'k', 'q'])); // forward pawn move
+        assert!(piece.valid_move((1, 1), (3, 1), ['K', 'Q', 'k', 'q'])); // double pawn move
         let piece = Piece::WPawn;
-        assert_eq!(piece.valid_move((6, 1), (5, 1), ['K', 'Q', 'k', 'q']), true); // sideways pawn move
-        assert_eq!(piece.valid_move((6, 1), (4, 1), ['K', 'Q', 'k', 'q']), true); // forward pawn move
-        assert_eq!(
-            piece.valid_move((6, 1), (3, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // double pawn move
+        assert!(piece.valid_move((6, 1), (5, 1), ['K', 'Q', 'k', 'q'])); // sideways pawn move
+        assert!(piece.valid_move((6, 1), (4, 1), ['K', 'Q', 'k', 'q'])); // forward pawn move
+        assert!(!piece.valid_move((6, 1), (3, 1), ['K', 'Q', 'k', 'q'])); // double pawn move
+        assert!(piece.valid_move((6, 1), (5, 2), ['K', 'Q', 'k', 'q'])); // capture
+        assert!(!piece.valid_move((6, 1), (7, 2), ['K', 'Q', 'k', 'q'])); // backwards capture
+        assert!(Piece::BPawn.valid_move((1, 1), (2, 0), ['-'; 4])); // capture
     }
 
     #[test]
     fn test_rook_move() {
         let piece = Piece::BRook;
-        assert_eq!(piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q']), true); // sideways move
-        assert_eq!(piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q']), true); // forward move
-        assert_eq!(piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q']), true); // backwards move
-        assert_eq!(
-            piece.valid_move((7, 1), (6, 2), ['K', 'Q', 'k', 'q']),
-            false
-        ); // diagonal move
-        assert_eq!(
-            piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q']),
-            false
-        ); // knight move
+        assert!(piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q'])); // sideways move
+        assert!(piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q'])); // forward move
+        assert!(piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q'])); // backwards move
+        assert!(!piece.valid_move((7, 1), (6, 2), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q'])); // knight move
     }
 
     #[test]
     fn test_bishop_move() {
         let piece = Piece::BBishop;
-        assert_eq!(
-            piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q']),
-            false
-        ); // sideways move
-        assert_eq!(
-            piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // forward move
-        assert_eq!(
-            piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // backwards move
-        assert_eq!(piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q']), true); // diagonal move
-        assert_eq!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q']), true); // diagonal move
-        assert_eq!(
-            piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q']),
-            false
-        ); // knight move
+        assert!(!piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q'])); // sideways move
+        assert!(!piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q'])); // forward move
+        assert!(!piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q'])); // backwards move
+        assert!(piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q'])); // knight move
     }
     #[test]
     fn test_queen_move() {
         let piece = Piece::BQueen;
-        assert_eq!(piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q']), true); // sideways move
-        assert_eq!(piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q']), true); // forward move
-        assert_eq!(piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q']), true); // backwards move
-        assert_eq!(piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q']), true); // diagonal move
-        assert_eq!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q']), true); // diagonal move
-        assert_eq!(
-            piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q']),
-            false
-        ); // knight move
+        assert!(piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q'])); // sideways move
+        assert!(piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q'])); // forward move
+        assert!(piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q'])); // backwards move
+        assert!(piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((7, 1), (5, 2), ['K', 'Q', 'k', 'q'])); // knight move
     }
 
     #[test]
     fn test_knight_move() {
         let piece = Piece::BKnight;
-        assert_eq!(
-            piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q']),
-            false
-        ); // sideways move
-        assert_eq!(
-            piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // forward move
-        assert_eq!(
-            piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // backwards move
-        assert_eq!(
-            piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q']),
-            false
-        ); // diagonal move
-        assert_eq!(
-            piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q']),
-            false
-        ); // diagonal move
-        assert_eq!(piece.valid_move((5, 5), (4, 3), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (6, 3), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (6, 7), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (4, 7), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (7, 4), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (7, 6), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (3, 4), ['K', 'Q', 'k', 'q']), true); // knight move
-        assert_eq!(piece.valid_move((5, 5), (3, 6), ['K', 'Q', 'k', 'q']), true);
+        assert!(!piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q'])); // sideways move
+        assert!(!piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q'])); // forward move
+        assert!(!piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q'])); // backwards move
+        assert!(!piece.valid_move((5, 5), (6, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(piece.valid_move((5, 5), (4, 3), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (6, 3), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (6, 7), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (4, 7), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (7, 4), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (7, 6), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (3, 4), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (3, 6), ['K', 'Q', 'k', 'q']));
         // knight move
     }
     #[test]
     fn test_king_move() {
         let piece = Piece::BKing;
-        assert_eq!(
-            piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q']),
-            false
-        ); // sideways move
-        assert_eq!(
-            piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // forward move
-        assert_eq!(
-            piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q']),
-            false
-        ); // backwards move
-        assert_eq!(
-            piece.valid_move((5, 5), (7, 7), ['K', 'Q', 'k', 'q']),
-            false
-        ); // diagonal move
-        assert_eq!(
-            piece.valid_move((5, 5), (3, 7), ['K', 'Q', 'k', 'q']),
-            false
-        ); // diagonal move
-        assert_eq!(
-            piece.valid_move((5, 5), (4, 3), ['K', 'Q', 'k', 'q']),
-            false
-        ); // knight move
-        assert_eq!(
-            piece.valid_move((5, 5), (6, 3), ['K', 'Q', 'k', 'q']),
-            false
-        ); // knight move
-        assert_eq!(piece.valid_move((5, 5), (4, 5), ['K', 'Q', 'k', 'q']), true); // 1 space
-        assert_eq!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q']), true); // 1 space
-        assert_eq!(piece.valid_move((5, 5), (5, 4), ['K', 'Q', 'k', 'q']), true); // 1 space
+        assert!(!piece.valid_move((1, 1), (1, 5), ['K', 'Q', 'k', 'q'])); // sideways move
+        assert!(!piece.valid_move((1, 1), (7, 1), ['K', 'Q', 'k', 'q'])); // forward move
+        assert!(!piece.valid_move((7, 1), (1, 1), ['K', 'Q', 'k', 'q'])); // backwards move
+        assert!(!piece.valid_move((5, 5), (7, 7), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((5, 5), (3, 7), ['K', 'Q', 'k', 'q'])); // diagonal move
+        assert!(!piece.valid_move((5, 5), (4, 3), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(!piece.valid_move((5, 5), (6, 3), ['K', 'Q', 'k', 'q'])); // knight move
+        assert!(piece.valid_move((5, 5), (4, 5), ['K', 'Q', 'k', 'q'])); // 1 space
+        assert!(piece.valid_move((5, 5), (4, 4), ['K', 'Q', 'k', 'q'])); // 1 space
+        assert!(piece.valid_move((5, 5), (5, 4), ['K', 'Q', 'k', 'q'])); // 1 space
                                                                                   // Castling
-        assert_eq!(piece.valid_move((0, 4), (0, 2), ['K', 'Q', 'k', 'q']), true);
-        assert_eq!(
-            piece.valid_move((0, 4), (0, 2), ['K', 'Q', 'k', '-']),
-            false
-        );
-        assert_eq!(
-            Piece::BKing.valid_move((7, 4), (7, 2), ['-', '-', 'k', 'q']),
-            false
-        );
-        assert_eq!(
-            Piece::WKing.valid_move((7, 4), (7, 2), ['K', 'Q', 'k', 'q']),
-            true
-        );
+        assert!(piece.valid_move((0, 4), (0, 2), ['K', 'Q', 'k', 'q']));
+        assert!(!piece.valid_move((0, 4), (0, 2), ['K', 'Q', 'k', '-']));
+        assert!(!Piece::BKing.valid_move((7, 4), (7, 2), ['-', '-', 'k', 'q']));
+        assert!(Piece::WKing.valid_move((7, 4), (7, 2), ['K', 'Q', 'k', 'q']));
+        assert!(!Piece::WKing.valid_move((7, 4), (7, 6), ['-', 'Q', 'k', 'q']));
+        assert!(!Piece::BKing.valid_move((0, 4), (0, 6), ['K', 'Q', '-', 'q']));
+        assert!(!Piece::WKing.valid_move((6, 4), (6, 6), ['K', 'Q', 'k', 'q']));
+    }
+
+    #[test]
+    fn test_from_type() {
+        assert!(Piece::from_type(PieceType::Knight, PieceColor::Black) == Piece::BKnight);
+        assert!(Piece::from_type(PieceType::Queen, PieceColor::None) == Piece::Blank);
     }
 }
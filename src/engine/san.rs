@@ -0,0 +1,291 @@
+use super::board::{Board, Move};
+use super::piece::{Piece, PieceType};
+use super::variant::{Standard, Variant};
+
+fn piece_from_letter(c: char) -> Option<PieceType> {
+    // SAN piece letters are always uppercase, a lowercase b is the b-file
+    match c {
+        'K' => Some(PieceType::King),
+        'Q' => Some(PieceType::Queen),
+        'R' => Some(PieceType::Rook),
+        'B' => Some(PieceType::Bishop),
+        'N' => Some(PieceType::Knight),
+        _ => None,
+    }
+}
+
+fn promotion_from_letter(c: char, allowed: &[PieceType]) -> Option<PieceType> {
+    // promotions are often written in lowercase, as in e8q
+    return piece_from_letter(c.to_ascii_uppercase()).filter(|t| allowed.contains(t));
+}
+
+impl Board {
+    pub fn to_san(&self, m: &Move) -> String {
+        return self.to_san_for(&Standard, m);
+    }
+
+    pub fn to_san_for<V: Variant + ?Sized>(&self, variant: &V, m: &Move) -> String {
+        // Standard Algebraic Notation for a legal move in this position
+        let mut san = String::new();
+        if m.is_drop() {
+            san.push_str(&m.to_string());
+        } else if self.is_castling(m) {
+            if m.end.1 > m.start.1 {
+                san.push_str("O-O");
+            } else {
+                san.push_str("O-O-O");
+            }
+        } else {
+            let ptype = m.piece.as_type();
+            let capture = self.is_capture(m);
+            if ptype == PieceType::Pawn {
+                if capture {
+                    san.push_str(&Board::square_name(m.start)[..1]);
+                }
+            } else {
+                san.push(ptype.as_upper());
+                san.push_str(&self.disambiguation(variant, m));
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&Board::square_name(m.end));
+            if m.promotion != Piece::Blank {
+                san.push('=');
+                san.push(m.promotion.as_type().as_upper());
+            }
+        }
+        let mut after = *self;
+        variant.play_move(&mut after, *m);
+        if variant.in_check(&after, after.to_move.as_color()) {
+            if variant.legal_moves(&after).is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+        return san;
+    }
+
+    fn disambiguation<V: Variant + ?Sized>(&self, variant: &V, m: &Move) -> String {
+        // as little as needed: the file if that is enough, else the rank, else both
+        let others: Vec<Move> = variant
+            .legal_moves(self)
+            .into_iter()
+            .filter(|o| o.piece == m.piece && o.end == m.end && o.start != m.start)
+            .collect();
+        if others.is_empty() {
+            return String::new();
+        }
+        let square = Board::square_name(m.start);
+        if others.iter().all(|o| o.start.1 != m.start.1) {
+            return square[..1].to_string();
+        }
+        if others.iter().all(|o| o.start.0 != m.start.0) {
+            return square[1..].to_string();
+        }
+        return square;
+    }
+
+    pub fn parse_san(&self, san: &str) -> Result<Move, String> {
+        return self.parse_san_for(&Standard, san);
+    }
+
+    pub fn parse_san_for<V: Variant + ?Sized>(
+        &self,
+        variant: &V,
+        san: &str,
+    ) -> Result<Move, String> {
+        // Accepts the usual variants: 0-0, missing x, e8Q or e8(Q), and trailing +#!? marks
+        let mut text = san.trim();
+        text = text.trim_end_matches(|c| "+#!?".contains(c));
+        text = text.strip_suffix("e.p.").unwrap_or(text).trim_end();
+        if text.is_empty() {
+            return Err(String::from("empty move"));
+        }
+
+        let castle = text.replace('0', "O").to_ascii_uppercase();
+        if castle == "O-O" || castle == "O-O-O" {
+            let kingside = castle == "O-O";
+            return match variant
+                .legal_moves(self)
+                .into_iter()
+                .find(|m| self.is_castling(m) && (m.end.1 > m.start.1) == kingside)
+            {
+                Some(m) => Ok(m),
+                None => Err(format!("{} is not legal here", castle)),
+            };
+        }
+
+        if let Some((letter, square)) = text.split_once('@') {
+            // Crazyhouse drops, N@f3, with the P optional for pawns
+            let letter = letter.chars().next().unwrap_or('P');
+            return variant.parse_move(self, &format!("{}@{}", letter, square));
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+        let ptype = match piece_from_letter(chars[0]) {
+            Some(t) => {
+                chars.remove(0);
+                t
+            }
+            None => PieceType::Pawn,
+        };
+
+        let mut promotion = None;
+        if ptype == PieceType::Pawn {
+            if chars.last() == Some(&')') {
+                chars.pop();
+            }
+            if chars.len() > 2 {
+                let allowed = variant.promotion_types();
+                if let Some(t) = promotion_from_letter(*chars.last().unwrap(), allowed) {
+                    if !chars[chars.len() - 2].is_ascii_lowercase() {
+                        promotion = Some(t);
+                        chars.pop();
+                        if matches!(chars.last(), Some('=') | Some('(') | Some('/')) {
+                            chars.pop();
+                        }
+                    }
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err(format!("'{}' has no target square", san));
+        }
+        let target_str: String = chars[chars.len() - 2..].iter().collect();
+        let target = match Board::parse_square(&target_str) {
+            Some(s) => s,
+            None => return Err(format!("'{}' has no target square", san)),
+        };
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(*c as usize - 'a' as usize),
+                '1'..='8' => from_rank = Some('8' as usize - *c as usize),
+                'x' | 'X' | ':' | '-' => {}
+                _ => return Err(format!("unexpected '{}' in {}", c, san)),
+            }
+        }
+
+        let candidates: Vec<Move> = variant
+            .legal_moves(self)
+            .into_iter()
+            .filter(|m| {
+                m.piece.as_type() == ptype
+                    && m.end == target
+                    && from_file.is_none_or(|f| m.start.1 == f)
+                    && from_rank.is_none_or(|r| m.start.0 == r)
+                    && match promotion {
+                        // a promotion letter only goes with a move that promotes
+                        Some(t) => m.promotion.as_type() == t,
+                        None => {
+                            m.promotion == Piece::Blank
+                                || m.promotion.as_type() == PieceType::Queen
+                        }
+                    }
+            })
+            .collect();
+        match candidates.len() {
+            0 => Err(format!("{} is not a legal move", san.trim())),
+            1 => Ok(candidates[0]),
+            _ => Err(format!("{} is ambiguous", san.trim())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn san(fen: &str, lan: &str) -> String {
+        let board = Board::from_fen(String::from(fen));
+        return board.to_san(&board.parse_move(lan).unwrap());
+    }
+
+    #[test]
+    fn test_to_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e2e4"), "e4");
+        assert_eq!(san(start, "g1f3"), "Nf3");
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castles, "e1g1"), "O-O");
+        assert_eq!(san(castles, "e1c1"), "O-O-O");
+        // Rxa8 is check
+        assert_eq!(san(castles, "a1a8"), "Rxa8+");
+        let pawns = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(san(pawns, "e5d6"), "exd6");
+        let promotion = "8/1P2k3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(promotion, "b7b8q"), "b8=Q");
+        assert_eq!(san(promotion, "b7b8n"), "b8=N");
+        let mate = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        assert_eq!(san(mate, "a1a8"), "Ra8#");
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        // knights on b8 and f6 can both reach d7
+        let board = "rn2k3/8/5n2/8/8/8/8/4K3 b - - 0 1";
+        assert_eq!(san(board, "b8d7"), "Nbd7");
+        // rooks on a1 and a5 share a file
+        let rooks = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        assert_eq!(san(rooks, "a1a3"), "R1a3");
+        // three queens need the full square
+        let queens = "8/8/1k6/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(san(queens, "h4e1"), "Qh4e1");
+        assert_eq!(san(queens, "e4e1"), "Qee1");
+        assert_eq!(san(queens, "h1e1"), "Q1e1");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let board = Board::from_fen(String::from("rn2k3/8/5n2/8/8/8/8/4K3 b - - 0 1"));
+        assert_eq!(board.parse_san("Nbd7").unwrap().to_string(), "b8d7");
+        assert_eq!(board.parse_san("Nfd7").unwrap().to_string(), "f6d7");
+        assert!(board.parse_san("Nd7").err().unwrap().contains("ambiguous"));
+        assert!(board.parse_san("Nd6").is_err());
+        assert!(board.parse_san("").is_err());
+        assert!(board.parse_san("Z").is_err());
+
+        let board = Board::default();
+        assert_eq!(board.parse_san("e4").unwrap().to_string(), "e2e4");
+        assert_eq!(board.parse_san("Nf3+").unwrap().to_string(), "g1f3");
+        assert_eq!(board.parse_san("Nf3!?").unwrap().to_string(), "g1f3");
+        assert!(board.parse_san("e5").is_err());
+        assert!(board.parse_san("e4=Q").is_err());
+        assert!(board.parse_san("e4Q").is_err());
+    }
+
+    #[test]
+    fn test_parse_san_variants() {
+        let castles = Board::from_fen(String::from("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1"));
+        assert_eq!(castles.parse_san("0-0").unwrap().to_string(), "e1g1");
+        assert_eq!(castles.parse_san("O-O-O").unwrap().to_string(), "e1c1");
+        // missing x
+        assert_eq!(castles.parse_san("Ra8").unwrap().to_string(), "a1a8");
+        let pawns = Board::from_fen(String::from("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"));
+        assert_eq!(pawns.parse_san("exd6 e.p.").unwrap().to_string(), "e5d6");
+        assert_eq!(pawns.parse_san("ed6").unwrap().to_string(), "e5d6");
+        let promotion = Board::from_fen(String::from("2r1k3/1P6/8/8/8/8/8/4K3 w - - 0 1"));
+        assert_eq!(promotion.parse_san("b8=Q").unwrap().to_string(), "b7b8q");
+        assert_eq!(promotion.parse_san("b8Q").unwrap().to_string(), "b7b8q");
+        assert_eq!(promotion.parse_san("b8(N)").unwrap().to_string(), "b7b8n");
+        assert_eq!(promotion.parse_san("bxc8=R+").unwrap().to_string(), "b7c8r");
+        assert_eq!(promotion.parse_san("bc8q").unwrap().to_string(), "b7c8q");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // every legal move survives to_san and parse_san
+        let board = Board::from_fen(String::from(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        ));
+        for m in board.generate_moves() {
+            let san = board.to_san(&m);
+            assert!(board.parse_san(&san).unwrap() == m, "{}", san);
+        }
+    }
+}
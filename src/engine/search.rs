@@ -0,0 +1,862 @@
+use super::board::{Board, Move};
+use super::eval;
+use super::game::GameResult;
+use super::piece::{Piece, PieceType};
+use super::tablebase::{Probe, Tablebases, Wdl};
+use super::time::{SearchLimits, StopSignal, TimeManager};
+use super::tt::{self, Bound, TranspositionTable, TtEntry};
+use super::variant::{Standard, Variant};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+pub const MATE: i32 = 30000;
+pub const INFINITY: i32 = 32000;
+pub const MAX_PLY: usize = 64;
+// mates up to this many plies away, which tablebases can know of, still read as mates
+const MAX_MATE_PLIES: i32 = 1000;
+const MATE_BOUND: i32 = MATE - MAX_MATE_PLIES;
+// wins from tables without distances get a band of their own, far below the mates and
+// far above anything the evaluation gives
+const TABLEBASE_WIN: i32 = 20000;
+const TABLEBASE_BOUND: i32 = TABLEBASE_WIN - MAX_MATE_PLIES;
+
+// how many nodes go by between looking at the clock
+const CHECK_INTERVAL: u64 = 256;
+// stack for threads that run a search, deep recursion needs more than the default
+pub const SEARCH_STACK_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Score {
+    // centipawns from the side to move's point of view
+    Cp(i32),
+    // mate in this many moves, negative if the side to move is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    pub fn from_internal(score: i32) -> Score {
+        if score.abs() >= MATE_BOUND {
+            let plies = MATE - score.abs();
+            if score > 0 {
+                return Score::Mate((plies + 1) / 2);
+            }
+            return Score::Mate(-plies / 2);
+        }
+        return Score::Cp(score);
+    }
+}
+
+pub struct SearchInfo {
+    pub depth: u32,
+    pub seldepth: u32,
+    pub multipv: usize,
+    pub score: Score,
+    pub nodes: u64,
+    pub time: u64,
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
+
+impl SearchInfo {
+    pub fn nps(&self) -> u64 {
+        return self.nodes * 1000 / self.time.max(1);
+    }
+}
+
+#[derive(Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub ponder: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SearchOptions {
+    pub hash_mb: usize,
+    pub threads: usize,
+    pub multi_pv: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            hash_mb: 16,
+            threads: 1,
+            multi_pv: 1,
+        }
+    }
+}
+
+pub struct Searcher {
+    pub options: SearchOptions,
+    tt: Arc<TranspositionTable>,
+    tablebases: Option<Arc<Tablebases>>,
+    variant: Arc<dyn Variant>,
+}
+
+impl Searcher {
+    pub fn new(options: SearchOptions) -> Searcher {
+        Searcher {
+            options,
+            tt: Arc::new(TranspositionTable::new(options.hash_mb)),
+            tablebases: None,
+            variant: Arc::new(Standard),
+        }
+    }
+
+    pub fn variant(&self) -> &dyn Variant {
+        return &*self.variant;
+    }
+
+    pub fn set_variant(&mut self, variant: Arc<dyn Variant>) {
+        // positions score differently under other rules, so the table starts over
+        self.variant = variant;
+        self.tt.clear();
+    }
+
+    pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+        self.tablebases = tablebases;
+    }
+
+    pub fn set_hash(&mut self, megabytes: usize) {
+        self.options.hash_mb = megabytes;
+        self.tt = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    pub fn clear(&self) {
+        // forget everything from the previous game
+        self.tt.clear();
+    }
+
+    pub fn search<F: FnMut(&SearchInfo)>(
+        &self,
+        board: &Board,
+        history: &[u64],
+        limits: &SearchLimits,
+        stop: StopSignal,
+        mut on_info: F,
+    ) -> SearchResult {
+        // `history` holds the hashes of the positions played before `board`
+        let tablebases = self.tablebases.as_deref().filter(|_| self.variant.uses_tablebases());
+        if let Some(result) = tablebases.and_then(|tb| self.tablebase_move(tb, board, limits)) {
+            on_info(&SearchInfo {
+                depth: 1,
+                seldepth: 1,
+                multipv: 1,
+                score: Score::from_internal(result.score),
+                nodes: 0,
+                time: 0,
+                hashfull: self.tt.hashfull(),
+                pv: result.pv.clone(),
+            });
+            return result;
+        }
+        let tm = TimeManager::with_signal(limits, board.to_move, stop);
+        let nodes = Arc::new(AtomicU64::new(0));
+        let helpers_stop = StopSignal::new();
+        let mut main = Worker::new(&self.tt, &nodes, history, Some(tm), helpers_stop.clone());
+        main.tablebases = tablebases;
+        main.variant = &*self.variant;
+
+        return thread::scope(|scope| {
+            for i in 1..self.options.threads.max(1) {
+                // Lazy SMP: helpers search the same tree and only share the table
+                let mut helper = Worker::new(&self.tt, &nodes, history, None, helpers_stop.clone());
+                helper.tablebases = tablebases;
+                helper.variant = &*self.variant;
+                let board = *board;
+                thread::Builder::new()
+                    .stack_size(SEARCH_STACK_SIZE)
+                    .spawn_scoped(scope, move || {
+                        helper.iterate(&board, 1 + (i as u32 % 2), 1, &mut |_: &SearchInfo| {});
+                    })
+                    .expect("failed to start a search thread");
+            }
+            let result = main.iterate(board, 1, self.options.multi_pv.max(1), &mut on_info);
+            helpers_stop.stop();
+            result
+        });
+    }
+
+    fn tablebase_move(
+        &self,
+        tablebases: &Tablebases,
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> Option<SearchResult> {
+        // a position in the tables needs no search, except when analysing
+        if limits.infinite {
+            return None;
+        }
+        let (m, probe) = tablebases.best_move(board)?;
+        return Some(SearchResult {
+            best_move: Some(m),
+            ponder: None,
+            score: tablebase_score(&probe, 0),
+            depth: 1,
+            nodes: 0,
+            pv: vec![m],
+        });
+    }
+}
+
+fn tablebase_score(probe: &Probe, ply: usize) -> i32 {
+    // a known distance is a mate, a WDL table only says the position is won
+    let score = match probe.dtm {
+        Some(dtm) => MATE - (ply as i32 + dtm as i32).min(MAX_MATE_PLIES),
+        None => TABLEBASE_WIN - ply as i32,
+    };
+    return match probe.wdl {
+        Wdl::Win => score,
+        Wdl::Loss => -score,
+        Wdl::Draw => 0,
+    };
+}
+
+fn result_score(result: GameResult, board: &Board, ply: usize) -> i32 {
+    // a game the rules have ended scores like a mate for the side to move
+    if result == GameResult::Draw {
+        return 0;
+    }
+    if result == GameResult::win_for(board.to_move) {
+        return MATE - ply as i32;
+    }
+    return -MATE + ply as i32;
+}
+
+struct Worker<'a> {
+    tt: &'a TranspositionTable,
+    variant: &'a dyn Variant,
+    tablebases: Option<&'a Tablebases>,
+    nodes: &'a AtomicU64,
+    local_nodes: u64,
+    history: Vec<u64>,
+    // only the main thread has a clock, helpers run until told to stop
+    tm: Option<TimeManager>,
+    helpers_stop: StopSignal,
+    stopped: bool,
+    seldepth: u32,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history_scores: [[i32; 64]; 64],
+    pv: Vec<[Option<Move>; MAX_PLY]>,
+    pv_len: [usize; MAX_PLY],
+}
+
+impl<'a> Worker<'a> {
+    fn new(
+        tt: &'a TranspositionTable,
+        nodes: &'a AtomicU64,
+        history: &[u64],
+        tm: Option<TimeManager>,
+        helpers_stop: StopSignal,
+    ) -> Worker<'a> {
+        Worker {
+            tt,
+            variant: &Standard,
+            tablebases: None,
+            nodes,
+            local_nodes: 0,
+            history: history.to_vec(),
+            tm,
+            helpers_stop,
+            stopped: false,
+            seldepth: 0,
+            killers: [[None; 2]; MAX_PLY],
+            history_scores: [[0; 64]; 64],
+            pv: vec![[None; MAX_PLY]; MAX_PLY],
+            pv_len: [0; MAX_PLY],
+        }
+    }
+
+    fn total_nodes(&self) -> u64 {
+        return self.nodes.load(Ordering::Relaxed);
+    }
+
+    fn elapsed(&self) -> u64 {
+        match &self.tm {
+            Some(tm) => tm.elapsed_millis(),
+            None => 0,
+        }
+    }
+
+    fn check_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        self.local_nodes += 1;
+        let total = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.local_nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.stopped = match &self.tm {
+                Some(tm) => tm.should_stop(total),
+                None => self.helpers_stop.is_stopped(),
+            };
+        }
+        return self.stopped;
+    }
+
+    fn iterate<F: FnMut(&SearchInfo)>(
+        &mut self,
+        board: &Board,
+        first_depth: u32,
+        multi_pv: usize,
+        on_info: &mut F,
+    ) -> SearchResult {
+        let root_moves = match self.variant.result(board) {
+            Some(_) => Vec::new(),
+            None => self.variant.legal_moves(board),
+        };
+        let mut result = SearchResult {
+            best_move: root_moves.first().cloned(),
+            ponder: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        if root_moves.is_empty() {
+            return result;
+        }
+        let multi_pv = multi_pv.min(root_moves.len());
+        let mut depth = first_depth;
+        loop {
+            let keep_going = match &self.tm {
+                Some(tm) => tm.should_start_iteration(depth, self.total_nodes()),
+                None => !self.helpers_stop.is_stopped(),
+            };
+            if !keep_going || depth as usize >= MAX_PLY - 1 {
+                break;
+            }
+            self.seldepth = 0;
+            let mut excluded: Vec<Move> = Vec::new();
+            let mut lines: Vec<(i32, Vec<Move>)> = Vec::new();
+            for _ in 0..multi_pv {
+                let score = self.root_search(board, depth, &excluded);
+                if self.stopped {
+                    break;
+                }
+                let line: Vec<Move> = self.pv[0][..self.pv_len[0]]
+                    .iter()
+                    .filter_map(|m| *m)
+                    .collect();
+                if let Some(first) = line.first() {
+                    excluded.push(*first);
+                }
+                lines.push((score, line));
+            }
+            if lines.is_empty() {
+                // stopped before even the first line finished, keep the last iteration
+                break;
+            }
+            for (i, (score, line)) in lines.iter().enumerate() {
+                on_info(&SearchInfo {
+                    depth,
+                    seldepth: self.seldepth.max(depth),
+                    multipv: i + 1,
+                    score: Score::from_internal(*score),
+                    nodes: self.total_nodes(),
+                    time: self.elapsed(),
+                    hashfull: self.tt.hashfull(),
+                    pv: line.clone(),
+                });
+            }
+            let (score, line) = lines.swap_remove(0);
+            let changed = line.first() != result.best_move.as_ref() || result.depth == 0;
+            if let Some(tm) = &mut self.tm {
+                tm.update_stability(changed && result.depth > 0);
+            }
+            if !line.is_empty() {
+                result.best_move = line.first().cloned();
+                result.ponder = line.get(1).cloned();
+                result.pv = line;
+            }
+            result.score = score;
+            result.depth = depth;
+            if self.stopped {
+                break;
+            }
+            // a mate within the horizon won't get any better
+            let infinite = self.tm.as_ref().is_none_or(|tm| tm.is_infinite());
+            if score.abs() >= MATE - depth as i32 && !infinite {
+                break;
+            }
+            depth += 1;
+        }
+        result.nodes = self.total_nodes();
+        return result;
+    }
+
+    fn root_search(&mut self, board: &Board, depth: u32, excluded: &[Move]) -> i32 {
+        self.pv_len[0] = 0;
+        let mut moves: Vec<Move> = self
+            .variant
+            .legal_moves(board)
+            .into_iter()
+            .filter(|m| !excluded.contains(m))
+            .collect();
+        let tt_move = self.tt.probe(board.hash()).map_or(0, |e| e.mv);
+        self.order_moves(board, &mut moves, tt_move, 0);
+        let mut alpha = -INFINITY;
+        let beta = INFINITY;
+        self.history.push(board.hash());
+        for (i, m) in moves.iter().enumerate() {
+            let mut next = *board;
+            self.variant.play_move(&mut next, *m);
+            let score = if i == 0 {
+                -self.negamax(&next, depth - 1, -beta, -alpha, 1)
+            } else {
+                let mut s = -self.negamax(&next, depth - 1, -alpha - 1, -alpha, 1);
+                if s > alpha && !self.stopped {
+                    s = -self.negamax(&next, depth - 1, -beta, -alpha, 1);
+                }
+                s
+            };
+            if self.stopped {
+                break;
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(0, *m);
+            }
+        }
+        self.history.pop();
+        if !self.stopped && excluded.is_empty() && self.pv_len[0] > 0 {
+            if let Some(best) = self.pv[0][0] {
+                self.tt.store(
+                    board.hash(),
+                    TtEntry {
+                        mv: tt::encode_move(&best),
+                        score: alpha,
+                        depth,
+                        bound: Bound::Exact,
+                    },
+                );
+            }
+        }
+        return alpha;
+    }
+
+    fn update_pv(&mut self, ply: usize, m: Move) {
+        self.pv[ply][0] = Some(m);
+        let child_len = if ply + 1 < MAX_PLY { self.pv_len[ply + 1] } else { 0 };
+        for i in 0..child_len {
+            if i + 1 < MAX_PLY {
+                self.pv[ply][i + 1] = self.pv[ply + 1][i];
+            }
+        }
+        self.pv_len[ply] = (child_len + 1).min(MAX_PLY);
+    }
+
+    fn is_repetition(&self, board: &Board) -> bool {
+        // positions can only repeat since the last capture or pawn move
+        let hash = board.hash();
+        return self
+            .history
+            .iter()
+            .rev()
+            .take(board.half_move as usize)
+            .any(|h| *h == hash);
+    }
+
+    fn negamax(&mut self, board: &Board, depth: u32, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_len[ply] = 0;
+        if self.check_stop() {
+            return 0;
+        }
+        if let Some(result) = self.variant.result(board) {
+            return result_score(result, board, ply);
+        }
+        if board.half_move >= 100
+            || self.variant.is_insufficient_material(board)
+            || self.is_repetition(board)
+        {
+            return 0;
+        }
+        if ply >= MAX_PLY - 1 {
+            return self.variant.evaluate(board);
+        }
+        if let Some(tb) = self.tablebases {
+            let pieces = board.squares.iter().flatten().filter(|p| **p != Piece::Blank);
+            if pieces.count() <= tb.max_pieces() {
+                if let Some(probe) = tb.probe(board) {
+                    return tablebase_score(&probe, ply);
+                }
+            }
+        }
+        let in_check = self.variant.in_check(board, board.to_move.as_color());
+        // look one move further when in check, so mates aren't cut off at the horizon
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiesce(board, alpha, beta, ply);
+        }
+
+        let hash = board.hash();
+        let mut tt_move = 0;
+        if let Some(entry) = self.tt.probe(hash) {
+            tt_move = entry.mv;
+            if entry.depth >= depth {
+                let score = score_from_tt(entry.score, ply);
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff && beta - alpha == 1 {
+                    return score;
+                }
+            }
+        }
+
+        let mut moves = self.variant.legal_moves(board);
+        if moves.is_empty() {
+            return result_score(self.variant.no_moves_result(board), board, ply);
+        }
+        self.order_moves(board, &mut moves, tt_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.history.push(hash);
+        for (i, m) in moves.iter().enumerate() {
+            let mut next = *board;
+            self.variant.play_move(&mut next, *m);
+            let score = if i == 0 {
+                -self.negamax(&next, depth - 1, -beta, -alpha, ply + 1)
+            } else {
+                let mut s = -self.negamax(&next, depth - 1, -alpha - 1, -alpha, ply + 1);
+                if s > alpha && s < beta && !self.stopped {
+                    s = -self.negamax(&next, depth - 1, -beta, -alpha, ply + 1);
+                }
+                s
+            };
+            if self.stopped {
+                self.history.pop();
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(*m);
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, *m);
+            }
+            if alpha >= beta {
+                if !board.is_capture(m) {
+                    self.store_killer(ply, *m);
+                    self.history_scores[square_index(m.start)][square_index(m.end)] +=
+                        (depth * depth) as i32;
+                }
+                break;
+            }
+        }
+        self.history.pop();
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(
+            hash,
+            TtEntry {
+                mv: best_move.map_or(0, |m| tt::encode_move(&m)),
+                score: score_to_tt(best_score, ply),
+                depth,
+                bound,
+            },
+        );
+        return best_score;
+    }
+
+    fn quiesce(&mut self, board: &Board, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        // only captures and promotions, so the static evaluation is taken in a quiet position
+        self.pv_len[ply] = 0;
+        if self.check_stop() {
+            return 0;
+        }
+        self.seldepth = self.seldepth.max(ply as u32);
+        if let Some(result) = self.variant.result(board) {
+            return result_score(result, board, ply);
+        }
+        // a side that has to capture can't stand pat, so those captures are all searched
+        let mut forced = None;
+        if self.variant.forced_captures() {
+            let moves = self.variant.legal_moves(board);
+            if moves.is_empty() {
+                return result_score(self.variant.no_moves_result(board), board, ply);
+            }
+            forced = Some(moves).filter(|moves| moves.iter().any(|m| board.is_capture(m)));
+        }
+        let stand_pat = self.variant.evaluate(board);
+        if ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        if forced.is_none() {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            if stand_pat > alpha {
+                alpha = stand_pat;
+            }
+        }
+        // captures that lose material in the exchange can't raise alpha, but only under the
+        // normal rules: a variant may win by a check or a king walk whatever the material
+        let prune = forced.is_none() && self.variant.standard_rules();
+        let mut moves: Vec<Move> = match forced {
+            Some(moves) => moves,
+            None => self
+                .variant
+                .legal_moves(board)
+                .into_iter()
+                .filter(|m| board.is_capture(m) || m.promotion != Piece::Blank)
+                .collect(),
+        };
+        self.order_moves(board, &mut moves, 0, ply);
+        for m in moves {
+            if prune && !board.see_ge(&m, 0) {
+                continue;
+            }
+            let mut next = *board;
+            self.variant.play_move(&mut next, m);
+            let score = -self.quiesce(&next, -beta, -alpha, ply + 1);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+                self.update_pv(ply, m);
+            }
+        }
+        return alpha;
+    }
+
+    fn store_killer(&mut self, ply: usize, m: Move) {
+        if self.killers[ply][0] != Some(m) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(m);
+        }
+    }
+
+    fn order_moves(&self, board: &Board, moves: &mut [Move], tt_move: u16, ply: usize) {
+        let killers = self.killers[ply.min(MAX_PLY - 1)];
+        moves.sort_by_cached_key(|m| {
+            if tt::move_matches(tt_move, m) {
+                return -3_000_000;
+            }
+            if board.is_capture(m) || m.promotion != Piece::Blank {
+                // captures that lose the exchange go after the quiet moves
+                if self.variant.standard_rules() && !board.see_ge(m, 0) {
+                    return 1_000_000 - board.see(m);
+                }
+                // most valuable victim, least valuable attacker
+                let victim = board.squares[m.end.0][m.end.1].as_type();
+                let victim = if victim == PieceType::Blank {
+                    PieceType::Pawn
+                } else {
+                    victim
+                };
+                return -2_000_000 - eval::piece_value(victim) * 10
+                    + eval::piece_value(m.piece.as_type()) / 10
+                    - eval::piece_value(m.promotion.as_type()) * 10;
+            }
+            if killers[0] == Some(*m) {
+                return -1_000_001;
+            }
+            if killers[1] == Some(*m) {
+                return -1_000_000;
+            }
+            return -self.history_scores[square_index(m.start)][square_index(m.end)];
+        });
+    }
+}
+
+fn square_index(square: (usize, usize)) -> usize {
+    return square.0 * 8 + square.1;
+}
+
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    // mate and tablebase scores are stored relative to the node, not the root
+    if score >= TABLEBASE_BOUND {
+        return score + ply as i32;
+    }
+    if score <= -TABLEBASE_BOUND {
+        return score - ply as i32;
+    }
+    return score;
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= TABLEBASE_BOUND {
+        return score - ply as i32;
+    }
+    if score <= -TABLEBASE_BOUND {
+        return score + ply as i32;
+    }
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::engine::variant::{Antichess, KingOfTheHill, ThreeCheck};
+
+    fn best_move(fen: &str, depth: u32) -> SearchResult {
+        let searcher = Searcher::new(SearchOptions::default());
+        let board = Board::from_fen(String::from(fen));
+        return searcher.search(
+            &board,
+            &[],
+            &SearchLimits::depth(depth),
+            StopSignal::new(),
+            |_| {},
+        );
+    }
+
+    #[test]
+    fn test_mate_in_one() {
+        let result = best_move("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(result.best_move.unwrap().to_string(), "a1a8");
+        assert_eq!(Score::from_internal(result.score), Score::Mate(1));
+    }
+
+    #[test]
+    fn test_getting_mated() {
+        let result = best_move("7k/8/8/8/8/8/5q2/6rK w - - 0 1", 2);
+        assert!(result.best_move.is_none());
+        let result = best_move("7k/8/6K1/8/8/8/8/R7 b - - 0 1", 3);
+        assert_eq!(Score::from_internal(result.score), Score::Mate(-1));
+    }
+
+    #[test]
+    fn test_wins_material() {
+        // the queen is hanging
+        let result = best_move("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 2);
+        assert_eq!(result.best_move.unwrap().to_string(), "d2d5");
+    }
+
+    #[test]
+    fn test_multi_pv_and_info() {
+        let mut searcher = Searcher::new(SearchOptions::default());
+        searcher.options.multi_pv = 3;
+        let mut lines = Vec::new();
+        searcher.search(
+            &Board::default(),
+            &[],
+            &SearchLimits::depth(2),
+            StopSignal::new(),
+            |info| lines.push((info.depth, info.multipv, info.pv[0])),
+        );
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[5].0, 2);
+        assert_eq!(lines[5].1, 3);
+        assert!(lines[3].2 != lines[4].2 && lines[4].2 != lines[5].2);
+    }
+
+    #[test]
+    fn test_stop_and_threads() {
+        let mut searcher = Searcher::new(SearchOptions::default());
+        searcher.options.threads = 2;
+        let stop = StopSignal::new();
+        stop.stop();
+        let result = searcher.search(
+            &Board::default(),
+            &[],
+            &SearchLimits::infinite(),
+            stop,
+            |_| {},
+        );
+        // even a search that is stopped straight away returns a legal move
+        assert!(result.best_move.is_some());
+        let result = searcher.search(
+            &Board::default(),
+            &[],
+            &SearchLimits::depth(2),
+            StopSignal::new(),
+            |_| {},
+        );
+        assert_eq!(result.depth, 2);
+    }
+
+    #[test]
+    fn test_repetition() {
+        let tt = TranspositionTable::new(1);
+        let nodes = AtomicU64::new(0);
+        let mut board = Board::default();
+        let mut history = Vec::new();
+        for m in ["g1f3", "g8f6", "f3g1", "f6g8"].iter() {
+            history.push(board.hash());
+            board.make_move_from_string(m.to_string());
+        }
+        let worker = Worker::new(&tt, &nodes, &history, None, StopSignal::new());
+        assert!(worker.is_repetition(&board));
+        // the halfmove clock limits how far back a repetition can be
+        board.half_move = 3;
+        assert!(!worker.is_repetition(&board));
+    }
+
+    #[test]
+    fn test_score() {
+        assert_eq!(Score::from_internal(MATE - 1), Score::Mate(1));
+        assert_eq!(Score::from_internal(MATE - 3), Score::Mate(2));
+        assert_eq!(Score::from_internal(-MATE + 2), Score::Mate(-1));
+        assert_eq!(Score::from_internal(35), Score::Cp(35));
+    }
+
+    #[test]
+    fn test_variants() {
+        // the king walks into the centre rather than taking the rook
+        let mut searcher = Searcher::new(SearchOptions::default());
+        searcher.set_variant(Arc::new(KingOfTheHill));
+        let board = Board::from_fen(String::from("k7/8/8/8/8/2r1K3/8/8 w - - 0 1"));
+        let result = searcher.search(&board, &[], &SearchLimits::depth(2), StopSignal::new(), |_| {});
+        assert!(["e3e4", "e3d4"].contains(&result.best_move.unwrap().to_string().as_str()));
+        assert_eq!(Score::from_internal(result.score), Score::Mate(1));
+        // with two checks given, a third one wins straight away
+        searcher.set_variant(Arc::new(ThreeCheck));
+        let board = Board::from_fen(String::from("4k3/8/8/8/8/8/1q6/4K2R w - - 0 1 +2+0"));
+        let result = searcher.search(&board, &[], &SearchLimits::depth(2), StopSignal::new(), |_| {});
+        assert_eq!(result.best_move.unwrap().to_string(), "h1h8");
+        // in Antichess the rook has to be given away, but not so as to stalemate black
+        searcher.set_variant(Arc::new(Antichess));
+        let board = Antichess.parse_fen("8/8/8/8/8/8/1p6/R7 w - - 0 1").unwrap();
+        let result = searcher.search(&board, &[], &SearchLimits::depth(3), StopSignal::new(), |_| {});
+        assert_eq!(result.best_move.unwrap().to_string(), "a1c1");
+        assert_eq!(Score::from_internal(result.score), Score::Mate(1));
+    }
+
+    #[test]
+    fn test_tablebases() {
+        let mut tablebases = Tablebases::new();
+        tablebases.generate("KQK").unwrap();
+        let board = Board::parse_fen("8/8/8/4k3/8/8/Q7/4K3 w - - 0 1").unwrap();
+        let dtm = tablebases.probe(&board).unwrap().dtm.unwrap() as i32;
+        let mut searcher = Searcher::new(SearchOptions::default());
+        searcher.set_tablebases(Some(Arc::new(tablebases)));
+        let result = searcher.search(&board, &[], &SearchLimits::depth(3), StopSignal::new(), |_| {});
+        assert_eq!(result.nodes, 0);
+        assert_eq!(Score::from_internal(result.score), Score::Mate((dtm + 1) / 2));
+        // the pawn has to go, and the tables score the position after that
+        let board = Board::parse_fen("8/8/8/4k3/8/8/Q2p4/4K3 w - - 0 1").unwrap();
+        let result = searcher.search(&board, &[], &SearchLimits::depth(2), StopSignal::new(), |_| {});
+        assert!(result.nodes > 0);
+        assert_eq!(result.best_move.unwrap().to_string(), "e1d2");
+        assert!(matches!(Score::from_internal(result.score), Score::Mate(n) if n > 0));
+
+        let long = Probe { wdl: Wdl::Loss, dtm: Some(100) };
+        assert_eq!(tablebase_score(&long, 2), -(MATE - 102));
+        assert_eq!(Score::from_internal(tablebase_score(&long, 2)), Score::Mate(-51));
+        let unknown = Probe { wdl: Wdl::Win, dtm: None };
+        assert_eq!(Score::from_internal(tablebase_score(&unknown, 3)), Score::Cp(TABLEBASE_WIN - 3));
+    }
+}
@@ -0,0 +1,182 @@
+use super::board::{Board, Move};
+use super::eval;
+use super::piece::{Piece, PieceColor, PieceType};
+
+// the king can take part in an exchange, but only as its last capture
+const KING_VALUE: i32 = 20000;
+
+fn value(ptype: PieceType) -> i32 {
+    if ptype == PieceType::King {
+        return KING_VALUE;
+    }
+    return eval::piece_value(ptype);
+}
+
+fn bit(square: (usize, usize)) -> u64 {
+    return 1 << (square.0 * 8 + square.1);
+}
+
+impl Board {
+    fn least_valuable_attacker(
+        &self,
+        square: (usize, usize),
+        color: PieceColor,
+        removed: u64,
+    ) -> Option<(usize, usize)> {
+        return self
+            .attackers_through(square, color, removed)
+            .into_iter()
+            .min_by_key(|s| value(self.squares[s.0][s.1].as_type()));
+    }
+
+    fn exchange_start(&self, m: &Move) -> (i32, i32, u64) {
+        // what the move wins straight away, the value of the piece left standing on the
+        // target, and the squares it empties
+        let mut removed = bit(m.start);
+        let mut captured = self.squares[m.end.0][m.end.1].as_type();
+        if m.piece.as_type() == PieceType::Pawn
+            && m.start.1 != m.end.1
+            && self.en_passant_square() == Some(m.end)
+        {
+            captured = PieceType::Pawn;
+            removed |= bit((m.start.0, m.end.1));
+        }
+        let mut gain = value(captured);
+        let mut on_square = value(m.piece.as_type());
+        if m.promotion != Piece::Blank {
+            gain += value(m.promotion.as_type()) - value(PieceType::Pawn);
+            on_square = value(m.promotion.as_type());
+        }
+        return (gain, on_square, removed);
+    }
+
+    pub fn see(&self, m: &Move) -> i32 {
+        // the material the side moving ends up with once every capture on the target
+        // square has been played out, each side choosing its least valuable attacker and
+        // free to stop when going on would lose; pins and checks are not considered
+        if self.is_castling(m) {
+            return 0;
+        }
+        let (first, mut on_square, mut removed) = self.exchange_start(m);
+        let mut gains = vec![first];
+        let mut color = m.piece.as_color().opposite();
+        while let Some(from) = self.least_valuable_attacker(m.end, color, removed) {
+            let attacker = self.squares[from.0][from.1].as_type();
+            removed |= bit(from);
+            // a king can't take a defended piece
+            if attacker == PieceType::King
+                && self
+                    .least_valuable_attacker(m.end, color.opposite(), removed)
+                    .is_some()
+            {
+                break;
+            }
+            gains.push(on_square - gains[gains.len() - 1]);
+            on_square = value(attacker);
+            color = color.opposite();
+        }
+        // each side only makes a capture if it is better than standing aside
+        while gains.len() > 1 {
+            let last = gains.pop().unwrap();
+            let previous = gains.len() - 1;
+            gains[previous] = -(-gains[previous]).max(last);
+        }
+        return gains[0];
+    }
+
+    pub fn see_ge(&self, m: &Move, threshold: i32) -> bool {
+        // whether see(m) >= threshold, stopping as soon as the answer is known
+        if self.is_castling(m) {
+            return threshold <= 0;
+        }
+        let (first, on_square, mut removed) = self.exchange_start(m);
+        // `swap` is how far the side that just captured is ahead of the threshold, from the
+        // point of view of whoever has to beat it next
+        let mut swap = first - threshold;
+        if swap < 0 {
+            return false;
+        }
+        swap = on_square - swap;
+        if swap <= 0 {
+            return true;
+        }
+        let mut color = m.piece.as_color();
+        let mut result = true;
+        loop {
+            color = color.opposite();
+            let from = match self.least_valuable_attacker(m.end, color, removed) {
+                Some(from) => from,
+                None => break,
+            };
+            let attacker = self.squares[from.0][from.1].as_type();
+            removed |= bit(from);
+            result = !result;
+            if attacker == PieceType::King {
+                // the king's capture only stands if nothing can take it back
+                let defended = self
+                    .least_valuable_attacker(m.end, color.opposite(), removed)
+                    .is_some();
+                return result != defended;
+            }
+            swap = value(attacker) - swap;
+            if swap < result as i32 {
+                break;
+            }
+        }
+        return result;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn see(fen: &str, text: &str) -> i32 {
+        let board = Board::from_fen(String::from(fen));
+        let m = board.parse_move(text).unwrap();
+        return board.see(&m);
+    }
+
+    #[test]
+    fn test_see() {
+        // an undefended pawn, then one defended by a pawn
+        assert_eq!(see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"), 100);
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4R1K1 w - - 0 1", "e1e5"), -400);
+        // the queen behind the rook joins in once the rook has gone
+        assert_eq!(
+            see("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1", "d3e5"),
+            -220
+        );
+        assert_eq!(see("4k3/8/5n2/8/3p4/8/1B6/Q3K3 w - - 0 1", "b2d4"), 100);
+        // the king may take, but not a defended piece
+        assert_eq!(see("4k3/3r4/8/8/8/8/8/3RK3 w - - 0 1", "d1d7"), 0);
+        assert_eq!(see("4k3/3r4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7"), 500);
+        // en passant, and a quiet move onto an attacked square
+        assert_eq!(see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see("4k3/8/8/8/2p5/8/8/4KN2 w - - 0 1", "f1d2"), 0);
+        assert_eq!(see("4k3/8/8/8/2p5/8/8/4KN2 w - - 0 1", "f1e3"), 0);
+        assert_eq!(see("4k3/8/8/5p2/8/8/8/4KN2 w - - 0 1", "f1e3"), 0);
+        assert_eq!(see("4k3/8/8/3p4/8/8/8/4KN2 w - - 0 1", "f1e3"), 0);
+        assert_eq!(see("4k3/8/8/8/3p4/8/8/4KN2 w - - 0 1", "f1e3"), -320);
+    }
+
+    #[test]
+    fn test_see_threshold() {
+        let fens = [
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1",
+            "4k3/3r4/8/8/8/8/8/3RK3 w - - 0 1",
+            "4k3/3r4/4K3/8/8/8/8/8 w - - 0 1",
+        ];
+        for fen in fens.iter() {
+            let board = Board::from_fen(String::from(*fen));
+            for m in board.generate_moves() {
+                let value = board.see(&m);
+                assert!(board.see_ge(&m, value));
+                assert!(!board.see_ge(&m, value + 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,1210 @@
+use super::board::{Board, Move, Turn, PROMOTION_TYPES};
+use super::piece::{Piece, PieceColor, PieceType};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// kings included, bigger tables take too long and too much memory to build
+pub const MAX_PIECES: usize = 5;
+
+pub const FILE_EXTENSION: &str = "rctb";
+const MAGIC: &[u8; 4] = b"RCTB";
+const VERSION: u8 = 1;
+
+// one byte per position in a DTM table: a draw, a position that can't happen, or
+// 2 + n for a result n plies from mate, a win for the side to move when n is odd
+const DRAW: u8 = 0;
+const ILLEGAL: u8 = 1;
+const FIRST_RESULT: u8 = 2;
+const MAX_PLIES: usize = 252;
+// only used while generating
+const UNKNOWN: u8 = 255;
+
+// WDL tables pack four positions into a byte
+const WDL_DRAW: u8 = 0;
+const WDL_ILLEGAL: u8 = 1;
+const WDL_WIN: u8 = 2;
+const WDL_LOSS: u8 = 3;
+
+const NO_PAIR: u16 = u16::MAX;
+
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const KING_STEPS: [(i8, i8); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const ROOK_STEPS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_STEPS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Wdl {
+    Loss,
+    Draw,
+    Win,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Probe {
+    // for the side to move
+    pub wdl: Wdl,
+    // plies to mate, None for draws and for tables that only keep WDL
+    pub dtm: Option<u32>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Metric {
+    Dtm,
+    Wdl,
+}
+
+pub struct Table {
+    layout: Layout,
+    metric: Metric,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct Tablebases {
+    tables: HashMap<String, Table>,
+}
+
+// squares are row * 8 + col, the same order as Board::squares
+type Cells = [Piece; 64];
+
+struct Layout {
+    name: String,
+    // white king, black king, then the other pieces with identical ones next to each other
+    kinds: Vec<Piece>,
+    groups: Vec<(usize, usize)>,
+    // all eight reflections without pawns, only the left-right mirror with them
+    transforms: Vec<[u8; 64]>,
+    pair_index: Vec<u16>,
+    pairs: Vec<(u8, u8)>,
+    size: usize,
+}
+
+fn order(ptype: PieceType) -> usize {
+    return match ptype {
+        PieceType::Queen => 0,
+        PieceType::Rook => 1,
+        PieceType::Bishop => 2,
+        PieceType::Knight => 3,
+        _ => 4,
+    };
+}
+
+fn side_name(pieces: &[PieceType]) -> String {
+    let mut sorted = pieces.to_vec();
+    sorted.sort_by_key(|t| order(*t));
+    let mut name = String::from("K");
+    name.extend(sorted.iter().map(|t| t.as_upper()));
+    return name;
+}
+
+fn stronger(a: &[PieceType], b: &[PieceType]) -> bool {
+    // more pieces first, then the more valuable ones
+    let key = |s: &[PieceType]| {
+        let mut ranks: Vec<usize> = s.iter().map(|t| order(*t)).collect();
+        ranks.sort_unstable();
+        (s.len(), ranks.iter().map(|r| 5 - r).collect::<Vec<usize>>())
+    };
+    return key(a) >= key(b);
+}
+
+fn material_name(white: &[PieceType], black: &[PieceType]) -> (String, bool) {
+    // the name of the table that holds this material, and whether colours are swapped in it
+    if stronger(white, black) {
+        return (format!("{}v{}", side_name(white), side_name(black)), false);
+    }
+    return (format!("{}v{}", side_name(black), side_name(white)), true);
+}
+
+fn is_drawn(white: &[PieceType], black: &[PieceType]) -> bool {
+    // bare kings, or a single minor piece that can't mate, need no table
+    return match (white, black) {
+        ([], []) => true,
+        ([t], []) | ([], [t]) => *t == PieceType::Bishop || *t == PieceType::Knight,
+        _ => false,
+    };
+}
+
+fn parse_side(text: &str) -> Result<Vec<PieceType>, String> {
+    let mut pieces = Vec::new();
+    for c in text.chars() {
+        let ptype = match c.to_ascii_uppercase() {
+            'Q' => PieceType::Queen,
+            'R' => PieceType::Rook,
+            'B' => PieceType::Bishop,
+            'N' => PieceType::Knight,
+            'P' => PieceType::Pawn,
+            _ => return Err(format!("unknown piece '{}'", c)),
+        };
+        pieces.push(ptype);
+    }
+    return Ok(pieces);
+}
+
+pub fn canonical_name(name: &str) -> Result<String, String> {
+    // accepts KQK, KQvK or kqk, and puts the stronger side first
+    let upper: String = name
+        .to_ascii_uppercase()
+        .chars()
+        .filter(|c| *c != 'V')
+        .collect();
+    if !upper.starts_with('K') {
+        return Err(format!("'{}' does not start with a king", name));
+    }
+    let split = match upper[1..].find('K') {
+        Some(i) => i + 1,
+        None => return Err(format!("'{}' needs two kings", name)),
+    };
+    let white = parse_side(&upper[1..split])?;
+    let black = parse_side(&upper[split + 1..])?;
+    if white.len() + black.len() + 2 > MAX_PIECES {
+        return Err(format!("'{}' has more than {} pieces", name, MAX_PIECES));
+    }
+    return Ok(material_name(&white, &black).0);
+}
+
+fn transform(square: u8, symmetry: usize) -> u8 {
+    let (mut row, mut col) = (square / 8, square % 8);
+    if symmetry & 4 != 0 {
+        std::mem::swap(&mut row, &mut col);
+    }
+    if symmetry & 2 != 0 {
+        row = 7 - row;
+    }
+    if symmetry & 1 != 0 {
+        col = 7 - col;
+    }
+    return row * 8 + col;
+}
+
+fn adjacent(a: u8, b: u8) -> bool {
+    let (ra, ca) = ((a / 8) as i8, (a % 8) as i8);
+    let (rb, cb) = ((b / 8) as i8, (b % 8) as i8);
+    return (ra - rb).abs() <= 1 && (ca - cb).abs() <= 1;
+}
+
+fn step(square: u8, (dr, dc): (i8, i8)) -> Option<u8> {
+    let row = (square / 8) as i8 + dr;
+    let col = (square % 8) as i8 + dc;
+    if !(0..8).contains(&row) || !(0..8).contains(&col) {
+        return None;
+    }
+    return Some((row * 8 + col) as u8);
+}
+
+fn attacks(cells: &Cells, from: u8, piece: Piece, target: u8) -> bool {
+    // whether the piece on `from` attacks `target`, cheaper than looking out from the target
+    let dr = (target / 8) as i8 - (from / 8) as i8;
+    let dc = (target % 8) as i8 - (from % 8) as i8;
+    let (straight, diagonal) = ((dr == 0) != (dc == 0), dr != 0 && dr.abs() == dc.abs());
+    let slides = match piece.as_type() {
+        PieceType::Pawn => {
+            let forward = if piece.as_color() == PieceColor::White {
+                -1
+            } else {
+                1
+            };
+            return dr == forward && dc.abs() == 1;
+        }
+        PieceType::Knight => return dr.abs() * dc.abs() == 2,
+        PieceType::King => return dr.abs().max(dc.abs()) == 1,
+        PieceType::Rook => straight,
+        PieceType::Bishop => diagonal,
+        PieceType::Queen => straight || diagonal,
+        PieceType::Blank => false,
+    };
+    if !slides {
+        return false;
+    }
+    let direction = (dr.signum(), dc.signum());
+    let mut current = step(from, direction).unwrap();
+    while current != target {
+        if cells[current as usize] != Piece::Blank {
+            return false;
+        }
+        current = step(current, direction).unwrap();
+    }
+    return true;
+}
+
+fn attacked(cells: &Cells, square: u8, by: PieceColor) -> bool {
+    return (0..64u8).any(|s| {
+        let p = cells[s as usize];
+        p != Piece::Blank && p.as_color() == by && attacks(cells, s, p, square)
+    });
+}
+
+fn king_square(cells: &Cells, color: PieceColor) -> Option<u8> {
+    let king = Piece::from_type(PieceType::King, color);
+    return cells.iter().position(|p| *p == king).map(|s| s as u8);
+}
+
+fn in_check(cells: &Cells, color: PieceColor) -> bool {
+    return match king_square(cells, color) {
+        Some(king) => attacked(cells, king, color.opposite()),
+        None => false,
+    };
+}
+
+fn piece_steps(ptype: PieceType) -> (&'static [(i8, i8)], bool) {
+    // the directions a piece moves in, and whether it slides along them
+    return match ptype {
+        PieceType::Knight => (&KNIGHT_STEPS, false),
+        PieceType::King => (&KING_STEPS, false),
+        PieceType::Rook => (&ROOK_STEPS, true),
+        PieceType::Bishop => (&BISHOP_STEPS, true),
+        _ => (&KING_STEPS, true),
+    };
+}
+
+fn legal_moves(cells: &Cells, color: PieceColor) -> Vec<(u8, u8, Piece)> {
+    // (from, to, promotion), castling never comes up in the tables and en passant can't
+    // either, as Layout::new turns down pawns on both sides
+    let mut moves = Vec::new();
+    let king = match king_square(cells, color) {
+        Some(k) => k,
+        None => return moves,
+    };
+    let mut own = Vec::new();
+    let mut enemies = Vec::new();
+    for (s, p) in cells.iter().enumerate() {
+        if *p != Piece::Blank {
+            if p.as_color() == color {
+                own.push((s as u8, *p));
+            } else {
+                enemies.push((s as u8, *p));
+            }
+        }
+    }
+    let mut targets = Vec::new();
+    for (from, piece) in own {
+        targets.clear();
+        if piece.as_type() == PieceType::Pawn {
+            let dr = if color == PieceColor::White { -1 } else { 1 };
+            if let Some(s) = step(from, (dr, 0)) {
+                if cells[s as usize] == Piece::Blank {
+                    targets.push(s);
+                    let start_row = if color == PieceColor::White { 6 } else { 1 };
+                    if from / 8 == start_row {
+                        let s2 = step(s, (dr, 0)).unwrap();
+                        if cells[s2 as usize] == Piece::Blank {
+                            targets.push(s2);
+                        }
+                    }
+                }
+            }
+            for dc in [-1, 1] {
+                if let Some(s) = step(from, (dr, dc)) {
+                    let p = cells[s as usize];
+                    if p != Piece::Blank && p.as_color() != color {
+                        targets.push(s);
+                    }
+                }
+            }
+        } else {
+            let (steps, slides) = piece_steps(piece.as_type());
+            for d in steps {
+                let mut current = from;
+                while let Some(s) = step(current, *d) {
+                    let p = cells[s as usize];
+                    if p != Piece::Blank && p.as_color() == color {
+                        break;
+                    }
+                    targets.push(s);
+                    if p != Piece::Blank || !slides {
+                        break;
+                    }
+                    current = s;
+                }
+            }
+        }
+        for to in targets.iter().copied() {
+            let mut next = *cells;
+            next[to as usize] = piece;
+            next[from as usize] = Piece::Blank;
+            let king = if from == king { to } else { king };
+            if enemies
+                .iter()
+                .any(|(s, p)| *s != to && attacks(&next, *s, *p, king))
+            {
+                continue;
+            }
+            if piece.as_type() == PieceType::Pawn && (to / 8 == 0 || to / 8 == 7) {
+                for t in PROMOTION_TYPES.iter() {
+                    moves.push((from, to, Piece::from_type(*t, color)));
+                }
+            } else {
+                moves.push((from, to, Piece::Blank));
+            }
+        }
+    }
+    return moves;
+}
+
+fn play(cells: &Cells, (from, to, promotion): (u8, u8, Piece)) -> Cells {
+    let mut next = *cells;
+    next[to as usize] = if promotion == Piece::Blank {
+        cells[from as usize]
+    } else {
+        promotion
+    };
+    next[from as usize] = Piece::Blank;
+    return next;
+}
+
+fn negate(value: u8) -> u8 {
+    // the value of a position as seen from the position before it
+    if value < FIRST_RESULT {
+        return value;
+    }
+    return value + 1;
+}
+
+fn better(a: u8, b: u8) -> bool {
+    // whether a is better than b for the side to move, faster wins and slower losses
+    let rank = |v: u8| -> i32 {
+        match v {
+            DRAW => 0,
+            ILLEGAL | UNKNOWN => i32::MIN,
+            v if (v - FIRST_RESULT) % 2 == 1 => 1000 - v as i32,
+            v => -1000 + v as i32,
+        }
+    };
+    return rank(a) > rank(b);
+}
+
+fn material_of(cells: &Cells) -> (Vec<PieceType>, Vec<PieceType>) {
+    let mut white = Vec::new();
+    let mut black = Vec::new();
+    for p in cells.iter() {
+        match (p.as_type(), p.as_color()) {
+            (PieceType::King, _) | (PieceType::Blank, _) => {}
+            (t, PieceColor::White) => white.push(t),
+            (t, _) => black.push(t),
+        }
+    }
+    return (white, black);
+}
+
+fn flip_colors(cells: &Cells) -> Cells {
+    // swaps the colours and mirrors the ranks, which leaves the position the same
+    let mut flipped = [Piece::Blank; 64];
+    for (s, p) in cells.iter().enumerate() {
+        if *p != Piece::Blank {
+            let mirrored = (7 - s / 8) * 8 + s % 8;
+            flipped[mirrored] = Piece::from_type(p.as_type(), p.as_color().opposite());
+        }
+    }
+    return flipped;
+}
+
+impl Layout {
+    fn new(name: &str) -> Result<Layout, String> {
+        let name = canonical_name(name)?;
+        let split = name.find('v').unwrap();
+        let white = parse_side(&name[1..split])?;
+        let black = parse_side(&name[split + 2..])?;
+        // a double push could then be taken en passant, which the index has no room for
+        if white.contains(&PieceType::Pawn) && black.contains(&PieceType::Pawn) {
+            return Err(format!(
+                "{} has pawns on both sides, which the tables don't support",
+                name
+            ));
+        }
+        let mut kinds = vec![Piece::WKing, Piece::BKing];
+        kinds.extend(
+            white
+                .iter()
+                .map(|t| Piece::from_type(*t, PieceColor::White)),
+        );
+        kinds.extend(
+            black
+                .iter()
+                .map(|t| Piece::from_type(*t, PieceColor::Black)),
+        );
+        let mut groups = Vec::new();
+        let mut start = 2;
+        for i in 3..=kinds.len() {
+            if i == kinds.len() || kinds[i] != kinds[start] {
+                if i - start > 1 {
+                    groups.push((start, i));
+                }
+                start = i;
+            }
+        }
+        let pawns = kinds.iter().any(|p| p.as_type() == PieceType::Pawn);
+        let mut pair_index = vec![NO_PAIR; 64 * 64];
+        let mut pairs = Vec::new();
+        for wk in 0..64u8 {
+            let (rank, file) = (7 - wk / 8, wk % 8);
+            let allowed = if pawns {
+                file < 4
+            } else {
+                file < 4 && rank <= file
+            };
+            if !allowed {
+                continue;
+            }
+            for bk in 0..64u8 {
+                if adjacent(wk, bk) {
+                    continue;
+                }
+                // with the white king on the a1-h8 diagonal, the black one goes on or below it
+                if !pawns && rank == file && 7 - bk / 8 > bk % 8 {
+                    continue;
+                }
+                pair_index[wk as usize * 64 + bk as usize] = pairs.len() as u16;
+                pairs.push((wk, bk));
+            }
+        }
+        let size = pairs.len() * 64usize.pow(kinds.len() as u32 - 2) * 2;
+        let symmetries = if pawns { 2 } else { 8 };
+        let mut transforms = vec![[0u8; 64]; symmetries];
+        for (symmetry, table) in transforms.iter_mut().enumerate() {
+            for (square, t) in table.iter_mut().enumerate() {
+                *t = transform(square as u8, symmetry);
+            }
+        }
+        return Ok(Layout {
+            name,
+            kinds,
+            groups,
+            transforms,
+            pair_index,
+            pairs,
+            size,
+        });
+    }
+
+    fn index(&self, squares: &[u8], white_to_move: bool) -> usize {
+        // the smallest index over the symmetric positions, so each position has just one
+        let n = self.kinds.len();
+        let mut best = usize::MAX;
+        for table in &self.transforms {
+            let mut sq = [0u8; MAX_PIECES];
+            for i in 0..n {
+                sq[i] = table[squares[i] as usize];
+            }
+            let pair = self.pair_index[sq[0] as usize * 64 + sq[1] as usize];
+            if pair == NO_PAIR {
+                continue;
+            }
+            for (start, end) in &self.groups {
+                sq[*start..*end].sort_unstable();
+            }
+            let mut index = pair as usize;
+            for s in &sq[2..n] {
+                index = index * 64 + *s as usize;
+            }
+            index = index * 2 + if white_to_move { 0 } else { 1 };
+            best = best.min(index);
+        }
+        return best;
+    }
+
+    fn decode(&self, index: usize) -> ([u8; MAX_PIECES], bool) {
+        let mut sq = [0u8; MAX_PIECES];
+        let white_to_move = index.is_multiple_of(2);
+        let mut rest = index / 2;
+        for i in (2..self.kinds.len()).rev() {
+            sq[i] = (rest % 64) as u8;
+            rest /= 64;
+        }
+        let (wk, bk) = self.pairs[rest];
+        sq[0] = wk;
+        sq[1] = bk;
+        return (sq, white_to_move);
+    }
+
+    fn cells(&self, squares: &[u8]) -> Option<Cells> {
+        // None when two pieces share a square
+        let mut cells = [Piece::Blank; 64];
+        for (i, s) in squares[..self.kinds.len()].iter().enumerate() {
+            if cells[*s as usize] != Piece::Blank {
+                return None;
+            }
+            cells[*s as usize] = self.kinds[i];
+        }
+        return Some(cells);
+    }
+
+    fn squares(&self, cells: &Cells) -> [u8; MAX_PIECES] {
+        // the cells must hold exactly this table's material
+        let mut sq = [0u8; MAX_PIECES];
+        let mut used = [false; 64];
+        for (i, kind) in self.kinds.iter().enumerate() {
+            let s = (0..64)
+                .find(|s| !used[*s] && cells[*s] == *kind)
+                .expect("material does not match the table");
+            used[s] = true;
+            sq[i] = s as u8;
+        }
+        return sq;
+    }
+
+    fn is_legal(&self, squares: &[u8], white_to_move: bool) -> Option<Cells> {
+        let cells = self.cells(squares)?;
+        for (i, kind) in self.kinds.iter().enumerate() {
+            let row = squares[i] / 8;
+            if kind.as_type() == PieceType::Pawn && (row == 0 || row == 7) {
+                return None;
+            }
+        }
+        let waiting = if white_to_move {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        if in_check(&cells, waiting) {
+            return None;
+        }
+        return Some(cells);
+    }
+}
+
+impl Table {
+    pub fn name(&self) -> &str {
+        return &self.layout.name;
+    }
+
+    pub fn metric(&self) -> Metric {
+        return self.metric;
+    }
+
+    pub fn pieces(&self) -> usize {
+        return self.layout.kinds.len();
+    }
+
+    pub fn len(&self) -> usize {
+        // positions, including the ones that can't happen
+        return self.layout.size;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.layout.size == 0;
+    }
+
+    fn value(&self, index: usize) -> u8 {
+        // WDL tables have no distances, a mate in one ply or in none stands in for them
+        return match self.metric {
+            Metric::Dtm => self.data[index],
+            Metric::Wdl => match (self.data[index / 4] >> (2 * (index % 4))) & 3 {
+                WDL_DRAW => DRAW,
+                WDL_WIN => FIRST_RESULT + 1,
+                WDL_LOSS => FIRST_RESULT,
+                _ => ILLEGAL,
+            },
+        };
+    }
+
+    pub fn to_wdl(&self) -> Table {
+        let mut data = vec![0u8; self.layout.size.div_ceil(4)];
+        for index in 0..self.layout.size {
+            let code = match self.value(index) {
+                DRAW => WDL_DRAW,
+                ILLEGAL => WDL_ILLEGAL,
+                v if (v - FIRST_RESULT) % 2 == 1 => WDL_WIN,
+                _ => WDL_LOSS,
+            };
+            data[index / 4] |= code << (2 * (index % 4));
+        }
+        return Table {
+            layout: Layout::new(&self.layout.name).unwrap(),
+            metric: Metric::Wdl,
+            data,
+        };
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // magic, version, metric, name length and name, then the values
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(match self.metric {
+            Metric::Dtm => 0,
+            Metric::Wdl => 1,
+        });
+        bytes.push(self.layout.name.len() as u8);
+        bytes.extend_from_slice(self.layout.name.as_bytes());
+        bytes.extend_from_slice(&self.data);
+        return bytes;
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Table, String> {
+        if bytes.len() < 7 || &bytes[..4] != MAGIC {
+            return Err(String::from("not a tablebase file"));
+        }
+        if bytes[4] != VERSION {
+            return Err(format!("unsupported tablebase version {}", bytes[4]));
+        }
+        let metric = match bytes[5] {
+            0 => Metric::Dtm,
+            1 => Metric::Wdl,
+            m => return Err(format!("unknown metric {}", m)),
+        };
+        let name_end = 7 + bytes[6] as usize;
+        let name = match bytes.get(7..name_end).map(std::str::from_utf8) {
+            Some(Ok(name)) => name,
+            _ => return Err(String::from("truncated tablebase header")),
+        };
+        let layout = Layout::new(name)?;
+        let expected = match metric {
+            Metric::Dtm => layout.size,
+            Metric::Wdl => layout.size.div_ceil(4),
+        };
+        if bytes.len() - name_end != expected {
+            return Err(format!(
+                "{} should hold {} bytes of values, not {}",
+                name,
+                expected,
+                bytes.len() - name_end
+            ));
+        }
+        return Ok(Table {
+            layout,
+            metric,
+            data: bytes[name_end..].to_vec(),
+        });
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        return fs::write(path, self.to_bytes())
+            .map_err(|e| format!("can't write {}: {}", path, e));
+    }
+
+    pub fn load(path: &str) -> Result<Table, String> {
+        let bytes = fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        return Table::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e));
+    }
+}
+
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases {
+            tables: HashMap::new(),
+        }
+    }
+
+    pub fn open_dir(dir: &str) -> Result<Tablebases, String> {
+        // every .rctb file in the directory
+        let mut tablebases = Tablebases::new();
+        let entries = fs::read_dir(dir).map_err(|e| format!("can't read {}: {}", dir, e))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(FILE_EXTENSION) {
+                tablebases.insert(Table::load(&path.to_string_lossy())?);
+            }
+        }
+        return Ok(tablebases);
+    }
+
+    pub fn save_dir(&self, dir: &str, metric: Metric) -> Result<(), String> {
+        for table in self.tables.values() {
+            let path = Path::new(dir).join(format!("{}.{}", table.name(), FILE_EXTENSION));
+            let path = path.to_string_lossy();
+            match (table.metric, metric) {
+                (Metric::Dtm, Metric::Wdl) => table.to_wdl().save(&path)?,
+                _ => table.save(&path)?,
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.name().to_string(), table);
+    }
+
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        return self.tables.get(&canonical_name(name).ok()?);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tables.keys().map(|n| n.as_str()).collect();
+        names.sort_unstable();
+        return names;
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        return self.tables.values().map(|t| t.pieces()).max().unwrap_or(0);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.tables.is_empty();
+    }
+
+    pub fn generate(&mut self, name: &str) -> Result<&Table, String> {
+        // builds the table by retrograde analysis, and first every smaller table it leads into
+        let layout = Layout::new(name)?;
+        let name = layout.name.clone();
+        if self.tables.get(&name).map(|t| t.metric) != Some(Metric::Dtm) {
+            for child in self.children(&layout) {
+                if self.tables.get(&child).map(|t| t.metric) != Some(Metric::Dtm) {
+                    self.generate(&child)?;
+                }
+            }
+            let table = self.build(layout)?;
+            self.tables.insert(name.clone(), table);
+        }
+        return Ok(&self.tables[&name]);
+    }
+
+    fn children(&self, layout: &Layout) -> Vec<String> {
+        // the tables a capture or a promotion leads into, bare kings need none
+        let pieces: Vec<Piece> = layout.kinds[2..].to_vec();
+        let mut names: Vec<String> = Vec::new();
+        let mut add = |pieces: &[Piece]| {
+            let white: Vec<PieceType> = pieces
+                .iter()
+                .filter(|p| p.as_color() == PieceColor::White)
+                .map(|p| p.as_type())
+                .collect();
+            let black: Vec<PieceType> = pieces
+                .iter()
+                .filter(|p| p.as_color() == PieceColor::Black)
+                .map(|p| p.as_type())
+                .collect();
+            if !is_drawn(&white, &black) {
+                let name = material_name(&white, &black).0;
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        };
+        let mut options: Vec<Vec<Piece>> = Vec::new();
+        for i in 0..pieces.len() {
+            let mut captured = pieces.clone();
+            captured.remove(i);
+            options.push(captured);
+        }
+        let promoted: Vec<Vec<Piece>> = options
+            .iter()
+            .chain(std::iter::once(&pieces))
+            .flat_map(|set| {
+                let mut out = Vec::new();
+                for (i, p) in set.iter().enumerate() {
+                    if p.as_type() == PieceType::Pawn {
+                        for t in [
+                            PieceType::Queen,
+                            PieceType::Rook,
+                            PieceType::Bishop,
+                            PieceType::Knight,
+                        ] {
+                            let mut next = set.clone();
+                            next[i] = Piece::from_type(t, p.as_color());
+                            out.push(next);
+                        }
+                    }
+                }
+                out
+            })
+            .collect();
+        for set in options.iter().chain(promoted.iter()) {
+            add(set);
+        }
+        return names;
+    }
+
+    fn cells_value(&self, cells: &Cells, white_to_move: bool) -> Option<u8> {
+        // the value for the side to move, from whichever table holds this material
+        let (white, black) = material_of(cells);
+        if is_drawn(&white, &black) {
+            return Some(DRAW);
+        }
+        let (name, swapped) = material_name(&white, &black);
+        let table = self.tables.get(&name)?;
+        let (cells, white_to_move) = if swapped {
+            (flip_colors(cells), !white_to_move)
+        } else {
+            (*cells, white_to_move)
+        };
+        let squares = table.layout.squares(&cells);
+        return Some(table.value(table.layout.index(&squares, white_to_move)));
+    }
+
+    fn build(&self, layout: Layout) -> Result<Table, String> {
+        let size = layout.size;
+        let mut values = vec![UNKNOWN; size];
+        // in-table moves whose result isn't known yet, and the best way out of the table
+        let mut counts = vec![0u8; size];
+        let mut exits = vec![ILLEGAL; size];
+        let mut buckets: Vec<Vec<u32>> = vec![Vec::new(); MAX_PLIES + 2];
+        let n = layout.kinds.len();
+        for index in 0..size {
+            let (squares, white_to_move) = layout.decode(index);
+            let cells = match layout.is_legal(&squares[..n], white_to_move) {
+                Some(c) if layout.index(&squares[..n], white_to_move) == index => c,
+                _ => {
+                    values[index] = ILLEGAL;
+                    continue;
+                }
+            };
+            let color = if white_to_move {
+                PieceColor::White
+            } else {
+                PieceColor::Black
+            };
+            let moves = legal_moves(&cells, color);
+            if moves.is_empty() {
+                if in_check(&cells, color) {
+                    buckets[0].push(index as u32);
+                } else {
+                    values[index] = DRAW;
+                }
+                continue;
+            }
+            let mut successors: Vec<usize> = Vec::new();
+            let mut exit = ILLEGAL;
+            for m in moves {
+                let next = play(&cells, m);
+                let captured = cells[m.1 as usize] != Piece::Blank;
+                if captured || m.2 != Piece::Blank {
+                    let value = match self.cells_value(&next, !white_to_move) {
+                        Some(v) if v < FIRST_RESULT + MAX_PLIES as u8 => negate(v),
+                        Some(_) => {
+                            return Err(format!("{} has mates too long to store", layout.name))
+                        }
+                        None => return Err(format!("{} needs more tables", layout.name)),
+                    };
+                    if better(value, exit) {
+                        exit = value;
+                    }
+                } else {
+                    let mut sq = squares;
+                    let moved = (0..n).find(|i| squares[*i] == m.0).unwrap();
+                    sq[moved] = m.1;
+                    successors.push(layout.index(&sq[..n], !white_to_move));
+                }
+            }
+            successors.sort_unstable();
+            successors.dedup();
+            counts[index] = successors.len() as u8;
+            exits[index] = exit;
+            if exit >= FIRST_RESULT && (exit - FIRST_RESULT) % 2 == 1 {
+                buckets[(exit - FIRST_RESULT) as usize].push(index as u32);
+            } else if successors.is_empty() {
+                match exit {
+                    DRAW => values[index] = DRAW,
+                    v => buckets[(v - FIRST_RESULT) as usize].push(index as u32),
+                }
+            }
+        }
+
+        for plies in 0..=MAX_PLIES {
+            let bucket = std::mem::take(&mut buckets[plies]);
+            for index in bucket {
+                let index = index as usize;
+                if values[index] != UNKNOWN {
+                    continue;
+                }
+                values[index] = FIRST_RESULT + plies as u8;
+                for previous in self.predecessors(&layout, index) {
+                    if values[previous] != UNKNOWN {
+                        continue;
+                    }
+                    if plies % 2 == 0 {
+                        // the move here wins for the side that makes it
+                        buckets[plies + 1].push(previous as u32);
+                        continue;
+                    }
+                    counts[previous] -= 1;
+                    if counts[previous] > 0 {
+                        continue;
+                    }
+                    match exits[previous] {
+                        DRAW => values[previous] = DRAW,
+                        ILLEGAL => buckets[plies + 1].push(previous as u32),
+                        // a win out of the table is already waiting in its bucket
+                        v if (v - FIRST_RESULT) % 2 == 1 => {}
+                        v => {
+                            let loss = (plies + 1).max((v - FIRST_RESULT) as usize);
+                            buckets[loss].push(previous as u32);
+                        }
+                    }
+                }
+            }
+        }
+        if buckets.iter().any(|b| !b.is_empty()) {
+            return Err(format!("{} has mates too long to store", layout.name));
+        }
+        for v in values.iter_mut() {
+            if *v == UNKNOWN {
+                *v = DRAW;
+            }
+        }
+        return Ok(Table {
+            layout,
+            metric: Metric::Dtm,
+            data: values,
+        });
+    }
+
+    fn predecessors(&self, layout: &Layout, index: usize) -> Vec<usize> {
+        // positions one quiet move earlier, undoing a move of the side that just moved
+        let n = layout.kinds.len();
+        let (squares, white_to_move) = layout.decode(index);
+        let cells = layout.cells(&squares[..n]).unwrap();
+        let moved = if white_to_move {
+            PieceColor::Black
+        } else {
+            PieceColor::White
+        };
+        let mut found = Vec::new();
+        for i in 0..n {
+            let kind = layout.kinds[i];
+            if kind.as_color() != moved {
+                continue;
+            }
+            let from = squares[i];
+            let mut origins = Vec::new();
+            if kind.as_type() == PieceType::Pawn {
+                // back down the board, never from the first rank and two squares from the fourth
+                let dr = if moved == PieceColor::White { 1 } else { -1 };
+                let home = if moved == PieceColor::White { 6 } else { 1 };
+                if let Some(s) = step(from, (dr, 0)) {
+                    if cells[s as usize] == Piece::Blank && s / 8 != 0 && s / 8 != 7 {
+                        origins.push(s);
+                        if let Some(s2) = step(s, (dr, 0)) {
+                            if s2 / 8 == home && cells[s2 as usize] == Piece::Blank {
+                                origins.push(s2);
+                            }
+                        }
+                    }
+                }
+            } else {
+                let (steps, slides) = piece_steps(kind.as_type());
+                for d in steps {
+                    let mut current = from;
+                    while let Some(s) = step(current, *d) {
+                        if cells[s as usize] != Piece::Blank {
+                            break;
+                        }
+                        origins.push(s);
+                        if !slides {
+                            break;
+                        }
+                        current = s;
+                    }
+                }
+            }
+            for origin in origins {
+                let mut sq = squares;
+                sq[i] = origin;
+                let previous = layout.index(&sq[..n], !white_to_move);
+                if previous < layout.size {
+                    found.push(previous);
+                }
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        return found;
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Probe> {
+        // None when no table holds the position, or it still has castling rights
+        if board.can_castle.iter().any(|c| *c != '-') {
+            return None;
+        }
+        let value = self.board_value(board)?;
+        return Some(self.to_probe(value));
+    }
+
+    fn has_distances(&self) -> bool {
+        // with a WDL table among them, no distance can be trusted
+        return self.tables.values().all(|t| t.metric == Metric::Dtm);
+    }
+
+    fn board_value(&self, board: &Board) -> Option<u8> {
+        let mut cells = [Piece::Blank; 64];
+        let mut count = 0;
+        for (s, cell) in cells.iter_mut().enumerate() {
+            *cell = board.squares[s / 8][s % 8];
+            if *cell != Piece::Blank {
+                count += 1;
+            }
+        }
+        if count > MAX_PIECES {
+            return None;
+        }
+        let value = self.cells_value(&cells, board.to_move == Turn::White)?;
+        if value == ILLEGAL {
+            return None;
+        }
+        return Some(value);
+    }
+
+    fn to_probe(&self, value: u8) -> Probe {
+        if value == DRAW {
+            return Probe {
+                wdl: Wdl::Draw,
+                dtm: None,
+            };
+        }
+        let plies = (value - FIRST_RESULT) as u32;
+        let wdl = if plies % 2 == 1 { Wdl::Win } else { Wdl::Loss };
+        let dtm = if self.has_distances() {
+            Some(plies)
+        } else {
+            None
+        };
+        return Probe { wdl, dtm };
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<(Move, Probe)> {
+        // the fastest win, or the slowest loss, with a DTM table this plays the ending perfectly
+        if board.can_castle.iter().any(|c| *c != '-') {
+            return None;
+        }
+        let mut best: Option<(Move, u8)> = None;
+        for m in board.generate_moves() {
+            let mut next = *board;
+            next.play_move(m);
+            let value = match next.generate_moves().is_empty() {
+                true if next.is_in_check() => FIRST_RESULT + 1,
+                true => DRAW,
+                false => negate(self.board_value(&next)?),
+            };
+            if best.is_none_or(|(_, b)| better(value, b)) {
+                best = Some((m, value));
+            }
+        }
+        let (m, value) = best?;
+        return Some((m, self.to_probe(value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::sync::OnceLock;
+
+    fn tables() -> &'static Tablebases {
+        // KPK needs KQK and KRK for its promotions, so this builds all three once
+        static TABLES: OnceLock<Tablebases> = OnceLock::new();
+        return TABLES.get_or_init(|| {
+            let mut tb = Tablebases::new();
+            tb.generate("KPK").unwrap();
+            tb
+        });
+    }
+
+    fn probe(tb: &Tablebases, fen: &str) -> Probe {
+        return tb.probe(&Board::parse_fen(fen).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(canonical_name("KQK").unwrap(), "KQvK");
+        assert_eq!(canonical_name("KvKQ").unwrap(), "KQvK");
+        assert_eq!(canonical_name("kbnk").unwrap(), "KBNvK");
+        assert_eq!(canonical_name("KPKR").unwrap(), "KRvKP");
+        assert!(canonical_name("KQRBK").is_ok());
+        assert!(canonical_name("KQRBNK").is_err());
+        assert!(canonical_name("QK").is_err());
+        assert_eq!(Layout::new("KQK").unwrap().pairs.len(), 462);
+        assert_eq!(Layout::new("KPK").unwrap().pairs.len(), 1806);
+        assert!(Layout::new("KPvKP").is_err());
+        assert!(Layout::new("KRPvKN").is_ok());
+    }
+
+    #[test]
+    fn test_kqk_krk() {
+        let tb = tables();
+        let longest = |name: &str| {
+            let table = tb.table(name).unwrap();
+            (0..table.len())
+                .map(|i| table.value(i))
+                .filter(|v| *v >= FIRST_RESULT && (*v - FIRST_RESULT) % 2 == 1)
+                .max()
+                .unwrap()
+                - FIRST_RESULT
+        };
+        // mate in 10 and in 16 at most
+        assert_eq!(longest("KQvK"), 19);
+        assert_eq!(longest("KRvK"), 31);
+        let mated = probe(tb, "k7/1Q6/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(
+            mated,
+            Probe {
+                wdl: Wdl::Loss,
+                dtm: Some(0)
+            }
+        );
+        assert_eq!(probe(tb, "k7/8/1K6/8/8/8/7Q/8 w - - 0 1").dtm, Some(1));
+        // black to move takes the queen
+        assert_eq!(probe(tb, "8/8/8/8/8/8/1q6/K1k5 w - - 0 1").wdl, Wdl::Loss);
+        assert_eq!(probe(tb, "8/8/8/8/8/8/1q6/K6k w - - 0 1").wdl, Wdl::Draw);
+        assert!(tb
+            .probe(&Board::parse_fen("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_kpk() {
+        let tb = tables();
+        assert!(tb.table("KQK").is_some());
+        assert!(tb.table("KBK").is_none());
+        assert_eq!(probe(tb, "8/8/8/8/8/8/4PK2/k7 w - - 0 1").wdl, Wdl::Win);
+        // the rook pawn can't push the king out of the corner
+        assert_eq!(probe(tb, "k7/8/8/8/8/8/P7/K7 w - - 0 1").wdl, Wdl::Draw);
+        assert_eq!(probe(tb, "k7/8/8/8/8/8/P7/K7 b - - 0 1").wdl, Wdl::Draw);
+        // the same with colours swapped
+        assert_eq!(probe(tb, "k7/p7/8/8/8/8/8/K7 w - - 0 1").wdl, Wdl::Draw);
+        assert_eq!(probe(tb, "8/4pk2/8/8/8/8/8/K7 b - - 0 1").wdl, Wdl::Win);
+        assert_eq!(probe(tb, "8/4pk2/8/8/8/8/8/K7 w - - 0 1").wdl, Wdl::Loss);
+    }
+
+    #[test]
+    fn test_play_and_files() {
+        let tb = tables();
+        let mut board = Board::parse_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+        let start = tb.probe(&board).unwrap();
+        assert_eq!(start.wdl, Wdl::Win);
+        let mut plies = 0;
+        while let Some((m, probe)) = tb.best_move(&board) {
+            assert_eq!(probe.dtm, Some(start.dtm.unwrap() - plies));
+            board.play_move(m);
+            plies += 1;
+        }
+        assert!(board.is_checkmate());
+        assert_eq!(Some(plies), start.dtm);
+
+        let table = tb.table("KRK").unwrap();
+        let again = Table::from_bytes(&table.to_bytes()).unwrap();
+        assert_eq!(again.data, table.data);
+        let wdl = Table::from_bytes(&table.to_wdl().to_bytes()).unwrap();
+        assert_eq!(wdl.metric(), Metric::Wdl);
+        assert_eq!(wdl.data.len(), table.len().div_ceil(4));
+        let mut small = Tablebases::new();
+        small.insert(wdl);
+        let fen = "8/8/8/4k3/8/8/8/R3K3 b - - 0 1";
+        assert_eq!(
+            probe(&small, fen),
+            Probe {
+                wdl: probe(tb, fen).wdl,
+                dtm: None
+            }
+        );
+        assert!(Table::from_bytes(&table.to_bytes()[..100]).is_err());
+        assert!(Table::from_bytes(b"nothing").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use super::board::Turn;
+
+use std::cmp;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Time kept back from every allocation to cover I/O and thread start-up
+const MOVE_OVERHEAD: u64 = 30;
+// Assumed number of moves left when the clock has no `movestogo`
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// Upper bound on how far an unstable best move may stretch the soft limit
+const MAX_INSTABILITY: f64 = 2.5;
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub move_time: Option<u64>,
+    pub infinite: bool,
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub moves_to_go: Option<u32>,
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn move_time(millis: u64) -> SearchLimits {
+        SearchLimits {
+            move_time: Some(millis),
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn infinite() -> SearchLimits {
+        SearchLimits {
+            infinite: true,
+            ..SearchLimits::default()
+        }
+    }
+
+    pub fn clock(&self, side: Turn) -> (Option<u64>, Option<u64>) {
+        // remaining time and increment for the side to move
+        match side {
+            Turn::White => (self.wtime, self.winc),
+            Turn::Black => (self.btime, self.binc),
+        }
+    }
+
+    pub fn has_clock(&self) -> bool {
+        return self.wtime.is_some() || self.btime.is_some();
+    }
+}
+
+// A cloneable flag that lets another thread (e.g. the UCI input loop) end a search.
+#[derive(Clone, Debug, Default)]
+pub struct StopSignal {
+    flag: Arc<AtomicBool>,
+}
+
+impl StopSignal {
+    pub fn new() -> StopSignal {
+        StopSignal::default()
+    }
+
+    pub fn stop(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        return self.flag.load(Ordering::SeqCst);
+    }
+
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::SeqCst);
+    }
+}
+
+pub struct TimeManager {
+    start: Instant,
+    soft_limit: Option<Duration>,
+    hard_limit: Option<Duration>,
+    max_nodes: Option<u64>,
+    max_depth: Option<u32>,
+    infinite: bool,
+    instability: f64,
+    stop: StopSignal,
+}
+
+impl TimeManager {
+    pub fn new(limits: &SearchLimits, side: Turn) -> TimeManager {
+        TimeManager::with_signal(limits, side, StopSignal::new())
+    }
+
+    pub fn with_signal(limits: &SearchLimits, side: Turn, stop: StopSignal) -> TimeManager {
+        let (soft, hard) = TimeManager::allocate(limits, side);
+        TimeManager {
+            start: Instant::now(),
+            soft_limit: soft.map(Duration::from_millis),
+            hard_limit: hard.map(Duration::from_millis),
+            max_nodes: limits.nodes,
+            max_depth: limits.depth,
+            infinite: limits.infinite,
+            instability: 1.0,
+            stop,
+        }
+    }
+
+    fn allocate(limits: &SearchLimits, side: Turn) -> (Option<u64>, Option<u64>) {
+        // returns (soft, hard) deadlines in milliseconds
+        if limits.infinite {
+            return (None, None);
+        }
+        if let Some(t) = limits.move_time {
+            let t = cmp::max(t.saturating_sub(MOVE_OVERHEAD), 1);
+            return (Some(t), Some(t));
+        }
+        let (remaining, inc) = limits.clock(side);
+        let remaining = match remaining {
+            Some(t) => t,
+            None => return (None, None),
+        };
+        let inc = inc.unwrap_or(0);
+        let usable = cmp::max(remaining.saturating_sub(MOVE_OVERHEAD), 1);
+        let moves_to_go = match limits.moves_to_go {
+            Some(n) if n > 0 => cmp::min(n as u64, DEFAULT_MOVES_TO_GO),
+            _ => DEFAULT_MOVES_TO_GO,
+        };
+        let soft = usable / moves_to_go + inc * 3 / 4;
+        // never plan on more than the whole remaining time for a single move
+        let hard_cap = if moves_to_go == 1 {
+            usable
+        } else {
+            usable / 2
+        };
+        let hard = cmp::min(soft * 4, hard_cap);
+        let soft = cmp::max(cmp::min(soft, hard), 1);
+        return (Some(soft), Some(cmp::max(hard, 1)));
+    }
+
+    pub fn stop_signal(&self) -> StopSignal {
+        return self.stop.clone();
+    }
+
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self.start.elapsed();
+    }
+
+    pub fn elapsed_millis(&self) -> u64 {
+        return self.elapsed().as_millis() as u64;
+    }
+
+    pub fn soft_limit(&self) -> Option<Duration> {
+        // the planned time for this move, stretched while the best move keeps changing
+        match self.soft_limit {
+            Some(soft) => {
+                let scaled = soft.mul_f64(self.instability);
+                Some(match self.hard_limit {
+                    Some(hard) => cmp::min(scaled, hard),
+                    None => scaled,
+                })
+            }
+            None => None,
+        }
+    }
+
+    pub fn hard_limit(&self) -> Option<Duration> {
+        return self.hard_limit;
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        return self.infinite;
+    }
+
+    pub fn update_stability(&mut self, best_move_changed: bool) {
+        // called once per finished iteration
+        if best_move_changed {
+            self.instability = (self.instability * 1.5).min(MAX_INSTABILITY);
+        } else {
+            self.instability = (self.instability * 0.9).max(1.0);
+        }
+    }
+
+    pub fn should_stop(&self, nodes: u64) -> bool {
+        // polled during the search; a hard stop abandons the current iteration
+        if self.stop.is_stopped() {
+            return true;
+        }
+        if let Some(max) = self.max_nodes {
+            if nodes >= max {
+                return true;
+            }
+        }
+        match self.hard_limit {
+            Some(hard) => self.elapsed() >= hard,
+            None => false,
+        }
+    }
+
+    pub fn should_start_iteration(&self, depth: u32, nodes: u64) -> bool {
+        // checked between iterations; the soft limit only prevents starting new work
+        if self.should_stop(nodes) {
+            return false;
+        }
+        if let Some(max) = self.max_depth {
+            if depth > max {
+                return false;
+            }
+        }
+        match self.soft_limit() {
+            Some(soft) => self.elapsed() < soft,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_move_time() {
+        let tm = TimeManager::new(&SearchLimits::move_time(1000), Turn::White);
+        assert_eq!(tm.soft_limit(), Some(Duration::from_millis(970)));
+        assert_eq!(tm.hard_limit(), Some(Duration::from_millis(970)));
+    }
+
+    #[test]
+    fn test_clock_allocation() {
+        let limits = SearchLimits {
+            wtime: Some(60_030),
+            btime: Some(1_030),
+            winc: Some(1000),
+            ..SearchLimits::default()
+        };
+        let white = TimeManager::new(&limits, Turn::White);
+        assert_eq!(white.soft_limit(), Some(Duration::from_millis(2750)));
+        assert_eq!(white.hard_limit(), Some(Duration::from_millis(11000)));
+        let black = TimeManager::new(&limits, Turn::Black);
+        assert_eq!(black.soft_limit(), Some(Duration::from_millis(33)));
+        assert_eq!(black.hard_limit(), Some(Duration::from_millis(132)));
+    }
+
+    #[test]
+    fn test_moves_to_go() {
+        // last move before the time control may use everything but the overhead
+        let limits = SearchLimits {
+            btime: Some(5_030),
+            moves_to_go: Some(1),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Turn::Black);
+        assert_eq!(tm.hard_limit(), Some(Duration::from_millis(5000)));
+    }
+
+    #[test]
+    fn test_instability_extends_soft_limit() {
+        let limits = SearchLimits {
+            wtime: Some(30_030),
+            ..SearchLimits::default()
+        };
+        let mut tm = TimeManager::new(&limits, Turn::White);
+        let base = tm.soft_limit().unwrap();
+        tm.update_stability(true);
+        assert!(tm.soft_limit().unwrap() > base);
+        for _ in 0..10 {
+            tm.update_stability(true);
+        }
+        assert!(tm.soft_limit().unwrap() <= tm.hard_limit().unwrap());
+        for _ in 0..50 {
+            tm.update_stability(false);
+        }
+        assert_eq!(tm.soft_limit().unwrap(), base);
+    }
+
+    #[test]
+    fn test_depth_and_nodes() {
+        let limits = SearchLimits {
+            depth: Some(3),
+            nodes: Some(500),
+            ..SearchLimits::default()
+        };
+        let tm = TimeManager::new(&limits, Turn::White);
+        assert!(tm.should_start_iteration(3, 0));
+        assert!(!tm.should_start_iteration(4, 0));
+        assert!(!tm.should_stop(499));
+        assert!(tm.should_stop(500));
+    }
+
+    #[test]
+    fn test_stop_from_thread() {
+        let tm = TimeManager::new(&SearchLimits::infinite(), Turn::White);
+        assert!(!tm.should_stop(u64::MAX));
+        let signal = tm.stop_signal();
+        thread::spawn(move || signal.stop()).join().unwrap();
+        assert!(tm.should_stop(0));
+        assert!(!tm.should_start_iteration(1, 0));
+    }
+}
@@ -0,0 +1,673 @@
+use super::board::{Board, Move};
+use super::pgn::{PgnGame, PgnMove};
+
+pub type NodeId = usize;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Arrow {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub color: MarkColor,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Highlight {
+    pub square: (usize, usize),
+    pub color: MarkColor,
+}
+
+#[derive(Clone)]
+pub struct Node {
+    // the root has no move, every other node holds the move that led to it
+    pub mv: Option<Move>,
+    pub san: String,
+    parent: Option<NodeId>,
+    // the first child continues the mainline, the others are variations
+    children: Vec<NodeId>,
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    pub nags: Vec<u8>,
+    pub arrows: Vec<Arrow>,
+    pub highlights: Vec<Highlight>,
+    // clock time left after the move, in milliseconds
+    pub clock: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct GameTree {
+    start: Board,
+    nodes: Vec<Node>,
+    pub tags: Vec<(String, String)>,
+    pub result: String,
+}
+
+pub struct Cursor<'a> {
+    tree: &'a mut GameTree,
+    node: NodeId,
+    board: Board,
+}
+
+impl MarkColor {
+    pub fn as_char(&self) -> char {
+        match *self {
+            MarkColor::Green => 'G',
+            MarkColor::Red => 'R',
+            MarkColor::Yellow => 'Y',
+            MarkColor::Blue => 'B',
+        }
+    }
+
+    pub fn from_char(c: char) -> Option<MarkColor> {
+        match c {
+            'G' => Some(MarkColor::Green),
+            'R' => Some(MarkColor::Red),
+            'Y' => Some(MarkColor::Yellow),
+            'B' => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    fn new(mv: Option<Move>, san: String, parent: Option<NodeId>) -> Node {
+        Node {
+            mv,
+            san,
+            parent,
+            children: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            nags: Vec::new(),
+            arrows: Vec::new(),
+            highlights: Vec::new(),
+            clock: None,
+        }
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        return self.parent;
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        return &self.children;
+    }
+}
+
+impl GameTree {
+    pub fn new(start: Board) -> GameTree {
+        GameTree {
+            start,
+            nodes: vec![Node::new(None, String::new(), None)],
+            tags: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        return 0;
+    }
+
+    pub fn start(&self) -> &Board {
+        return &self.start;
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        return &self.nodes[id];
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        return &mut self.nodes[id];
+    }
+
+    pub fn mainline_child(&self, id: NodeId) -> Option<NodeId> {
+        return self.nodes[id].children.first().copied();
+    }
+
+    pub fn variations(&self, id: NodeId) -> &[NodeId] {
+        // the alternatives to the mainline child of id
+        let children = &self.nodes[id].children;
+        if children.is_empty() {
+            return children;
+        }
+        return &children[1..];
+    }
+
+    pub fn mainline(&self) -> Vec<NodeId> {
+        let mut line = Vec::new();
+        let mut id = self.root();
+        while let Some(child) = self.mainline_child(id) {
+            line.push(child);
+            id = child;
+        }
+        return line;
+    }
+
+    pub fn path(&self, id: NodeId) -> Vec<Move> {
+        // the moves from the start position to id
+        let mut moves = Vec::new();
+        let mut id = id;
+        while let Some(parent) = self.nodes[id].parent {
+            moves.push(self.nodes[id].mv.unwrap());
+            id = parent;
+        }
+        moves.reverse();
+        return moves;
+    }
+
+    pub fn board_at(&self, id: NodeId) -> Option<Board> {
+        // None for a node that has been deleted
+        if !self.is_in_tree(id) {
+            return None;
+        }
+        let mut board = self.start;
+        for m in self.path(id) {
+            board.play_move(m);
+        }
+        return Some(board);
+    }
+
+    pub fn add_move(&mut self, parent: NodeId, m: Move) -> Result<NodeId, String> {
+        // a move that is already there is reused, a new one becomes the last variation
+        let board = self
+            .board_at(parent)
+            .ok_or(format!("node {} has been deleted", parent))?;
+        if let Some(id) = self.nodes[parent]
+            .children
+            .iter()
+            .find(|c| self.nodes[**c].mv == Some(m))
+        {
+            return Ok(*id);
+        }
+        if !board.generate_moves().contains(&m) {
+            return Err(format!("{} is not a legal move", m));
+        }
+        return Ok(self.push_node(parent, m, board.to_san(&m)));
+    }
+
+    fn push_node(&mut self, parent: NodeId, m: Move, san: String) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node::new(Some(m), san, Some(parent)));
+        self.nodes[parent].children.push(id);
+        return id;
+    }
+
+    pub fn promote(&mut self, id: NodeId) {
+        // makes the line through id the mainline at every branch point above it
+        let mut id = id;
+        while let Some(parent) = self.nodes[id].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|c| *c == id).unwrap();
+            children.remove(index);
+            children.insert(0, id);
+            id = parent;
+        }
+    }
+
+    pub fn promote_variation(&mut self, id: NodeId) {
+        // moves id one place up among its siblings, so the first variation swaps with the mainline
+        if let Some(parent) = self.nodes[id].parent {
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|c| *c == id).unwrap();
+            if index > 0 {
+                children.swap(index, index - 1);
+            }
+        }
+    }
+
+    pub fn delete_from(&mut self, id: NodeId) -> Result<NodeId, String> {
+        // removes id and everything after it, and returns its parent
+        let parent = match self.nodes[id].parent {
+            Some(p) => p,
+            None => return Err(String::from("the root can't be deleted")),
+        };
+        self.nodes[parent].children.retain(|c| *c != id);
+        self.nodes[id].parent = None;
+        return Ok(parent);
+    }
+
+    pub fn is_in_tree(&self, id: NodeId) -> bool {
+        // deleted nodes stay in storage but are no longer reachable from the root
+        if id >= self.nodes.len() {
+            return false;
+        }
+        let mut id = id;
+        while let Some(parent) = self.nodes[id].parent {
+            id = parent;
+        }
+        return id == self.root();
+    }
+
+    pub fn cursor(&mut self) -> Cursor<'_> {
+        let board = self.start;
+        return Cursor {
+            tree: self,
+            node: 0,
+            board,
+        };
+    }
+
+    pub fn from_pgn(pgn: &PgnGame) -> GameTree {
+        let mut tree = GameTree::new(pgn.start);
+        tree.tags = pgn.tags.clone();
+        tree.result = pgn.result.clone();
+        let root = tree.root();
+        tree.add_line(root, &pgn.moves);
+        return tree;
+    }
+
+    fn add_line(&mut self, parent: NodeId, line: &[PgnMove]) {
+        // the moves were checked by the PGN reader, so they are added without replaying them
+        let mut parent = parent;
+        for pgn_move in line {
+            let id = self.push_node(parent, pgn_move.mv, pgn_move.san.clone());
+            let node = &mut self.nodes[id];
+            node.comments_before = pgn_move.comments_before.clone();
+            node.nags = pgn_move.nags.clone();
+            for comment in &pgn_move.comments {
+                let text = parse_commands(comment, node);
+                if !text.is_empty() {
+                    node.comments.push(text);
+                }
+            }
+            for variation in &pgn_move.variations {
+                self.add_line(parent, variation);
+            }
+            parent = id;
+        }
+    }
+
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::new(self.start);
+        pgn.tags = self.tags.clone();
+        pgn.result = self.result.clone();
+        if let Some(first) = self.mainline_child(self.root()) {
+            pgn.moves = self.line(first, true);
+        }
+        return pgn;
+    }
+
+    fn line(&self, first: NodeId, with_siblings: bool) -> Vec<PgnMove> {
+        // the mainline from first on, with the variations at each step
+        let mut moves = Vec::new();
+        let mut id = first;
+        let mut with_siblings = with_siblings;
+        loop {
+            let node = &self.nodes[id];
+            let mut pgn_move = PgnMove::new(node.san.clone(), node.mv.unwrap());
+            pgn_move.comments_before = node.comments_before.clone();
+            pgn_move.nags = node.nags.clone();
+            // commands share the first comment, as in {[%clk 0:03:00] a good move}
+            pgn_move.comments = node.comments.clone();
+            let commands = format_commands(node);
+            if !commands.is_empty() {
+                match pgn_move.comments.first_mut() {
+                    Some(first) => *first = format!("{} {}", commands, first),
+                    None => pgn_move.comments.push(commands),
+                }
+            }
+            if with_siblings {
+                for sibling in self.variations(node.parent.unwrap()) {
+                    pgn_move.variations.push(self.line(*sibling, false));
+                }
+            }
+            moves.push(pgn_move);
+            match self.mainline_child(id) {
+                Some(child) => id = child,
+                None => break,
+            }
+            with_siblings = true;
+        }
+        return moves;
+    }
+}
+
+impl<'a> Cursor<'a> {
+    pub fn node(&self) -> NodeId {
+        return self.node;
+    }
+
+    pub fn board(&self) -> &Board {
+        return &self.board;
+    }
+
+    pub fn tree(&self) -> &GameTree {
+        return self.tree;
+    }
+
+    pub fn current(&mut self) -> &mut Node {
+        return &mut self.tree.nodes[self.node];
+    }
+
+    pub fn forward(&mut self) -> bool {
+        // follows the mainline
+        return self.enter(0);
+    }
+
+    pub fn enter(&mut self, index: usize) -> bool {
+        // index 0 is the mainline, 1 the first variation and so on
+        match self.tree.nodes[self.node].children.get(index) {
+            Some(child) => {
+                let child = *child;
+                self.board.play_move(self.tree.nodes[child].mv.unwrap());
+                self.node = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn back(&mut self) -> bool {
+        match self.tree.nodes[self.node].parent {
+            Some(parent) => {
+                self.board = self.tree.board_at(parent).unwrap();
+                self.node = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn goto(&mut self, id: NodeId) -> Result<(), String> {
+        self.board = self
+            .tree
+            .board_at(id)
+            .ok_or(format!("node {} has been deleted", id))?;
+        self.node = id;
+        return Ok(());
+    }
+
+    pub fn to_start(&mut self) {
+        self.board = self.tree.start;
+        self.node = self.tree.root();
+    }
+
+    pub fn to_end(&mut self) {
+        while self.forward() {}
+    }
+
+    pub fn play(&mut self, m: Move) -> Result<NodeId, String> {
+        let id = self.tree.add_move(self.node, m)?;
+        self.board.play_move(m);
+        self.node = id;
+        return Ok(id);
+    }
+
+    pub fn play_san(&mut self, san: &str) -> Result<NodeId, String> {
+        let m = self.board.parse_san(san)?;
+        return self.play(m);
+    }
+
+    pub fn promote(&mut self) {
+        self.tree.promote(self.node);
+    }
+
+    pub fn delete(&mut self) -> Result<(), String> {
+        // deletes the current node and what follows, and steps back to its parent
+        let parent = self.tree.delete_from(self.node)?;
+        return self.goto(parent);
+    }
+}
+
+fn parse_square_pair(text: &str) -> Option<((usize, usize), (usize, usize))> {
+    if text.len() != 4 || !text.is_ascii() {
+        return None;
+    }
+    let from = Board::parse_square(&text[0..2])?;
+    let to = Board::parse_square(&text[2..4])?;
+    return Some((from, to));
+}
+
+fn parse_clock(text: &str) -> Option<u64> {
+    // h:mm:ss with optional fractions of a second
+    let parts: Vec<&str> = text.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let hours = parts[0].parse::<u64>().ok()?;
+    let minutes = parts[1].parse::<u64>().ok()?;
+    let seconds = parts[2].parse::<f64>().ok()?;
+    if seconds < 0.0 {
+        return None;
+    }
+    return Some((hours * 3600 + minutes * 60) * 1000 + (seconds * 1000.0).round() as u64);
+}
+
+fn parse_command(name: &str, args: &str, node: &mut Node) -> bool {
+    match name {
+        "cal" => {
+            for arg in args.split(',') {
+                let arg = arg.trim();
+                let color = arg.chars().next().and_then(MarkColor::from_char);
+                match (color, parse_square_pair(arg.get(1..).unwrap_or(""))) {
+                    (Some(color), Some((from, to))) => node.arrows.push(Arrow { from, to, color }),
+                    _ => return false,
+                }
+            }
+            true
+        }
+        "csl" => {
+            for arg in args.split(',') {
+                let arg = arg.trim();
+                let color = arg.chars().next().and_then(MarkColor::from_char);
+                match (color, Board::parse_square(arg.get(1..).unwrap_or(""))) {
+                    (Some(color), Some(square)) => {
+                        node.highlights.push(Highlight { square, color })
+                    }
+                    _ => return false,
+                }
+            }
+            true
+        }
+        "clk" => match parse_clock(args.trim()) {
+            Some(ms) => {
+                node.clock = Some(ms);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn parse_commands(comment: &str, node: &mut Node) -> String {
+    // takes [%cal ...], [%csl ...] and [%clk ...] out of a comment and returns the rest
+    let mut rest = String::new();
+    let mut text = comment;
+    while let Some(start) = text.find("[%") {
+        let end = match text[start..].find(']') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let command = &text[start + 2..end];
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        rest.push_str(&text[..start]);
+        if !parse_command(name, args, node) {
+            // unknown commands are kept for whoever reads the comment next
+            rest.push_str(&text[start..=end]);
+        }
+        text = &text[end + 1..];
+    }
+    rest.push_str(text);
+    return rest.split_whitespace().collect::<Vec<&str>>().join(" ");
+}
+
+fn format_commands(node: &Node) -> String {
+    let mut commands = Vec::new();
+    if !node.highlights.is_empty() {
+        let marks: Vec<String> = node
+            .highlights
+            .iter()
+            .map(|h| format!("{}{}", h.color.as_char(), Board::square_name(h.square)))
+            .collect();
+        commands.push(format!("[%csl {}]", marks.join(",")));
+    }
+    if !node.arrows.is_empty() {
+        let marks: Vec<String> = node
+            .arrows
+            .iter()
+            .map(|a| {
+                format!(
+                    "{}{}{}",
+                    a.color.as_char(),
+                    Board::square_name(a.from),
+                    Board::square_name(a.to)
+                )
+            })
+            .collect();
+        commands.push(format!("[%cal {}]", marks.join(",")));
+    }
+    if let Some(ms) = node.clock {
+        let seconds = ms / 1000;
+        let mut clock = format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        );
+        if ms % 1000 != 0 {
+            clock.push_str(format!(".{:03}", ms % 1000).trim_end_matches('0'));
+        }
+        commands.push(format!("[%clk {}]", clock));
+    }
+    return commands.join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::engine::pgn::PgnReader;
+
+    const STUDY: &str = "[Event \"Study\"]\n\n1. e4 { [%clk 0:03:00] [%cal Ge2e4,Rd7d5] best by test } e5 (1... c5 { [%csl Yd4] Sicilian [%evp 1,2] } 2. Nf3) (1... e6) 2. Nf3 { [%clk 0:02:58.5] } *\n";
+
+    fn study() -> GameTree {
+        let pgn = PgnReader::new(STUDY.as_bytes()).next().unwrap().unwrap();
+        return GameTree::from_pgn(&pgn);
+    }
+
+    #[test]
+    fn test_from_pgn() {
+        let tree = study();
+        let mainline = tree.mainline();
+        assert_eq!(mainline.len(), 3);
+        let e4 = tree.node(mainline[0]);
+        assert_eq!(e4.san, "e4");
+        assert_eq!(e4.clock, Some(180_000));
+        assert_eq!(e4.comments, vec!["best by test"]);
+        assert!(
+            e4.arrows
+                == vec![
+                    Arrow {
+                        from: (6, 4),
+                        to: (4, 4),
+                        color: MarkColor::Green
+                    },
+                    Arrow {
+                        from: (1, 3),
+                        to: (3, 3),
+                        color: MarkColor::Red
+                    },
+                ]
+        );
+        let variations = tree.variations(mainline[0]);
+        assert_eq!(variations.len(), 2);
+        let c5 = tree.node(variations[0]);
+        assert_eq!(c5.san, "c5");
+        assert!(
+            c5.highlights
+                == vec![Highlight {
+                    square: (4, 3),
+                    color: MarkColor::Yellow
+                }]
+        );
+        assert_eq!(c5.comments, vec!["Sicilian [%evp 1,2]"]);
+        assert_eq!(
+            tree.node(tree.mainline_child(variations[0]).unwrap()).san,
+            "Nf3"
+        );
+        assert_eq!(tree.node(mainline[2]).clock, Some(178_500));
+    }
+
+    #[test]
+    fn test_pgn_round_trip() {
+        let pgn = study().to_pgn().to_pgn();
+        let text = pgn.replace('\n', " ");
+        assert!(text.contains("1. e4 {[%cal Ge2e4,Rd7d5] [%clk 0:03:00] best by test} 1... e5"));
+        assert!(text.contains("(1... c5 {[%csl Yd4] Sicilian [%evp 1,2]} 2. Nf3) (1... e6)"));
+        assert!(text.contains("2. Nf3 {[%clk 0:02:58.5]} *"));
+        let again = PgnReader::new(pgn.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(GameTree::from_pgn(&again).to_pgn().to_pgn(), pgn);
+    }
+
+    #[test]
+    fn test_promote_and_delete() {
+        let mut tree = study();
+        let e4 = tree.mainline()[0];
+        let e6 = tree.variations(e4)[1];
+        tree.promote(e6);
+        assert_eq!(tree.node(tree.mainline()[1]).san, "e6");
+        assert_eq!(tree.mainline().len(), 2);
+        assert_eq!(tree.node(tree.variations(e4)[0]).san, "e5");
+
+        tree.promote_variation(tree.variations(e4)[1]);
+        assert_eq!(tree.node(tree.variations(e4)[0]).san, "c5");
+
+        let c5 = tree.variations(e4)[0];
+        assert_eq!(tree.delete_from(c5).unwrap(), e4);
+        assert!(!tree.is_in_tree(c5));
+        assert!(tree.board_at(c5).is_none());
+        assert!(tree.board_at(e4).is_some());
+        assert!(tree.add_move(c5, Board::default().parse_move("g1f3").unwrap()).is_err());
+        assert_eq!(tree.variations(e4).len(), 1);
+        assert!(tree.delete_from(tree.root()).is_err());
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut tree = GameTree::new(Board::default());
+        let mut cursor = tree.cursor();
+        cursor.play_san("e4").unwrap();
+        cursor.play_san("e5").unwrap();
+        cursor.back();
+        let c5 = cursor.play_san("c5").unwrap();
+        assert_eq!(
+            cursor.board().to_fen(),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2"
+        );
+        assert!(cursor.play_san("Ke3").is_err());
+        cursor.current().nags.push(1);
+        cursor.to_start();
+        assert!(cursor.board().to_fen() == Board::default().to_fen());
+        cursor.to_end();
+        assert_eq!(cursor.tree().node(cursor.node()).san, "e5");
+        cursor.back();
+        assert!(cursor.enter(1));
+        assert_eq!(cursor.node(), c5);
+        // playing a move that is already there follows it
+        cursor.back();
+        assert_eq!(
+            cursor.play_san("e5").unwrap(),
+            tree_mainline_second(cursor.tree())
+        );
+        let e5 = cursor.node();
+        cursor.delete().unwrap();
+        assert_eq!(cursor.tree().node(cursor.node()).san, "e4");
+        assert!(cursor.goto(e5).is_err());
+        assert_eq!(cursor.tree().node(cursor.node()).san, "e4");
+        assert!(cursor.forward());
+        assert_eq!(cursor.node(), c5);
+        assert_eq!(tree.node(c5).nags, vec![1]);
+    }
+
+    fn tree_mainline_second(tree: &GameTree) -> NodeId {
+        return tree.mainline()[1];
+    }
+}
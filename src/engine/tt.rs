@@ -0,0 +1,191 @@
+use super::board::Move;
+use super::piece::PieceType;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TtEntry {
+    // best move packed by encode_move, 0 if there is none
+    pub mv: u16,
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+}
+
+const ENTRY_BYTES: usize = 16;
+const VALID: u64 = 1 << 63;
+
+pub fn encode_move(m: &Move) -> u16 {
+    // 6 bits start square, 6 bits end square, 3 bits promotion type; a drop has the
+    // same start and end square and the dropped piece in place of the promotion
+    let start = (m.start.0 * 8 + m.start.1) as u16;
+    let end = (m.end.0 * 8 + m.end.1) as u16;
+    let piece = if m.is_drop() { m.piece } else { m.promotion };
+    let promotion = match piece.as_type() {
+        PieceType::Queen => 1,
+        PieceType::Rook => 2,
+        PieceType::Bishop => 3,
+        PieceType::Knight => 4,
+        PieceType::Pawn => 5,
+        _ => 0,
+    };
+    return start | end << 6 | promotion << 12;
+}
+
+fn pack(entry: &TtEntry) -> u64 {
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+    return entry.mv as u64
+        | (entry.score as u32 as u64) << 16
+        | (entry.depth.min(255) as u64) << 48
+        | bound << 56
+        | VALID;
+}
+
+fn unpack(data: u64) -> TtEntry {
+    TtEntry {
+        mv: data as u16,
+        score: (data >> 16) as u32 as i32,
+        depth: ((data >> 48) & 0xff) as u32,
+        bound: match (data >> 56) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        },
+    }
+}
+
+// Shared between search threads without locking: each slot stores the key xored
+// with the data, so a torn write from two threads just reads back as a miss.
+pub struct TranspositionTable {
+    entries: Vec<(AtomicU64, AtomicU64)>,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let count = (megabytes.max(1) * 1024 * 1024 / ENTRY_BYTES).max(1);
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            entries.push((AtomicU64::new(0), AtomicU64::new(0)));
+        }
+        TranspositionTable { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+
+    pub fn clear(&self) {
+        for (key, data) in &self.entries {
+            key.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &(AtomicU64, AtomicU64) {
+        return &self.entries[(key % self.entries.len() as u64) as usize];
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let (stored_key, stored_data) = self.slot(key);
+        let data = stored_data.load(Ordering::Relaxed);
+        if data & VALID == 0 || stored_key.load(Ordering::Relaxed) ^ data != key {
+            return None;
+        }
+        return Some(unpack(data));
+    }
+
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        let (stored_key, stored_data) = self.slot(key);
+        let old = stored_data.load(Ordering::Relaxed);
+        // keep deeper results for the same position
+        if old & VALID != 0
+            && stored_key.load(Ordering::Relaxed) ^ old == key
+            && unpack(old).depth > entry.depth
+            && entry.bound != Bound::Exact
+        {
+            return;
+        }
+        let data = pack(&entry);
+        stored_key.store(key ^ data, Ordering::Relaxed);
+        stored_data.store(data, Ordering::Relaxed);
+    }
+
+    pub fn hashfull(&self) -> u32 {
+        // permille of the first thousand slots in use, as reported to UCI
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|(_, data)| data.load(Ordering::Relaxed) & VALID != 0)
+            .count();
+        return (used * 1000 / sample.max(1)) as u32;
+    }
+}
+
+pub fn move_matches(encoded: u16, m: &Move) -> bool {
+    return encoded != 0 && encoded == encode_move(m);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::engine::board::Board;
+
+    #[test]
+    fn test_store_probe() {
+        let tt = TranspositionTable::new(1);
+        let board = Board::default();
+        let m = board.parse_move("e2e4").unwrap();
+        let entry = TtEntry {
+            mv: encode_move(&m),
+            score: -123,
+            depth: 7,
+            bound: Bound::Lower,
+        };
+        tt.store(board.hash(), entry);
+        assert_eq!(tt.probe(board.hash()), Some(entry));
+        assert!(move_matches(tt.probe(board.hash()).unwrap().mv, &m));
+        assert_eq!(tt.probe(board.hash() ^ 1), None);
+        tt.clear();
+        assert_eq!(tt.probe(board.hash()), None);
+    }
+
+    #[test]
+    fn test_depth_preferred() {
+        let tt = TranspositionTable::new(1);
+        let deep = TtEntry {
+            mv: 0,
+            score: 10,
+            depth: 8,
+            bound: Bound::Upper,
+        };
+        let shallow = TtEntry { depth: 2, ..deep };
+        tt.store(42, deep);
+        tt.store(42, shallow);
+        assert_eq!(tt.probe(42).unwrap().depth, 8);
+    }
+
+    #[test]
+    fn test_promotion_encoding() {
+        let board = Board::from_fen(String::from("8/P6k/8/8/8/8/8/4K3 w - - 0 1"));
+        let queen = board.parse_move("a7a8q").unwrap();
+        let knight = board.parse_move("a7a8n").unwrap();
+        assert!(encode_move(&queen) != encode_move(&knight));
+        assert!(!move_matches(encode_move(&queen), &knight));
+    }
+}
@@ -0,0 +1,932 @@
+use super::board::{Board, Move, Turn, PROMOTION_TYPES};
+use super::eval;
+use super::game::GameResult;
+use super::piece::{Piece, PieceColor, PieceType};
+
+use std::sync::Arc;
+
+// the names UCI_Variant knows, as used by lichess and the multi-variant engines
+pub const VARIANTS: [&str; 8] = [
+    "chess",
+    "kingofthehill",
+    "3check",
+    "crazyhouse",
+    "atomic",
+    "antichess",
+    "horde",
+    "racingkings",
+];
+
+// what a pocket can hold, in the order of VariantState::pockets
+pub const POCKET_TYPES: [PieceType; 5] = [
+    PieceType::Pawn,
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+// Antichess pawns may become kings as well
+const ANTICHESS_PROMOTIONS: [PieceType; 5] = [
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::King,
+];
+
+const CENTRE: [(usize, usize); 4] = [(3, 3), (3, 4), (4, 3), (4, 4)];
+const CHECKS_TO_WIN: u8 = 3;
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct VariantState {
+    // checks given so far by white and black, for Three-check
+    pub checks: [u8; 2],
+    // pieces in hand for white and black, counted in POCKET_TYPES order
+    pub pockets: [[u8; 5]; 2],
+    // squares holding a promoted piece, which goes back to a pocket as a pawn
+    pub promoted: u64,
+}
+
+impl VariantState {
+    pub const fn new() -> VariantState {
+        // Board::default is a const fn, which Default::default is not
+        VariantState {
+            checks: [0, 0],
+            pockets: [[0; 5]; 2],
+            promoted: 0,
+        }
+    }
+
+    fn pocket_slot(piece: Piece) -> Option<(usize, usize)> {
+        let color = match piece.as_color() {
+            PieceColor::White => 0,
+            PieceColor::Black => 1,
+            PieceColor::None => return None,
+        };
+        let kind = POCKET_TYPES.iter().position(|t| *t == piece.as_type())?;
+        return Some((color, kind));
+    }
+
+    pub fn in_hand(&self, piece: Piece) -> u8 {
+        return match VariantState::pocket_slot(piece) {
+            Some((color, kind)) => self.pockets[color][kind],
+            None => 0,
+        };
+    }
+
+    pub fn add_to_pocket(&mut self, piece: Piece) {
+        if let Some((color, kind)) = VariantState::pocket_slot(piece) {
+            self.pockets[color][kind] = self.pockets[color][kind].saturating_add(1);
+        }
+    }
+
+    pub fn take_from_pocket(&mut self, piece: Piece) {
+        if let Some((color, kind)) = VariantState::pocket_slot(piece) {
+            self.pockets[color][kind] = self.pockets[color][kind].saturating_sub(1);
+        }
+    }
+
+    pub fn is_promoted(&self, square: (usize, usize)) -> bool {
+        return self.promoted & square_bit(square) != 0;
+    }
+
+    pub fn record_move(
+        &mut self,
+        mover: PieceColor,
+        captured: Piece,
+        location: (usize, usize),
+        target: (usize, usize),
+        promotion: bool,
+    ) {
+        // the captured piece changes colour into the mover's pocket, and a promoted
+        // piece keeps its mark as it moves
+        if captured != Piece::Blank {
+            let ptype = if self.is_promoted(target) {
+                PieceType::Pawn
+            } else {
+                captured.as_type()
+            };
+            self.add_to_pocket(Piece::from_type(ptype, mover));
+        }
+        let promoted = self.is_promoted(location) || promotion;
+        self.promoted &= !(square_bit(location) | square_bit(target));
+        if promoted {
+            self.promoted |= square_bit(target);
+        }
+    }
+
+    pub fn pocket_string(&self) -> String {
+        // as in FEN, white's pieces first and the strongest first
+        let mut text = String::new();
+        for (color, pocket) in self.pockets.iter().enumerate() {
+            for (kind, count) in pocket.iter().enumerate().rev() {
+                let letter = POCKET_TYPES[kind].as_char();
+                let letter = if color == 0 {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                };
+                text.extend(std::iter::repeat_n(letter, *count as usize));
+            }
+        }
+        return text;
+    }
+}
+
+fn square_bit(square: (usize, usize)) -> u64 {
+    return 1 << (square.0 * 8 + square.1);
+}
+
+pub trait Variant: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn start_position(&self) -> Board {
+        return Board::default();
+    }
+
+    fn parse_fen(&self, fen: &str) -> Result<Board, String> {
+        return Board::parse_fen(fen);
+    }
+
+    fn to_fen(&self, board: &Board) -> String {
+        return board.to_fen();
+    }
+
+    fn is_legal(&self, _board: &Board, _m: &Move) -> bool {
+        // a filter on top of the normal rules, for moves the variant forbids
+        return true;
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        return board
+            .generate_moves_for(self)
+            .into_iter()
+            .filter(|m| self.is_legal(board, m))
+            .collect();
+    }
+
+    fn parse_move(&self, board: &Board, text: &str) -> Result<Move, String> {
+        let m = board.parse_move_for(self, text)?;
+        if !self.is_legal(board, &m) {
+            return Err(format!("{} is not legal in {}", text, self.name()));
+        }
+        return Ok(m);
+    }
+
+    fn play_move(&self, board: &mut Board, m: Move) {
+        // where the variant keeps its extra state up to date, and what a capture
+        // does besides taking the piece
+        board.play_move(m);
+    }
+
+    fn in_check(&self, board: &Board, color: PieceColor) -> bool {
+        return board.in_check(color);
+    }
+
+    fn is_safe_for(&self, board: &Board, color: PieceColor) -> bool {
+        // whether the side that just moved got away with it
+        return !self.in_check(board, color);
+    }
+
+    fn promotion_types(&self) -> &'static [PieceType] {
+        return &PROMOTION_TYPES;
+    }
+
+    fn result(&self, _board: &Board) -> Option<GameResult> {
+        // a game the variant's own rules have ended, whatever moves are left
+        return None;
+    }
+
+    fn no_moves_result(&self, board: &Board) -> GameResult {
+        if self.in_check(board, board.to_move.as_color()) {
+            return GameResult::win_for(board.to_move.opposite_turn());
+        }
+        return GameResult::Draw;
+    }
+
+    fn is_insufficient_material(&self, board: &Board) -> bool {
+        return board.is_insufficient_material();
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        // from the side to move's point of view, like eval::evaluate
+        return eval::evaluate_with(board, self.standard_rules());
+    }
+
+    fn standard_rules(&self) -> bool {
+        // whether a position is won, drawn or lost just as in chess, so that known
+        // endings and material counting carry over
+        return false;
+    }
+
+    fn uses_tablebases(&self) -> bool {
+        // the endgame tables only hold for the normal rules
+        return self.standard_rules();
+    }
+
+    fn forced_captures(&self) -> bool {
+        // when set, the search can't stand pat in a position with a capture to make
+        return false;
+    }
+}
+
+pub struct Standard;
+
+pub struct KingOfTheHill;
+
+pub struct ThreeCheck;
+
+pub struct Crazyhouse;
+
+pub struct Atomic;
+
+pub struct Antichess;
+
+pub struct Horde;
+
+pub struct RacingKings;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        return "chess";
+    }
+
+    fn standard_rules(&self) -> bool {
+        return true;
+    }
+}
+
+impl Variant for KingOfTheHill {
+    // a king reaching one of the four centre squares wins
+    fn name(&self) -> &'static str {
+        return "kingofthehill";
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        for turn in [Turn::White, Turn::Black].iter() {
+            let king = board.king_position(turn.as_color());
+            if king.is_some_and(|k| CENTRE.contains(&k)) {
+                return Some(GameResult::win_for(*turn));
+            }
+        }
+        return None;
+    }
+
+    fn is_insufficient_material(&self, _board: &Board) -> bool {
+        // a bare king can still walk to the centre
+        return false;
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        let mut score = eval::evaluate_with(board, false);
+        for turn in [Turn::White, Turn::Black].iter() {
+            if let Some(king) = board.king_position(turn.as_color()) {
+                let distance = CENTRE
+                    .iter()
+                    .map(|c| (c.0 as i32 - king.0 as i32).abs().max((c.1 as i32 - king.1 as i32).abs()))
+                    .min()
+                    .unwrap();
+                let bonus = (3 - distance) * 30;
+                score += if *turn == board.to_move { bonus } else { -bonus };
+            }
+        }
+        return score;
+    }
+}
+
+impl Variant for ThreeCheck {
+    // giving the third check wins
+    fn name(&self) -> &'static str {
+        return "3check";
+    }
+
+    fn to_fen(&self, board: &Board) -> String {
+        // the checks still to give go after the en passant square, as in 3+3
+        let fen = board.to_fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let left = |side: usize| CHECKS_TO_WIN.saturating_sub(board.state.checks[side]);
+        return format!(
+            "{} {}+{} {}",
+            fields[..4].join(" "),
+            left(0),
+            left(1),
+            fields[4..].join(" ")
+        );
+    }
+
+    fn play_move(&self, board: &mut Board, m: Move) {
+        let mover = side_index(board.to_move);
+        board.play_move(m);
+        if board.is_in_check() {
+            board.state.checks[mover] += 1;
+        }
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        if board.state.checks[0] >= CHECKS_TO_WIN {
+            return Some(GameResult::WhiteWins);
+        }
+        if board.state.checks[1] >= CHECKS_TO_WIN {
+            return Some(GameResult::BlackWins);
+        }
+        return None;
+    }
+
+    fn is_insufficient_material(&self, board: &Board) -> bool {
+        // any piece can still give checks
+        return board
+            .squares
+            .iter()
+            .flatten()
+            .all(|p| *p == Piece::Blank || p.as_type() == PieceType::King);
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        // each check given is worth more than a minor piece, the next one most of all
+        const CHECK_BONUS: [i32; 3] = [0, 250, 600];
+        let bonus = |side: usize| CHECK_BONUS[board.state.checks[side].min(2) as usize];
+        let us = side_index(board.to_move);
+        return eval::evaluate_with(board, false) + bonus(us) - bonus(1 - us);
+    }
+}
+
+impl Variant for Crazyhouse {
+    // captured pieces change sides and can be dropped back onto the board
+    fn name(&self) -> &'static str {
+        return "crazyhouse";
+    }
+
+    fn to_fen(&self, board: &Board) -> String {
+        // the pockets go in brackets after the placement, even when empty
+        let fen = board.to_fen();
+        let (placement, rest) = fen.split_once(' ').unwrap_or((&fen, ""));
+        return format!("{}[{}] {}", placement, board.state.pocket_string(), rest);
+    }
+
+    fn play_move(&self, board: &mut Board, m: Move) {
+        if m.is_drop() || board.is_castling(&m) {
+            board.play_move(m);
+            return;
+        }
+        let mut taken = board.squares[m.end.0][m.end.1];
+        if taken == Piece::Blank && board.is_capture(&m) {
+            // en passant
+            taken = Piece::from_type(PieceType::Pawn, m.piece.as_color().opposite());
+        }
+        let promotion = m.piece.as_type() == PieceType::Pawn && (m.end.0 == 0 || m.end.0 == 7);
+        board.play_move(m);
+        board.state.record_move(m.piece.as_color(), taken, m.start, m.end, promotion);
+    }
+
+    fn is_insufficient_material(&self, board: &Board) -> bool {
+        let empty = board.state.pockets.iter().flatten().all(|n| *n == 0);
+        return empty && board.is_insufficient_material();
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        // a piece in hand can go anywhere, so it counts a little more than one on the board
+        let mut hand = 0;
+        for (color, pocket) in board.state.pockets.iter().enumerate() {
+            let value: i32 = pocket
+                .iter()
+                .zip(POCKET_TYPES.iter())
+                .map(|(n, t)| *n as i32 * eval::piece_value(*t) * 5 / 4)
+                .sum();
+            hand += if color == side_index(board.to_move) {
+                value
+            } else {
+                -value
+            };
+        }
+        return eval::evaluate_with(board, false) + hand;
+    }
+}
+
+impl Variant for Atomic {
+    // a capture blows up the capturer and every piece but a pawn around it
+    fn name(&self) -> &'static str {
+        return "atomic";
+    }
+
+    fn is_legal(&self, board: &Board, m: &Move) -> bool {
+        // a king would blow itself up
+        return m.piece.as_type() != PieceType::King || !board.is_capture(m);
+    }
+
+    fn play_move(&self, board: &mut Board, m: Move) {
+        let capture = !m.is_drop() && board.is_capture(&m);
+        board.play_move(m);
+        if capture {
+            explode(board, m.end);
+        }
+    }
+
+    fn in_check(&self, board: &Board, color: PieceColor) -> bool {
+        // taking a king that touches the other king blows up both
+        let king = match board.king_position(color) {
+            Some(king) => king,
+            None => return false,
+        };
+        let touching = board
+            .king_position(color.opposite())
+            .is_some_and(|k| k.0.abs_diff(king.0) <= 1 && k.1.abs_diff(king.1) <= 1);
+        return !touching && board.in_check(color);
+    }
+
+    fn is_safe_for(&self, board: &Board, color: PieceColor) -> bool {
+        // the mover's own king must survive, and blowing up the other king wins
+        // whatever the checks
+        if board.king_position(color).is_none() {
+            return false;
+        }
+        if board.king_position(color.opposite()).is_none() {
+            return true;
+        }
+        return !self.in_check(board, color);
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        // only the side that just moved can have blown up a king
+        if board.king_position(board.to_move.as_color()).is_none() {
+            return Some(GameResult::win_for(board.to_move.opposite_turn()));
+        }
+        return None;
+    }
+}
+
+impl Variant for Antichess {
+    // the first side to lose all its pieces, or to have no move, wins
+    fn name(&self) -> &'static str {
+        return "antichess";
+    }
+
+    fn start_position(&self) -> Board {
+        let mut board = Board::default();
+        board.can_castle = ['-'; 4];
+        return board;
+    }
+
+    fn parse_fen(&self, fen: &str) -> Result<Board, String> {
+        let mut board = Board::parse_fen(fen)?;
+        board.can_castle = ['-'; 4];
+        return Ok(board);
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        // a capture has to be made when there is one
+        let mut moves = board.generate_moves_for(self);
+        if moves.iter().any(|m| board.is_capture(m)) {
+            moves.retain(|m| board.is_capture(m));
+        }
+        return moves;
+    }
+
+    fn parse_move(&self, board: &Board, text: &str) -> Result<Move, String> {
+        let m = board.parse_move_for(self, text)?;
+        if !board.is_capture(&m) && !self.legal_moves(board).contains(&m) {
+            return Err(format!("{} is not a capture, and capturing is compulsory", text.trim()));
+        }
+        return Ok(m);
+    }
+
+    fn in_check(&self, _board: &Board, _color: PieceColor) -> bool {
+        // the king is just another piece
+        return false;
+    }
+
+    fn promotion_types(&self) -> &'static [PieceType] {
+        return &ANTICHESS_PROMOTIONS;
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        let color = board.to_move.as_color();
+        if board.squares.iter().flatten().all(|p| p.as_color() != color) {
+            return Some(GameResult::win_for(board.to_move));
+        }
+        return None;
+    }
+
+    fn no_moves_result(&self, board: &Board) -> GameResult {
+        // being stalemated wins
+        return GameResult::win_for(board.to_move);
+    }
+
+    fn is_insufficient_material(&self, _board: &Board) -> bool {
+        return false;
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        // every piece is a burden, the king no less than a minor piece
+        let color = board.to_move.as_color();
+        let mut score = 0;
+        for piece in board.squares.iter().flatten() {
+            let value = match piece.as_type() {
+                PieceType::King => 300,
+                PieceType::Blank => continue,
+                ptype => eval::piece_value(ptype) / 2 + 100,
+            };
+            score += if piece.as_color() == color { -value } else { value };
+        }
+        return score;
+    }
+
+    fn forced_captures(&self) -> bool {
+        return true;
+    }
+}
+
+impl Variant for Horde {
+    // 36 white pawns and no king against a normal black army
+    fn name(&self) -> &'static str {
+        return "horde";
+    }
+
+    fn start_position(&self) -> Board {
+        return Board::from_fen(String::from(
+            "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1",
+        ));
+    }
+
+    fn legal_moves(&self, board: &Board) -> Vec<Move> {
+        let mut moves = board.generate_moves_for(self);
+        moves.extend(first_rank_pushes(board));
+        return moves;
+    }
+
+    fn parse_move(&self, board: &Board, text: &str) -> Result<Move, String> {
+        let push = first_rank_pushes(board).into_iter().find(|m| m.to_string() == text.trim());
+        return match push {
+            Some(m) => Ok(m),
+            None => board.parse_move_for(self, text),
+        };
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        // black wins by taking everything, white by mating as usual
+        if board.squares.iter().flatten().all(|p| p.as_color() != PieceColor::White) {
+            return Some(GameResult::BlackWins);
+        }
+        return None;
+    }
+
+    fn is_insufficient_material(&self, _board: &Board) -> bool {
+        // black wins by taking white's last piece, so a lone knight or bishop is no draw
+        return false;
+    }
+}
+
+impl Variant for RacingKings {
+    // no checks, and the first king to reach the eighth rank wins
+    fn name(&self) -> &'static str {
+        return "racingkings";
+    }
+
+    fn start_position(&self) -> Board {
+        return Board::from_fen(String::from("8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1"));
+    }
+
+    fn is_legal(&self, board: &Board, m: &Move) -> bool {
+        // giving check is as illegal as walking into one
+        let mut after = *board;
+        after.play_move(*m);
+        return !after.is_in_check();
+    }
+
+    fn result(&self, board: &Board) -> Option<GameResult> {
+        let home = |color| board.king_position(color).is_some_and(|k| k.0 == 0);
+        return match (home(PieceColor::White), home(PieceColor::Black)) {
+            (true, true) => Some(GameResult::Draw),
+            (false, true) => Some(GameResult::BlackWins),
+            // black gets one more move to draw by getting there too
+            (true, false) if board.to_move == Turn::Black => {
+                let king = board.king_position(PieceColor::Black);
+                let catches_up = king.is_some_and(|k| {
+                    k.0 == 1 && self.legal_moves(board).iter().any(|m| m.start == k && m.end.0 == 0)
+                });
+                if catches_up {
+                    None
+                } else {
+                    Some(GameResult::WhiteWins)
+                }
+            }
+            (true, false) => Some(GameResult::WhiteWins),
+            (false, false) => None,
+        };
+    }
+
+    fn is_insufficient_material(&self, _board: &Board) -> bool {
+        return false;
+    }
+
+    fn evaluate(&self, board: &Board) -> i32 {
+        // material, and above all how far each king has come
+        let mut score = 0;
+        for (i, row) in board.squares.iter().enumerate() {
+            for piece in row.iter() {
+                let value = match piece.as_type() {
+                    PieceType::King => (7 - i as i32) * 150,
+                    ptype => eval::piece_value(ptype),
+                };
+                score += if piece.as_color() == PieceColor::White {
+                    value
+                } else {
+                    -value
+                };
+            }
+        }
+        if board.to_move == Turn::Black {
+            return -score;
+        }
+        return score;
+    }
+}
+
+fn explode(board: &mut Board, center: (usize, usize)) {
+    // the capturing piece goes, and so does every piece but a pawn next to it
+    board.remove_piece(center);
+    for row in center.0.saturating_sub(1)..=(center.0 + 1).min(7) {
+        for col in center.1.saturating_sub(1)..=(center.1 + 1).min(7) {
+            let piece = board.squares[row][col];
+            if piece != Piece::Blank && piece.as_type() != PieceType::Pawn {
+                board.remove_piece((row, col));
+            }
+        }
+    }
+}
+
+fn first_rank_pushes(board: &Board) -> Vec<Move> {
+    // Horde's white pawns may move two squares from the first rank as well, which
+    // gives no en passant
+    let mut pushes = Vec::new();
+    if board.to_move != Turn::White {
+        return pushes;
+    }
+    for col in 0..8 {
+        if board.squares[7][col] != Piece::WPawn
+            || board.is_occupied((6, col))
+            || board.is_occupied((5, col))
+        {
+            continue;
+        }
+        let m = Move::new(Piece::WPawn, (7, col), (5, col));
+        let mut after = *board;
+        after.play_move(m);
+        if !after.in_check(PieceColor::White) {
+            pushes.push(m);
+        }
+    }
+    return pushes;
+}
+
+fn side_index(turn: Turn) -> usize {
+    match turn {
+        Turn::White => 0,
+        Turn::Black => 1,
+    }
+}
+
+pub fn parse_check_counter(field: &str) -> Option<[u8; 2]> {
+    // 3+3 counts the checks each side still needs, +1+0 the checks already given
+    let (given, text) = match field.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, field),
+    };
+    let mut parts = text.split('+').map(|n| n.parse::<u8>().ok());
+    let (white, black) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(Some(w)), Some(Some(b)), None) => (w, b),
+        _ => return None,
+    };
+    if given {
+        return Some([white, black]);
+    }
+    return Some([
+        CHECKS_TO_WIN.saturating_sub(white),
+        CHECKS_TO_WIN.saturating_sub(black),
+    ]);
+}
+
+pub fn by_name(name: &str) -> Option<Arc<dyn Variant>> {
+    match name.to_lowercase().as_str() {
+        "chess" | "standard" => Some(Arc::new(Standard)),
+        "kingofthehill" => Some(Arc::new(KingOfTheHill)),
+        "3check" | "threecheck" => Some(Arc::new(ThreeCheck)),
+        "crazyhouse" | "zh" => Some(Arc::new(Crazyhouse)),
+        "atomic" => Some(Arc::new(Atomic)),
+        "antichess" | "giveaway" => Some(Arc::new(Antichess)),
+        "horde" => Some(Arc::new(Horde)),
+        "racingkings" => Some(Arc::new(RacingKings)),
+        _ => None,
+    }
+}
+
+pub fn perft<V: Variant + ?Sized>(variant: &V, board: &Board, depth: u32) -> u64 {
+    // a position the variant has already decided has no moves to count
+    if depth == 0 {
+        return 1;
+    }
+    if variant.result(board).is_some() {
+        return 0;
+    }
+    let moves = variant.legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    let mut count = 0;
+    for m in moves {
+        let mut next = *board;
+        variant.play_move(&mut next, m);
+        count += perft(variant, &next, depth - 1);
+    }
+    return count;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn play(variant: &dyn Variant, board: &mut Board, moves: &str) {
+        for text in moves.split_whitespace() {
+            let m = variant.parse_move(board, text).unwrap();
+            variant.play_move(board, m);
+        }
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let variant = KingOfTheHill;
+        let mut board = variant.parse_fen("4k3/8/8/8/8/4K3/8/8 w - - 0 1").unwrap();
+        assert!(variant.result(&board).is_none());
+        assert!(!variant.is_insufficient_material(&board));
+        play(&variant, &mut board, "e3e4");
+        assert_eq!(variant.result(&board), Some(GameResult::WhiteWins));
+        assert_eq!(perft(&variant, &board, 2), 0);
+        assert_eq!(perft(&variant, &Board::default(), 2), 400);
+        // a drawn pawn ending in chess, but the white king walks to the centre
+        let board = variant.parse_fen("k7/8/8/8/8/8/P7/1K6 w - - 0 1").unwrap();
+        assert_eq!(eval::evaluate(&board), 0);
+        assert!(variant.evaluate(&board) > 0);
+        assert!(!variant.standard_rules() && Standard.standard_rules());
+    }
+
+    #[test]
+    fn test_three_check() {
+        let variant = ThreeCheck;
+        let mut board = variant.start_position();
+        assert_eq!(
+            variant.to_fen(&board),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1"
+        );
+        play(&variant, &mut board, "e2e4 f7f6 d1h5 g7g6");
+        assert_eq!(board.state.checks, [1, 0]);
+        let fen = variant.to_fen(&board);
+        assert!(fen.contains(" - 2+3 "));
+        assert_eq!(variant.parse_fen(&fen).unwrap().state.checks, [1, 0]);
+        let board = variant
+            .parse_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1 +2+0")
+            .unwrap();
+        assert_eq!(board.state.checks, [2, 0]);
+        let mut third = board;
+        play(&variant, &mut third, "d1d8");
+        assert_eq!(variant.result(&third), Some(GameResult::WhiteWins));
+        let board = variant.parse_fen("k7/8/8/8/8/8/P7/1K6 w - - 0 1").unwrap();
+        assert!(variant.evaluate(&board) > 0);
+        assert_eq!(parse_check_counter("3+1"), Some([0, 2]));
+        assert_eq!(parse_check_counter("3-1"), None);
+        assert!(by_name("3check").is_some() && by_name("shogi").is_none());
+    }
+
+    #[test]
+    fn test_crazyhouse() {
+        let variant = Crazyhouse;
+        let mut board = variant.start_position();
+        play(&variant, &mut board, "e2e4 d7d5 e4d5 d8d5 b1c3 d5a5");
+        assert_eq!(board.state.pockets, [[1, 0, 0, 0, 0], [1, 0, 0, 0, 0]]);
+        play(&variant, &mut board, "P@e6");
+        assert!(board.squares[2][4] == Piece::WPawn);
+        let fen = variant.to_fen(&board);
+        assert!(fen.starts_with("rnb1kbnr/ppp1pppp/4P3/q7/8/2N5/PPPP1PPP/R1BQKBNR[p] b"));
+
+        // a promoted queen goes back to the pocket as a pawn, and pawns can't be dropped on the back rank
+        let mut board = variant
+            .parse_fen("r3k3/1P6/8/8/8/8/8/4K3[Pp] w - - 0 1")
+            .unwrap();
+        play(&variant, &mut board, "b7a8q");
+        assert!(variant.to_fen(&board).starts_with("Q~3k3/8/8/8/8/8/8/4K3[RPp] b"));
+        assert!(variant.parse_move(&board, "P@d1").is_err());
+        play(&variant, &mut board, "e8d7 a8b8 p@e5 b8c7 d7c7");
+        assert_eq!(board.state.pockets, [[1, 0, 0, 1, 0], [1, 0, 0, 0, 0]]);
+        assert_eq!(board.to_san(&board.parse_san("R@e2").unwrap()), "R@e2");
+        assert!(variant.to_fen(&variant.start_position()).contains("RNBQKBNR[] w"));
+
+        let board = variant
+            .parse_fen("2k5/8/8/8/8/8/8/4K3/QRBNPqrbnp w - - 0 1")
+            .unwrap();
+        assert_eq!(perft(&variant, &board, 2), 75353);
+        assert_eq!(perft(&variant, &variant.start_position(), 3), 8902);
+        // the promoted queen is taken and goes back as a pawn, as are black's promotions
+        let board = variant.parse_fen("4k3/1Q~6/8/8/4b3/8/Kpp5/8/ b - - 0 1").unwrap();
+        assert_eq!(perft(&variant, &board, 3), 5445);
+    }
+
+    #[test]
+    fn test_atomic() {
+        let variant = Atomic;
+        let mut board = variant.start_position();
+        play(&variant, &mut board, "g1f3 d7d5 f3e5 a7a6 e5f7");
+        // the knight blew up itself, the king and the pieces around f7 but not the pawns
+        assert!(board.to_fen().starts_with("rnbq3r/1pp1p1pp/p7/3p4/8/8/PPPPPPPP/RNBQKB1R b KQ -"));
+        assert_eq!(variant.result(&board), Some(GameResult::WhiteWins));
+
+        // kings can't capture, and touching kings are never in check
+        let board = variant.parse_fen("8/8/8/8/3kq3/4K3/8/8 w - - 0 1").unwrap();
+        assert!(variant.parse_move(&board, "e3e4").is_err());
+        assert!(!variant.in_check(&board, PieceColor::White));
+        assert!(variant.parse_move(&board, "e3d3").is_ok());
+        assert!(variant.parse_move(&board, "e3f3").is_err());
+
+        let board = variant
+            .parse_fen("rn1qkb1r/p5pp/2p5/3p4/N3P3/5P2/PPP4P/R1BQK3 w Qkq - 0 1")
+            .unwrap();
+        assert_eq!(perft(&variant, &board, 2), 833);
+    }
+
+    #[test]
+    fn test_antichess() {
+        let variant = Antichess;
+        let mut board = variant.start_position();
+        play(&variant, &mut board, "e2e3 b7b5");
+        // the bishop has to take
+        assert!(variant.parse_move(&board, "a2a3").is_err());
+        assert_eq!(variant.legal_moves(&board).len(), 1);
+        play(&variant, &mut board, "f1b5");
+        assert!(board.to_fen().starts_with("rnbqkbnr/p1pppppp/8/1B6/8/4P3/PPPP1PPP/RNBQK1NR b - -"));
+
+        // kings can be taken and pawns can become kings
+        let mut board = variant.parse_fen("8/P7/8/8/8/8/8/7k w - - 0 1").unwrap();
+        assert_eq!(variant.legal_moves(&board).len(), 5);
+        let king = variant.parse_move(&board, "a7a8k").unwrap();
+        assert_eq!(board.to_san_for(&variant, &king), "a8=K");
+        assert!(board.parse_san_for(&variant, "a8=K").unwrap() == king);
+        assert!(Board::default().parse_san("a8=K").is_err());
+        play(&variant, &mut board, "a7a8k");
+        assert!(board.squares[0][0] == Piece::WKing);
+        assert!(variant.result(&board).is_none());
+        let board = variant.parse_fen("8/8/8/8/8/8/8/7k w - - 0 1").unwrap();
+        assert_eq!(variant.result(&board), Some(GameResult::WhiteWins));
+        // being stalemated wins as well, and having fewer pieces is better
+        let board = variant.parse_fen("k7/8/8/8/8/p7/P7/8 w - - 0 1").unwrap();
+        assert!(variant.legal_moves(&board).is_empty());
+        assert_eq!(variant.no_moves_result(&board), GameResult::WhiteWins);
+        assert!(variant.evaluate(&board) > 0);
+
+        assert_eq!(perft(&variant, &variant.start_position(), 3), 8067);
+    }
+
+    #[test]
+    fn test_horde() {
+        let variant = Horde;
+        let board = variant.start_position();
+        assert_eq!(board.squares.iter().flatten().filter(|p| **p == Piece::WPawn).count(), 36);
+        assert_eq!(perft(&variant, &board, 3), 1274);
+        let board = variant.parse_fen("4k3/8/8/8/8/8/8/4N3 w - - 0 1").unwrap();
+        assert!(!variant.is_insufficient_material(&board));
+        // a double push from the first rank gives no en passant
+        let mut board = variant.parse_fen("4k3/8/8/8/8/1p6/8/P7 w - - 0 1").unwrap();
+        play(&variant, &mut board, "a1a3");
+        assert!(board.en_passant_square().is_none());
+        assert!(variant.parse_move(&board, "b3a2").is_err());
+        // first rank pawns may double-push, and white loses with nothing left
+        let mut board = variant.parse_fen("4k3/8/8/8/8/8/8/P7 w - - 0 1").unwrap();
+        play(&variant, &mut board, "a1a3");
+        assert!(variant.result(&board).is_none());
+        let board = variant.parse_fen("4k3/8/8/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(variant.result(&board), Some(GameResult::BlackWins));
+    }
+
+    #[test]
+    fn test_racing_kings() {
+        let variant = RacingKings;
+        let board = variant.start_position();
+        assert_eq!(variant.legal_moves(&board).len(), 21);
+        // no check may be given
+        let board = variant.parse_fen("8/8/8/8/8/k7/8/6KR w - - 0 1").unwrap();
+        assert!(variant.parse_move(&board, "h1a1").is_err());
+        assert!(variant.parse_move(&board, "h1h2").is_ok());
+
+        // black may draw by reaching the last rank straight after white
+        let mut board = variant.parse_fen("8/1k5K/8/8/8/8/8/8 w - - 0 1").unwrap();
+        play(&variant, &mut board, "h7h8");
+        assert!(variant.result(&board).is_none());
+        let mut drawn = board;
+        play(&variant, &mut drawn, "b7b8");
+        assert_eq!(variant.result(&drawn), Some(GameResult::Draw));
+        play(&variant, &mut board, "b7c6");
+        assert_eq!(variant.result(&board), Some(GameResult::WhiteWins));
+        let board = variant.parse_fen("7K/8/k7/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(variant.result(&board), Some(GameResult::WhiteWins));
+        let board = variant.parse_fen("k7/8/8/8/8/8/8/7K w - - 0 1").unwrap();
+        assert_eq!(variant.result(&board), Some(GameResult::BlackWins));
+    }
+}
@@ -0,0 +1,182 @@
+use super::board::{Board, Turn};
+use super::piece::Piece;
+
+pub struct Keys {
+    pub pieces: [[u64; 64]; 12],
+    pub black_to_move: u64,
+    pub castling: [u64; 4],
+    pub en_passant: [u64; 8],
+    // Three-check counters, by side and number of checks given
+    pub checks: [[u64; 4]; 2],
+    // Crazyhouse pockets, by side, piece type and count up to 16
+    pub pockets: [[[u64; 17]; 5]; 2],
+    // Crazyhouse pieces that were promoted pawns, by square
+    pub promoted: [u64; 64],
+}
+
+const fn next_key(state: u64) -> u64 {
+    // xorshift64*, only used to fill the key table at compile time
+    let mut x = state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    return x.wrapping_mul(0x2545_f491_4f6c_dd1d);
+}
+
+const fn generate() -> Keys {
+    let mut keys = Keys {
+        pieces: [[0; 64]; 12],
+        black_to_move: 0,
+        castling: [0; 4],
+        en_passant: [0; 8],
+        checks: [[0; 4]; 2],
+        pockets: [[[0; 17]; 5]; 2],
+        promoted: [0; 64],
+    };
+    let mut state: u64 = 0x0123_4567_89ab_cdef;
+    let mut p = 0;
+    while p < 12 {
+        let mut sq = 0;
+        while sq < 64 {
+            state = next_key(state);
+            keys.pieces[p][sq] = state;
+            sq += 1;
+        }
+        p += 1;
+    }
+    state = next_key(state);
+    keys.black_to_move = state;
+    let mut i = 0;
+    while i < 4 {
+        state = next_key(state);
+        keys.castling[i] = state;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 8 {
+        state = next_key(state);
+        keys.en_passant[i] = state;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 8 {
+        state = next_key(state);
+        keys.checks[i / 4][i % 4] = state;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 170 {
+        state = next_key(state);
+        keys.pockets[i / 85][i / 17 % 5][i % 17] = state;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 64 {
+        state = next_key(state);
+        keys.promoted[i] = state;
+        i += 1;
+    }
+    return keys;
+}
+
+pub static KEYS: Keys = generate();
+
+pub fn piece_key(piece: Piece, square: (usize, usize)) -> u64 {
+    if piece == Piece::Blank {
+        return 0;
+    }
+    return KEYS.pieces[piece as usize][square.0 * 8 + square.1];
+}
+
+pub fn hash(board: &Board) -> u64 {
+    // Internal position key, used for the transposition table and repetitions
+    let mut h = 0;
+    for (i, row) in board.squares.iter().enumerate() {
+        for (j, piece) in row.iter().enumerate() {
+            h ^= piece_key(*piece, (i, j));
+        }
+    }
+    if board.to_move == Turn::Black {
+        h ^= KEYS.black_to_move;
+    }
+    for (i, c) in board.can_castle.iter().enumerate() {
+        if *c != '-' {
+            h ^= KEYS.castling[i];
+        }
+    }
+    if let Some(square) = board.en_passant_square() {
+        h ^= KEYS.en_passant[square.1];
+    }
+    for (side, given) in board.state.checks.iter().enumerate() {
+        if *given > 0 {
+            h ^= KEYS.checks[side][(*given as usize).min(3)];
+        }
+    }
+    for (side, pocket) in board.state.pockets.iter().enumerate() {
+        for (kind, count) in pocket.iter().enumerate() {
+            if *count > 0 {
+                h ^= KEYS.pockets[side][kind][(*count as usize).min(16)];
+            }
+        }
+    }
+    // a promoted piece goes back to the pocket as a pawn, so it is not the same position
+    for (i, key) in KEYS.promoted.iter().enumerate() {
+        if board.state.promoted & (1 << i) != 0 {
+            h ^= key;
+        }
+    }
+    return h;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_keys_unique() {
+        let mut all: Vec<u64> = KEYS.pieces.iter().flat_map(|r| r.iter().cloned()).collect();
+        all.push(KEYS.black_to_move);
+        all.extend(KEYS.castling.iter());
+        all.extend(KEYS.en_passant.iter());
+        all.extend(KEYS.checks.iter().flatten());
+        all.extend(KEYS.pockets.iter().flatten().flatten());
+        all.extend(KEYS.promoted.iter());
+        let len = all.len();
+        all.sort();
+        all.dedup();
+        assert_eq!(all.len(), len);
+    }
+
+    #[test]
+    fn test_transposition() {
+        // the same position reached by different move orders hashes the same
+        let mut board1 = Board::default();
+        let mut board2 = Board::default();
+        for m in ["g1f3", "g8f6", "b1c3", "b8c6"].iter() {
+            board1.make_move_from_string(m.to_string());
+        }
+        for m in ["b1c3", "b8c6", "g1f3", "g8f6"].iter() {
+            board2.make_move_from_string(m.to_string());
+        }
+        assert_eq!(board1.hash(), board2.hash());
+        assert!(board1.hash() != Board::default().hash());
+    }
+
+    #[test]
+    fn test_side_and_castling() {
+        let white = Board::from_fen(String::from("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"));
+        let black = Board::from_fen(String::from("4k3/8/8/8/8/8/8/R3K3 b Q - 0 1"));
+        let no_castle = Board::from_fen(String::from("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        assert!(white.hash() != black.hash());
+        assert!(white.hash() != no_castle.hash());
+    }
+
+    #[test]
+    fn test_promoted_pieces() {
+        let promoted = Board::parse_fen("Q~3k3/8/8/8/8/8/8/4K3[p] b - - 0 1").unwrap();
+        let queen = Board::parse_fen("Q3k3/8/8/8/8/8/8/4K3[p] b - - 0 1").unwrap();
+        assert!(promoted.state.is_promoted((0, 0)));
+        assert!(promoted.hash() != queen.hash());
+    }
+}
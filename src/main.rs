@@ -1,43 +1,51 @@
-mod engine;
+#![allow(clippy::needless_return)]
 
-use engine::board::Board;
-fn main() {
-    let mut my_board = Board::default();
-    my_board.print();
-    my_board.make_move_from_string(String::from("e2e4"));
-    my_board.print();
-    my_board.make_move_from_string(String::from("e7e5"));
-    my_board.print();
-    let my_board = Board::default();
+mod book;
+mod repl;
+mod tablebase;
+mod testsuite;
+mod uci;
+mod xboard;
+
+use std::env;
+use std::io::{self, BufRead};
+use std::process;
 
-    let moves1 = my_board.generate_moves();
-    let mut count1 = 0;
-    for m in moves1 {
-        count1 += 1;
-        println!(
-            "{}, ({}, {}), ({}, {})",
-            m.piece.as_string(),
-            m.start.0,
-            m.start.1,
-            m.end.0,
-            m.start.1
-        )
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("play") => {
+            // rust_chess play: a game against the engine in the terminal
+            repl::run();
+            return;
+        }
+        Some("book") => {
+            // rust_chess book make games.pgn book.bin: build an opening book from games
+            process::exit(book::run(&args[2..]));
+        }
+        Some("tablebase") => {
+            // rust_chess tablebase generate tables KQK KRK: build endgame tables
+            process::exit(tablebase::run(&args[2..]));
+        }
+        Some("testsuite") => {
+            // rust_chess testsuite wac.epd depth 8: solve the positions of an EPD file
+            process::exit(testsuite::run(&args[2..]));
+        }
+        _ => {}
     }
-    let moves2 = my_board.recurse_gen_moves();
-    let mut count2 = 0;
-    for l in moves2 {
-        for m in l {
-            count2 += 1;
-            /* println!(
-                "{}, ({}, {}), ({}, {})",
-                m.piece.as_string(),
-                m.start.0,
-                m.start.1,
-                m.end.0,
-                m.start.1
-            ) */
+    // The first command tells us which protocol the GUI speaks, UCI unless it is "xboard"
+    let stdin = io::stdin();
+    let mut first_line = String::new();
+    while first_line.trim().is_empty() {
+        first_line.clear();
+        match stdin.lock().read_line(&mut first_line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
         }
     }
-    println!("{} moves for white", count1);
-    println!("Results in {} moves for black", count2);
+    if first_line.trim() == "xboard" {
+        xboard::run();
+    } else {
+        uci::run(Some(first_line));
+    }
 }
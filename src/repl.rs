@@ -0,0 +1,358 @@
+use crate::uci::ENGINE_NAME;
+
+use rust_chess::engine::board::{Board, Move, Turn};
+use rust_chess::engine::game::Game;
+use rust_chess::engine::piece::Piece;
+use rust_chess::engine::search::{SearchOptions, Searcher};
+use rust_chess::engine::time::{SearchLimits, StopSignal};
+
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+
+const DEFAULT_DEPTH: u32 = 4;
+
+const HELP: &str = "Enter a move like Nf3, e4 or e2e4 (e8=Q or e7e8q to promote), or one of:
+  moves [square]   list the legal moves, optionally only from one square
+  undo             take back your last move
+  hint             ask the engine for a move
+  fen              print the position as FEN
+  pgn [file]       print the game as PGN, or save it to a file
+  flip             turn the board around
+  new [black]      start a new game, as white unless black is given
+  level <n>        let the engine search n plies deep
+  level <n>s       let the engine think for n seconds per move
+  help             show this message
+  quit             leave the game";
+
+pub struct Repl {
+    game: Game,
+    human: Turn,
+    flipped: bool,
+    limits: SearchLimits,
+    searcher: Searcher,
+}
+
+pub fn run() {
+    let mut repl = Repl::new();
+    println!("{} - type 'help' for a list of commands", ENGINE_NAME);
+    println!("{}", repl.render());
+    let stdin = io::stdin();
+    loop {
+        print!("{}> ", repl.game.board().to_move.to_string());
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        match repl.handle(&line) {
+            Some(text) => {
+                if !text.is_empty() {
+                    println!("{}", text);
+                }
+            }
+            None => return,
+        }
+    }
+}
+
+fn fresh_game(human: Turn) -> Game {
+    let mut game = Game::default();
+    game.set_tag("Event", "Terminal game");
+    let (white, black) = match human {
+        Turn::White => ("Human", ENGINE_NAME),
+        Turn::Black => (ENGINE_NAME, "Human"),
+    };
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+    return game;
+}
+
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            game: fresh_game(Turn::White),
+            human: Turn::White,
+            flipped: false,
+            limits: SearchLimits::depth(DEFAULT_DEPTH),
+            searcher: Searcher::new(SearchOptions::default()),
+        }
+    }
+
+    pub fn handle(&mut self, line: &str) -> Option<String> {
+        // returns the text to show, or None to quit
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let text = match tokens.as_slice() {
+            [] => String::new(),
+            ["quit"] | ["exit"] => return None,
+            ["help"] => String::from(HELP),
+            ["fen"] => self.game.board().to_fen(),
+            ["pgn"] => self.game.to_pgn().to_pgn(),
+            ["pgn", path] => self.save(path),
+            ["flip"] => {
+                self.flipped = !self.flipped;
+                self.render()
+            }
+            ["new"] => self.new_game(Turn::White),
+            ["new", "white"] => self.new_game(Turn::White),
+            ["new", "black"] => self.new_game(Turn::Black),
+            ["undo"] => self.undo(),
+            ["hint"] => match self.think() {
+                Some(m) => format!("Hint: {}", self.game.board().to_san(&m)),
+                None => String::from("There are no legal moves."),
+            },
+            ["moves"] => self.list_moves(None),
+            ["moves", square] => match Board::parse_square(square) {
+                Some(s) => self.list_moves(Some(s)),
+                None => format!("'{}' is not a square, try something like e2.", square),
+            },
+            ["level", value] => self.set_level(value),
+            [text] => self.human_move(text),
+            _ => format!("Unknown command '{}'. Type 'help' for a list of commands.", line.trim()),
+        };
+        return Some(text);
+    }
+
+    fn new_game(&mut self, human: Turn) -> String {
+        self.game = fresh_game(human);
+        self.human = human;
+        self.flipped = human == Turn::Black;
+        self.searcher.clear();
+        let mut text = String::new();
+        if self.game.board().to_move != self.human {
+            text.push_str(&self.engine_move());
+        }
+        text.push_str(&self.render());
+        return text;
+    }
+
+    fn set_level(&mut self, value: &str) -> String {
+        if let Some(seconds) = value.strip_suffix('s') {
+            if let Ok(n) = seconds.parse::<u64>() {
+                if n > 0 {
+                    self.limits = SearchLimits::move_time(n * 1000);
+                    return format!("The engine now thinks for {} seconds per move.", n);
+                }
+            }
+        } else if let Ok(n) = value.parse::<u32>() {
+            if n > 0 {
+                self.limits = SearchLimits::depth(n);
+                return format!("The engine now searches {} plies deep.", n);
+            }
+        }
+        return format!(
+            "'{}' is not a level, use a depth like 'level 4' or a time like 'level 5s'.",
+            value
+        );
+    }
+
+    fn undo(&mut self) -> String {
+        // take back moves until it is the human's turn again
+        if self.game.is_empty() {
+            return String::from("There is nothing to undo.");
+        }
+        while self.game.take_back().is_some() {
+            if self.game.board().to_move == self.human {
+                break;
+            }
+        }
+        return self.render();
+    }
+
+    fn list_moves(&self, from: Option<(usize, usize)>) -> String {
+        let board = self.game.board();
+        let moves: Vec<String> = board
+            .generate_moves()
+            .iter()
+            .filter(|m| from.is_none() || from == Some(m.start))
+            .map(|m| board.to_san(m))
+            .collect();
+        if moves.is_empty() {
+            return match from {
+                Some(s) => format!("There are no legal moves from {}.", Board::square_name(s)),
+                None => String::from("There are no legal moves."),
+            };
+        }
+        return moves.join(" ");
+    }
+
+    fn human_move(&mut self, text: &str) -> String {
+        if let Some(result) = self.result() {
+            return format!("The game is over ({}). Type 'new' to play again.", result);
+        }
+        if self.game.board().to_move != self.human {
+            return String::from("It is not your turn.");
+        }
+        if let Err(e) = self.game.play_str(text) {
+            return format!(
+                "Can't play '{}': {}. Type 'moves' to see the legal moves, or 'help'.",
+                text, e
+            );
+        }
+        let mut reply = String::new();
+        if self.result().is_none() {
+            reply.push_str(&self.engine_move());
+        }
+        reply.push_str(&self.render());
+        return reply;
+    }
+
+    fn think(&self) -> Option<Move> {
+        let result = self.searcher.search(
+            self.game.board(),
+            self.game.history(),
+            &self.limits,
+            StopSignal::new(),
+            |_| {},
+        );
+        return result.best_move;
+    }
+
+    fn engine_move(&mut self) -> String {
+        match self.think() {
+            Some(m) => {
+                let san = self.game.board().to_san(&m);
+                let _ = self.game.play(m);
+                format!("{} plays {}\n", ENGINE_NAME, san)
+            }
+            None => String::new(),
+        }
+    }
+
+    fn save(&self, path: &str) -> String {
+        let written = File::create(path).and_then(|mut file| self.game.to_pgn().write(&mut file));
+        match written {
+            Ok(()) => format!("Saved the game to {}.", path),
+            Err(e) => format!("Can't save to {}: {}", path, e),
+        }
+    }
+
+    fn result(&self) -> Option<String> {
+        let (result, termination) = self.game.result()?;
+        return Some(format!("{} {{{}}}", result.as_str(), termination.reason(result)));
+    }
+
+    pub fn render(&self) -> String {
+        // the board with coordinates, from the human's side when flipped
+        let mut rows: Vec<usize> = (0..8).collect();
+        let mut cols: Vec<usize> = (0..8).collect();
+        if self.flipped {
+            rows.reverse();
+            cols.reverse();
+        }
+        let files: Vec<String> = cols
+            .iter()
+            .map(|c| ((b'a' + *c as u8) as char).to_string())
+            .collect();
+        let mut text = String::from("  +-----------------+\n");
+        for row in &rows {
+            text.push_str(&format!("{} |", 8 - row));
+            for col in &cols {
+                let piece = self.game.board().squares[*row][*col];
+                let c = if piece == Piece::Blank {
+                    '.'
+                } else {
+                    piece.as_char()
+                };
+                text.push(' ');
+                text.push(c);
+            }
+            text.push_str(" |\n");
+        }
+        text.push_str("  +-----------------+\n");
+        text.push_str(&format!("    {}", files.join(" ")));
+        if let Some(result) = self.result() {
+            text.push_str(&format!("\nGame over: {}", result));
+        } else if self.game.board().is_in_check() {
+            text.push_str("\nCheck!");
+        }
+        return text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_engine_replies() {
+        let mut repl = Repl::new();
+        repl.handle("level 1");
+        let text = repl.handle("e2e4").unwrap();
+        assert!(text.contains("rust_chess plays"));
+        assert!(repl.game.board().to_move == Turn::White);
+        assert_eq!(repl.game.len(), 2);
+        repl.handle("undo");
+        assert_eq!(repl.game.board().to_fen(), Board::default().to_fen());
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let mut repl = Repl::new();
+        assert!(repl.handle("e2e5").unwrap().contains("not a legal move"));
+        assert!(repl.handle("xyzzy").unwrap().contains("help"));
+        assert!(repl.handle("moves z9").unwrap().contains("not a square"));
+        assert!(repl.handle("level fast").unwrap().contains("not a level"));
+        assert!(repl.handle("undo").unwrap().contains("nothing to undo"));
+        assert!(repl.handle("do a barrel roll").unwrap().contains("Unknown command"));
+        assert!(repl.handle("quit").is_none());
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.handle("moves e2").unwrap(), "e4 e3");
+        assert_eq!(repl.handle("moves e4").unwrap(), "There are no legal moves from e4.");
+        assert_eq!(
+            repl.handle("fen").unwrap(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert!(repl.handle("hint").unwrap().starts_with("Hint: "));
+        assert!(repl.handle("level 2s").unwrap().contains("2 seconds"));
+        assert!(repl.limits.move_time == Some(2000));
+    }
+
+    #[test]
+    fn test_pgn() {
+        let mut repl = Repl::new();
+        repl.handle("level 1");
+        repl.handle("e4");
+        let text = repl.handle("pgn").unwrap();
+        assert!(text.contains("[White \"Human\"]\n[Black \"rust_chess\"]"));
+        assert!(text.contains("\n\n1. e4 "));
+        assert!(text.trim_end().ends_with('*'));
+        repl.handle("undo");
+        assert!(repl.handle("pgn").unwrap().ends_with("\n\n*\n"));
+    }
+
+    #[test]
+    fn test_render_and_flip() {
+        let mut repl = Repl::new();
+        let text = repl.render();
+        assert!(text.starts_with("  +-----------------+\n8 | r n b q k b n r |"));
+        assert!(text.ends_with("    a b c d e f g h"));
+        let text = repl.handle("flip").unwrap();
+        assert!(text.starts_with("  +-----------------+\n1 | R N B K Q B N R |"));
+        assert!(text.ends_with("    h g f e d c b a"));
+    }
+
+    #[test]
+    fn test_play_black() {
+        let mut repl = Repl::new();
+        repl.handle("level 1");
+        let text = repl.handle("new black").unwrap();
+        assert!(text.contains("rust_chess plays"));
+        assert!(repl.game.board().to_move == Turn::Black);
+        assert!(repl.handle("e2e4").unwrap().contains("Black's turn"));
+    }
+
+    #[test]
+    fn test_san_input() {
+        let mut repl = Repl::new();
+        repl.handle("level 1");
+        assert!(repl.handle("Nf3").unwrap().contains("rust_chess plays"));
+        assert!(repl.game.board().squares[5][5] == Piece::WKnight);
+        assert!(repl.handle("Nf3").unwrap().contains("Can't play 'Nf3'"));
+    }
+}
@@ -0,0 +1,130 @@
+use rust_chess::engine::board::Board;
+use rust_chess::engine::tablebase::{Metric, Probe, Tablebases, Wdl};
+
+use std::fs;
+use std::time::Instant;
+
+const USAGE: &str = "usage: rust_chess tablebase generate <dir> <table>... [wdl]\n       \
+                     rust_chess tablebase probe <dir> <fen>";
+
+pub fn run(args: &[String]) -> i32 {
+    // returns the process exit code
+    let result = match (args.first().map(|s| s.as_str()), args.get(1)) {
+        (Some("generate"), Some(dir)) if args.len() > 2 => generate(dir, &args[2..]),
+        (Some("probe"), Some(dir)) if args.len() > 2 => probe(dir, &args[2..].join(" ")),
+        (Some("generate"), _) | (Some("probe"), _) => {
+            eprintln!("missing arguments\n{}", USAGE);
+            return 2;
+        }
+        (Some(other), _) => {
+            eprintln!("unknown command '{}'\n{}", other, USAGE);
+            return 2;
+        }
+        (None, _) => {
+            eprintln!("missing command\n{}", USAGE);
+            return 2;
+        }
+    };
+    return match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    };
+}
+
+fn generate(dir: &str, args: &[String]) -> Result<(), String> {
+    // tables already in the directory are reused for the captures and promotions
+    let metric = if args.iter().any(|a| a == "wdl") {
+        Metric::Wdl
+    } else {
+        Metric::Dtm
+    };
+    fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {}", dir, e))?;
+    let mut tablebases = Tablebases::open_dir(dir)?;
+    for name in args.iter().filter(|a| *a != "wdl") {
+        let start = Instant::now();
+        let table = tablebases.generate(name)?;
+        println!(
+            "{}: {} positions in {:.1}s",
+            table.name(),
+            table.len(),
+            start.elapsed().as_secs_f64()
+        );
+    }
+    tablebases.save_dir(dir, metric)?;
+    println!("saved {} tables to {}", tablebases.names().len(), dir);
+    return Ok(());
+}
+
+fn probe(dir: &str, fen: &str) -> Result<(), String> {
+    let tablebases = Tablebases::open_dir(dir)?;
+    let board = Board::parse_fen(fen)?;
+    let result = match tablebases.probe(&board) {
+        Some(p) => p,
+        None => return Err(String::from("no table holds this position")),
+    };
+    println!("{}", describe(&result));
+    if let Some((m, _)) = tablebases.best_move(&board) {
+        println!("best move {}", board.to_san(&m));
+    }
+    return Ok(());
+}
+
+fn describe(probe: &Probe) -> String {
+    let result = match probe.wdl {
+        Wdl::Win => "win",
+        Wdl::Draw => "draw",
+        Wdl::Loss => "loss",
+    };
+    return match probe.dtm {
+        Some(plies) => format!("{}, mate in {} plies", result, plies),
+        None => result.to_string(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        return text.split_whitespace().map(|s| s.to_string()).collect();
+    }
+
+    #[test]
+    fn test_generate_and_probe() {
+        // a fresh directory of our own, so parallel runs don't share tables
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "rust_chess_tablebase_{}_{}",
+            std::process::id(),
+            nanos
+        ));
+        let dir = dir.to_str().unwrap();
+        assert_eq!(run(&args(&format!("generate {} KQK", dir))), 0);
+        assert!(fs::metadata(format!("{}/KQvK.rctb", dir)).is_ok());
+        assert_eq!(
+            run(&args(&format!("probe {} 8/8/8/4k3/8/8/Q7/4K3 w - -", dir))),
+            0
+        );
+        assert_eq!(
+            run(&args(&format!("probe {} 8/8/8/4k3/8/8/R7/4K3 w - -", dir))),
+            1
+        );
+        assert_eq!(run(&args("generate")), 2);
+        assert_eq!(run(&args(&format!("generate {} KQQQQK", dir))), 1);
+        assert_eq!(
+            describe(&Probe {
+                wdl: Wdl::Loss,
+                dtm: Some(4)
+            }),
+            "loss, mate in 4 plies"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
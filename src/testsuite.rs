@@ -0,0 +1,254 @@
+use rust_chess::engine::board::Move;
+use rust_chess::engine::epd::Epd;
+use rust_chess::engine::search::{Score, SearchOptions, Searcher};
+use rust_chess::engine::time::{SearchLimits, StopSignal};
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+const USAGE: &str =
+    "usage: rust_chess testsuite <file.epd> [depth <n> | movetime <ms>] [threads <n>] [hash <mb>]";
+
+const DEFAULT_MOVE_TIME: u64 = 1000;
+
+pub struct Outcome {
+    pub played: Option<Move>,
+    pub solved: bool,
+    // STS points for the played move, None when the position has none to give
+    pub points: Option<u32>,
+    pub max_points: u32,
+}
+
+pub fn run(args: &[String]) -> i32 {
+    // returns the process exit code
+    let (path, limits, options) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    let file = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("can't open {}: {}", path, e);
+            return 1;
+        }
+    };
+    let searcher = Searcher::new(options);
+    let mut total = 0;
+    let mut solved = 0;
+    let mut points = 0;
+    let mut max_points = 0;
+    // STS ids look like "STS(v1.0) Undermine.001", the part before the dot names the theme
+    let mut themes: Vec<(String, u32, u32)> = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => {
+                eprintln!("line {}: {}", number + 1, e);
+                break;
+            }
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let epd = match Epd::parse(&line) {
+            Ok(epd) => epd,
+            Err(e) => {
+                println!("line {}: skipped, {}", number + 1, e);
+                continue;
+            }
+        };
+        let id = epd
+            .id()
+            .map(|s| s.to_string())
+            .unwrap_or(format!("line {}", number + 1));
+        searcher.clear();
+        let outcome = match solve(&epd, &searcher, &limits) {
+            Ok(o) => o,
+            Err(e) => {
+                println!("{}: skipped, {}", id, e);
+                continue;
+            }
+        };
+        total += 1;
+        if outcome.solved {
+            solved += 1;
+        }
+        println!("{}", format_outcome(&id, &epd, &outcome));
+        if let Some(p) = outcome.points {
+            points += p;
+            max_points += outcome.max_points;
+            let theme = id
+                .rsplit_once('.')
+                .map(|(t, _)| t)
+                .unwrap_or(&id)
+                .to_string();
+            match themes.iter_mut().find(|(t, _, _)| *t == theme) {
+                Some(entry) => {
+                    entry.1 += p;
+                    entry.2 += outcome.max_points;
+                }
+                None => themes.push((theme, p, outcome.max_points)),
+            }
+        }
+    }
+    println!();
+    for (theme, p, max) in &themes {
+        println!("{}: {} of {}", theme, p, max);
+    }
+    println!("Solved {} of {}", solved, total);
+    if max_points > 0 {
+        println!(
+            "STS score {} of {} ({:.1}%)",
+            points,
+            max_points,
+            100.0 * points as f64 / max_points as f64
+        );
+    }
+    return 0;
+}
+
+fn parse_args(args: &[String]) -> Result<(String, SearchLimits, SearchOptions), String> {
+    let path = match args.first() {
+        Some(p) => p.clone(),
+        None => return Err(String::from("missing EPD file")),
+    };
+    let mut limits = SearchLimits::move_time(DEFAULT_MOVE_TIME);
+    let mut options = SearchOptions::default();
+    let mut i = 1;
+    while i < args.len() {
+        let value = match args.get(i + 1) {
+            Some(v) => v,
+            None => return Err(format!("missing value for {}", args[i])),
+        };
+        let number = match value.parse::<u64>() {
+            Ok(n) if n > 0 => n,
+            _ => return Err(format!("invalid value '{}' for {}", value, args[i])),
+        };
+        match args[i].as_str() {
+            "depth" => limits = SearchLimits::depth(number as u32),
+            "movetime" => limits = SearchLimits::move_time(number),
+            "threads" => options.threads = number as usize,
+            "hash" => options.hash_mb = number as usize,
+            other => return Err(format!("unknown option '{}'", other)),
+        }
+        i += 2;
+    }
+    return Ok((path, limits, options));
+}
+
+pub fn solve(epd: &Epd, searcher: &Searcher, limits: &SearchLimits) -> Result<Outcome, String> {
+    // bm and am decide when present, then dm, and STS points on their own otherwise
+    let best = epd.best_moves()?;
+    let avoid = epd.avoid_moves()?;
+    let mate = epd.direct_mate();
+    let move_points = epd.move_points();
+    if best.is_empty() && avoid.is_empty() && mate.is_none() && move_points.is_empty() {
+        return Err(String::from("no bm, am, dm or STS points to check against"));
+    }
+    let result = searcher.search(&epd.board, &[], limits, StopSignal::new(), |_| {});
+    let played = result.best_move;
+    let max_points = move_points.iter().map(|(_, p)| *p).max().unwrap_or(0);
+    let points = match played {
+        Some(m) if !move_points.is_empty() => Some(
+            move_points
+                .iter()
+                .find(|(pm, _)| *pm == m)
+                .map(|(_, p)| *p)
+                .unwrap_or(0),
+        ),
+        None if !move_points.is_empty() => Some(0),
+        _ => None,
+    };
+    let solved = match played {
+        None => false,
+        Some(m) if !best.is_empty() || !avoid.is_empty() => {
+            (best.is_empty() || best.contains(&m)) && !avoid.contains(&m)
+        }
+        Some(_) => match mate {
+            Some(n) => {
+                matches!(Score::from_internal(result.score), Score::Mate(m) if m > 0 && m as u32 <= n)
+            }
+            None => points == Some(max_points),
+        },
+    };
+    return Ok(Outcome {
+        played,
+        solved,
+        points,
+        max_points,
+    });
+}
+
+pub fn format_outcome(id: &str, epd: &Epd, outcome: &Outcome) -> String {
+    let played = match outcome.played {
+        Some(m) => epd.board.to_san(&m),
+        None => String::from("nothing"),
+    };
+    let mut text = format!(
+        "{}: {}, played {}",
+        id,
+        if outcome.solved { "solved" } else { "failed" },
+        played
+    );
+    if let Some(bm) = epd.get("bm") {
+        text.push_str(&format!(", best {}", bm.join(" ")));
+    }
+    if let Some(am) = epd.get("am") {
+        text.push_str(&format!(", avoid {}", am.join(" ")));
+    }
+    if let Some(p) = outcome.points {
+        text.push_str(&format!(", {} of {} points", p, outcome.max_points));
+    }
+    return text;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        return text.split_whitespace().map(|s| s.to_string()).collect();
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let (path, limits, options) = parse_args(&args("wac.epd depth 5 threads 2")).unwrap();
+        assert_eq!(path, "wac.epd");
+        assert!(limits == SearchLimits::depth(5));
+        assert_eq!(options.threads, 2);
+        let (_, limits, _) = parse_args(&args("wac.epd")).unwrap();
+        assert!(limits == SearchLimits::move_time(DEFAULT_MOVE_TIME));
+        assert!(parse_args(&[]).is_err());
+        assert!(parse_args(&args("wac.epd depth")).is_err());
+        assert!(parse_args(&args("wac.epd depth 0")).is_err());
+        assert!(parse_args(&args("wac.epd speed 3")).is_err());
+    }
+
+    #[test]
+    fn test_solve() {
+        let searcher = Searcher::new(SearchOptions::default());
+        let limits = SearchLimits::depth(3);
+        let epd = Epd::parse(
+            "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate.1\"; c0 \"Ra8#=10, Kf2=1\";",
+        )
+        .unwrap();
+        let outcome = solve(&epd, &searcher, &limits).unwrap();
+        assert!(outcome.solved);
+        assert_eq!(outcome.points, Some(10));
+        assert_eq!(
+            format_outcome("mate.1", &epd, &outcome),
+            "mate.1: solved, played Ra8#, best Ra8#, 10 of 10 points"
+        );
+
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra8#;").unwrap();
+        assert!(!solve(&epd, &searcher, &limits).unwrap().solved);
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - dm 1;").unwrap();
+        assert!(solve(&epd, &searcher, &limits).unwrap().solved);
+        let epd = Epd::parse("6k1/5ppp/8/8/8/8/8/R5K1 w - - id \"x\";").unwrap();
+        assert!(solve(&epd, &searcher, &limits).is_err());
+    }
+}
@@ -0,0 +1,578 @@
+use rust_chess::engine::board::Board;
+use rust_chess::engine::polyglot::{Book, Selection};
+use rust_chess::engine::search::{
+    Score, SearchInfo, SearchOptions, SearchResult, Searcher, SEARCH_STACK_SIZE,
+};
+use rust_chess::engine::tablebase::Tablebases;
+use rust_chess::engine::time::{SearchLimits, StopSignal};
+use rust_chess::engine::variant::{self, Variant, VARIANTS};
+
+use std::io::{self, BufRead};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const ENGINE_NAME: &str = "rust_chess";
+pub const ENGINE_AUTHOR: &str = "kyrod";
+
+const MAX_HASH: usize = 1024;
+const MAX_THREADS: usize = 64;
+const MAX_MULTI_PV: usize = 64;
+
+pub struct GoCommand {
+    pub limits: SearchLimits,
+    pub perft: Option<u32>,
+}
+
+pub struct Uci {
+    board: Board,
+    // hashes of the positions before `board`, for repetition detection
+    history: Vec<u64>,
+    searcher: Arc<Searcher>,
+    stop: StopSignal,
+    search: Option<JoinHandle<()>>,
+    own_book: bool,
+    book_best: bool,
+    book_file: String,
+    book: Option<Book>,
+    chess960: bool,
+}
+
+pub fn run(first_line: Option<String>) {
+    let mut uci = Uci::new();
+    if let Some(line) = first_line {
+        if !uci.handle(&line) {
+            return;
+        }
+    }
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if !uci.handle(&line) {
+                    return;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    uci.wait_for_search();
+}
+
+impl Uci {
+    pub fn new() -> Uci {
+        Uci {
+            board: Board::default(),
+            history: Vec::new(),
+            searcher: Arc::new(Searcher::new(SearchOptions::default())),
+            stop: StopSignal::new(),
+            search: None,
+            own_book: false,
+            book_best: false,
+            book_file: String::new(),
+            book: None,
+            chess960: false,
+        }
+    }
+
+    pub fn handle(&mut self, line: &str) -> bool {
+        // returns false once the GUI asks us to quit
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"uci") => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                let options = self.searcher.options;
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    options.hash_mb, MAX_HASH
+                );
+                println!(
+                    "option name Threads type spin default {} min 1 max {}",
+                    options.threads, MAX_THREADS
+                );
+                println!(
+                    "option name MultiPV type spin default {} min 1 max {}",
+                    options.multi_pv, MAX_MULTI_PV
+                );
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                println!("option name BookBestMove type check default false");
+                println!("option name TablebaseDir type string default <empty>");
+                println!("option name UCI_Chess960 type check default false");
+                let vars: Vec<String> = VARIANTS.iter().map(|v| format!("var {}", v)).collect();
+                println!("option name UCI_Variant type combo default chess {}", vars.join(" "));
+                println!("uciok");
+            }
+            Some(&"isready") => println!("readyok"),
+            Some(&"ucinewgame") => {
+                self.stop_search();
+                self.searcher.clear();
+                self.board = self.searcher.variant().start_position();
+                self.history.clear();
+            }
+            Some(&"setoption") => {
+                self.stop_search();
+                if let Err(e) = self.set_option(&tokens[1..]) {
+                    println!("info string {}", e);
+                }
+            }
+            Some(&"position") => {
+                self.stop_search();
+                match parse_position(&tokens[1..], self.searcher.variant(), self.chess960) {
+                    Ok((board, history)) => {
+                        self.board = board;
+                        self.history = history;
+                    }
+                    Err(e) => println!("info string {}", e),
+                }
+            }
+            Some(&"go") => {
+                self.stop_search();
+                let go = parse_go(&tokens[1..]);
+                match go.perft {
+                    Some(depth) => self.perft(depth),
+                    None => match self.book_move(&go.limits) {
+                        Some(m) => println!("bestmove {}", m),
+                        None => self.start_search(go.limits),
+                    },
+                }
+            }
+            Some(&"stop") => self.stop_search(),
+            Some(&"quit") => {
+                self.stop_search();
+                return false;
+            }
+            Some(other) => println!("info string unknown command '{}'", other),
+            None => {}
+        }
+        return true;
+    }
+
+    fn set_option(&mut self, tokens: &[&str]) -> Result<(), String> {
+        let (name, value) = parse_option(tokens)?;
+        let check = || -> Result<bool, String> {
+            match value.as_deref() {
+                Some("true") => Ok(true),
+                Some("false") => Ok(false),
+                _ => Err(format!("invalid value for option {}", name)),
+            }
+        };
+        match name.to_lowercase().as_str() {
+            "ownbook" => {
+                self.own_book = check()?;
+                return self.load_book();
+            }
+            "bookbestmove" => {
+                self.book_best = check()?;
+                return Ok(());
+            }
+            "uci_chess960" => {
+                self.chess960 = check()?;
+                return Ok(());
+            }
+            "bookfile" => {
+                self.book_file = match value.as_deref() {
+                    None | Some("<empty>") => String::new(),
+                    Some(path) => path.to_string(),
+                };
+                self.book = None;
+                return self.load_book();
+            }
+            _ => {}
+        }
+        let number = || -> Result<usize, String> {
+            match value.as_ref().map(|v| v.parse::<usize>()) {
+                Some(Ok(n)) if n >= 1 => Ok(n),
+                _ => Err(format!("invalid value for option {}", name)),
+            }
+        };
+        // the search thread has been joined, so nobody else holds the searcher
+        let searcher = match Arc::get_mut(&mut self.searcher) {
+            Some(s) => s,
+            None => return Err(String::from("cannot change options while searching")),
+        };
+        match name.to_lowercase().as_str() {
+            "hash" => searcher.set_hash(number()?.min(MAX_HASH)),
+            "threads" => searcher.options.threads = number()?.min(MAX_THREADS),
+            "multipv" => searcher.options.multi_pv = number()?.min(MAX_MULTI_PV),
+            "uci_variant" => match value.as_deref().and_then(variant::by_name) {
+                Some(v) => {
+                    searcher.set_variant(v);
+                    self.board = searcher.variant().start_position();
+                    self.history.clear();
+                }
+                None => return Err(format!("unknown variant '{}'", value.unwrap_or_default())),
+            },
+            "tablebasedir" => match value.as_deref() {
+                None | Some("<empty>") => searcher.set_tablebases(None),
+                Some(dir) => {
+                    let tablebases = Tablebases::open_dir(dir)?;
+                    println!("info string loaded {} tables from {}", tablebases.names().len(), dir);
+                    searcher.set_tablebases(Some(Arc::new(tablebases)));
+                }
+            },
+            _ => return Err(format!("unknown option '{}'", name)),
+        }
+        return Ok(());
+    }
+
+    fn load_book(&mut self) -> Result<(), String> {
+        // the book is read once it is both enabled and named
+        if !self.own_book || self.book_file.is_empty() || self.book.is_some() {
+            return Ok(());
+        }
+        let book = Book::open(&self.book_file)?;
+        println!("info string loaded {} book entries from {}", book.len(), self.book_file);
+        self.book = Some(book);
+        return Ok(());
+    }
+
+    fn book_move(&self, limits: &SearchLimits) -> Option<String> {
+        // analysis always searches, games take a book move when there is one; Polyglot
+        // books are for standard chess, even where a Chess960 position has the same key
+        if !self.own_book
+            || limits.infinite
+            || self.searcher.variant().name() != "chess"
+            || self.board.chess960
+        {
+            return None;
+        }
+        let selection = if self.book_best {
+            Selection::Best
+        } else {
+            Selection::Weighted
+        };
+        let m = self.book.as_ref()?.pick(&self.board, selection)?;
+        return Some(m.to_string());
+    }
+
+    fn start_search(&mut self, limits: SearchLimits) {
+        let searcher = Arc::clone(&self.searcher);
+        let board = self.board;
+        let history = self.history.clone();
+        let stop = StopSignal::new();
+        self.stop = stop.clone();
+        let handle = thread::Builder::new()
+            .stack_size(SEARCH_STACK_SIZE)
+            .spawn(move || {
+                let result = searcher.search(&board, &history, &limits, stop.clone(), |info| {
+                    println!("{}", format_info(info))
+                });
+                if limits.infinite {
+                    // bestmove may only be sent after "stop" when searching infinitely
+                    while !stop.is_stopped() {
+                        thread::sleep(Duration::from_millis(5));
+                    }
+                }
+                println!("{}", format_bestmove(&result));
+            })
+            .expect("failed to start the search thread");
+        self.search = Some(handle);
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.stop();
+        self.wait_for_search();
+    }
+
+    fn wait_for_search(&mut self) {
+        if let Some(handle) = self.search.take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn perft(&self, depth: u32) {
+        // "go perft N" prints the node count below every root move
+        let variant = self.searcher.variant();
+        if depth == 0 {
+            // just the position itself, with no moves to list
+            println!();
+            println!("Nodes searched: 1");
+            return;
+        }
+        let mut total = 0;
+        for m in variant.legal_moves(&self.board) {
+            let mut next = self.board;
+            variant.play_move(&mut next, m);
+            let count = variant::perft(variant, &next, depth - 1);
+            total += count;
+            println!("{}: {}", m, count);
+        }
+        println!();
+        println!("Nodes searched: {}", total);
+    }
+}
+
+pub fn parse_position(
+    tokens: &[&str],
+    variant: &dyn Variant,
+    chess960: bool,
+) -> Result<(Board, Vec<u64>), String> {
+    // position startpos|fen <fen> [moves <move1> ... <movei>], in Chess960 castling
+    // moves are written as the king taking its own rook
+    let moves_at = tokens.iter().position(|t| *t == "moves");
+    let setup = &tokens[..moves_at.unwrap_or(tokens.len())];
+    let mut board = match setup.first() {
+        Some(&"startpos") => variant.start_position(),
+        Some(&"fen") => variant.parse_fen(&setup[1..].join(" "))?,
+        _ => return Err(String::from("expected 'startpos' or 'fen'")),
+    };
+    if chess960 {
+        board.chess960 = true;
+    }
+    let mut history = Vec::new();
+    if let Some(i) = moves_at {
+        for token in &tokens[i + 1..] {
+            let m = match variant.parse_move(&board, token) {
+                Ok(m) => m,
+                Err(e) => return Err(format!("illegal move {}: {}", token, e)),
+            };
+            history.push(board.hash());
+            variant.play_move(&mut board, m);
+        }
+    }
+    return Ok((board, history));
+}
+
+pub fn parse_go(tokens: &[&str]) -> GoCommand {
+    let mut go = GoCommand {
+        limits: SearchLimits::default(),
+        perft: None,
+    };
+    let mut i = 0;
+    while i < tokens.len() {
+        let value = tokens.get(i + 1).and_then(|v| v.parse::<u64>().ok());
+        let mut takes_value = true;
+        match tokens[i] {
+            "wtime" => go.limits.wtime = value,
+            "btime" => go.limits.btime = value,
+            "winc" => go.limits.winc = value,
+            "binc" => go.limits.binc = value,
+            "movestogo" => go.limits.moves_to_go = value.map(|v| v as u32),
+            "depth" => go.limits.depth = value.map(|v| v as u32),
+            "nodes" => go.limits.nodes = value,
+            "movetime" => go.limits.move_time = value,
+            "perft" => go.perft = value.map(|v| v as u32),
+            "infinite" => {
+                go.limits.infinite = true;
+                takes_value = false;
+            }
+            _ => takes_value = false,
+        }
+        i += if takes_value { 2 } else { 1 };
+    }
+    return go;
+}
+
+pub fn parse_option(tokens: &[&str]) -> Result<(String, Option<String>), String> {
+    // name <id> [value <x>], both of which may contain spaces
+    if tokens.first() != Some(&"name") {
+        return Err(String::from("expected 'setoption name <id> [value <x>]'"));
+    }
+    let value_at = tokens.iter().position(|t| *t == "value");
+    let name = tokens[1..value_at.unwrap_or(tokens.len())].join(" ");
+    let value = value_at.map(|i| tokens[i + 1..].join(" "));
+    return Ok((name, value));
+}
+
+pub fn format_info(info: &SearchInfo) -> String {
+    let score = match info.score {
+        Score::Cp(cp) => format!("cp {}", cp),
+        Score::Mate(moves) => format!("mate {}", moves),
+    };
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+    return format!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        score,
+        info.nodes,
+        info.nps(),
+        info.hashfull,
+        info.time,
+        pv.join(" ")
+    );
+}
+
+pub fn format_bestmove(result: &SearchResult) -> String {
+    match (result.best_move, result.ponder) {
+        (Some(m), Some(p)) => format!("bestmove {} ponder {}", m, p),
+        (Some(m), None) => format!("bestmove {}", m),
+        // no legal moves, the game is already over
+        _ => String::from("bestmove 0000"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rust_chess::engine::variant::Standard;
+
+    #[test]
+    fn test_parse_position() {
+        let (board, history) =
+            parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"], &Standard, false).unwrap();
+        assert_eq!(
+            board.to_string(),
+            "rnbqkbnr\npppp-ppp\n--------\n----p---\n----P---\n-----N--\nPPPP-PPP\nRNBQKB-R\n"
+        );
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0], Board::default().hash());
+
+        let fen = "fen 8/P6k/8/8/8/8/8/4K3 w - - 0 1 moves a7a8n".split(' ').collect::<Vec<_>>();
+        let (board, _) = parse_position(&fen, &Standard, false).unwrap();
+        assert_eq!(board.squares[0][0].as_char(), 'N');
+
+        assert!(parse_position(&["startpos", "moves", "e2e5"], &Standard, false).is_err());
+        assert!(parse_position(&["fen", "not", "a", "fen"], &Standard, false).is_err());
+        assert!(parse_position(&[], &Standard, false).is_err());
+
+        // with UCI_Chess960 castling is the king taking its rook
+        let moves = "startpos moves g1f3 g8f6 e2e3 e7e6 f1e2 f8e7 e1h1"
+            .split(' ')
+            .collect::<Vec<_>>();
+        assert!(parse_position(&moves, &Standard, false).is_err());
+        let (board, _) = parse_position(&moves, &Standard, true).unwrap();
+        assert_eq!(board.squares[7][6].as_char(), 'K');
+        assert_eq!(board.squares[7][5].as_char(), 'R');
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let tokens: Vec<&str> = "wtime 1000 btime 2000 winc 10 binc 20 movestogo 5"
+            .split(' ')
+            .collect();
+        let go = parse_go(&tokens);
+        assert_eq!(go.limits.wtime, Some(1000));
+        assert_eq!(go.limits.btime, Some(2000));
+        assert_eq!(go.limits.winc, Some(10));
+        assert_eq!(go.limits.binc, Some(20));
+        assert_eq!(go.limits.moves_to_go, Some(5));
+        assert!(!go.limits.infinite);
+
+        let go = parse_go(&["infinite"]);
+        assert!(go.limits.infinite);
+        let go = parse_go(&["depth", "6", "nodes", "1000", "movetime", "50"]);
+        assert_eq!(go.limits.depth, Some(6));
+        assert_eq!(go.limits.nodes, Some(1000));
+        assert_eq!(go.limits.move_time, Some(50));
+        assert_eq!(parse_go(&["perft", "3"]).perft, Some(3));
+    }
+
+    #[test]
+    fn test_parse_option() {
+        let (name, value) = parse_option(&["name", "Hash", "value", "128"]).unwrap();
+        assert_eq!(name, "Hash");
+        assert_eq!(value, Some(String::from("128")));
+        let (name, value) = parse_option(&["name", "Clear", "Hash"]).unwrap();
+        assert_eq!(name, "Clear Hash");
+        assert_eq!(value, None);
+        assert!(parse_option(&["Hash"]).is_err());
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut uci = Uci::new();
+        assert!(uci.set_option(&["name", "Threads", "value", "4"]).is_ok());
+        assert!(uci.set_option(&["name", "multipv", "value", "3"]).is_ok());
+        assert!(uci.set_option(&["name", "Hash", "value", "1"]).is_ok());
+        assert!(uci.set_option(&["name", "Hash", "value", "zero"]).is_err());
+        assert!(uci.set_option(&["name", "Ponder", "value", "true"]).is_err());
+        assert!(uci.set_option(&["name", "UCI_Chess960", "value", "true"]).is_ok());
+        assert!(uci.chess960);
+        assert!(uci.set_option(&["name", "UCI_Variant", "value", "kingofthehill"]).is_ok());
+        assert!(uci.set_option(&["name", "UCI_Variant", "value", "shogi"]).is_err());
+        assert_eq!(uci.searcher.variant().name(), "kingofthehill");
+        uci.handle("position fen 8/8/8/8/8/4K3/8/k7 w - - 0 1 moves e3e4");
+        assert!(uci.searcher.variant().result(&uci.board).is_some());
+        assert_eq!(uci.searcher.options.threads, 4);
+        assert_eq!(uci.searcher.options.multi_pv, 3);
+        assert_eq!(uci.searcher.options.hash_mb, 1);
+    }
+
+    #[test]
+    fn test_format() {
+        let board = Board::default();
+        let m = board.parse_move("e2e4").unwrap();
+        let info = SearchInfo {
+            depth: 3,
+            seldepth: 5,
+            multipv: 1,
+            score: Score::Mate(-2),
+            nodes: 2000,
+            time: 1000,
+            hashfull: 7,
+            pv: vec![m],
+        };
+        assert_eq!(
+            format_info(&info),
+            "info depth 3 seldepth 5 multipv 1 score mate -2 nodes 2000 nps 2000 hashfull 7 time 1000 pv e2e4"
+        );
+        let result = SearchResult {
+            best_move: None,
+            ponder: None,
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        assert_eq!(format_bestmove(&result), "bestmove 0000");
+    }
+
+    #[test]
+    fn test_book_options() {
+        // a one entry book holding 1. e4 for the start position
+        let dir = test_dir("book");
+        let path = dir.join("book.bin");
+        let mut bytes = 0x463b96181691fc9cu64.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[0x03, 0x1c, 0, 1, 0, 0, 0, 0]);
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut uci = Uci::new();
+        let limits = SearchLimits::default();
+        let file = format!("setoption name BookFile value {}", path.display());
+        uci.handle(&file);
+        assert!(uci.book.is_none());
+        assert!(uci.book_move(&limits).is_none());
+        uci.handle("setoption name OwnBook value true");
+        assert_eq!(uci.book_move(&limits), Some(String::from("e2e4")));
+        assert!(uci.book_move(&SearchLimits::infinite()).is_none());
+        uci.handle("position startpos moves e2e4");
+        assert!(uci.book_move(&limits).is_none());
+        uci.handle("setoption name UCI_Chess960 value true");
+        uci.handle("position startpos");
+        assert!(uci.book_move(&limits).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_tablebase_option() {
+        let mut uci = Uci::new();
+        let missing = ["name", "TablebaseDir", "value", "/no/such/dir"];
+        assert!(uci.set_option(&missing).is_err());
+        let path = test_dir("tablebases");
+        let dir = path.to_string_lossy().to_string();
+        assert!(uci.set_option(&["name", "TablebaseDir", "value", &dir]).is_ok());
+        assert!(uci.set_option(&["name", "TablebaseDir", "value", "<empty>"]).is_ok());
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        // a fresh directory of our own, so parallel runs and stray files don't interfere
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "rust_chess_uci_{}_{}_{}",
+            name,
+            std::process::id(),
+            nanos
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+}
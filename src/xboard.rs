@@ -0,0 +1,496 @@
+use crate::uci::ENGINE_NAME;
+
+use rust_chess::engine::board::{Board, Move, Turn};
+use rust_chess::engine::game::adjudicate;
+use rust_chess::engine::search::{
+    Score, SearchInfo, SearchOptions, SearchResult, Searcher, SEARCH_STACK_SIZE,
+};
+use rust_chess::engine::time::{SearchLimits, StopSignal};
+
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+// xboard reports mate scores as 100000 + moves to mate
+const XBOARD_MATE: i32 = 100000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Level {
+    // moves per time control, 0 for the whole game
+    pub moves: u32,
+    pub base_ms: u64,
+    pub inc_ms: u64,
+}
+
+pub struct XBoard {
+    board: Board,
+    // every position before `board`, for undo and repetitions
+    previous: Vec<Board>,
+    engine_side: Option<Turn>,
+    post: bool,
+    level: Option<Level>,
+    move_time: Option<u64>,
+    max_depth: Option<u32>,
+    engine_time: Option<u64>,
+    opponent_time: Option<u64>,
+    searcher: Arc<Searcher>,
+    stop: StopSignal,
+    // set when the position changes under a running search, so its move is never sent
+    cancelled: Arc<Mutex<bool>>,
+    search: Option<JoinHandle<Option<Move>>>,
+}
+
+pub fn run() {
+    let mut xboard = XBoard::new();
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => {
+                if !xboard.handle(&line) {
+                    return;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    xboard.finish_search();
+}
+
+impl XBoard {
+    pub fn new() -> XBoard {
+        XBoard {
+            board: Board::default(),
+            previous: Vec::new(),
+            engine_side: Some(Turn::Black),
+            post: false,
+            level: None,
+            move_time: None,
+            max_depth: None,
+            engine_time: None,
+            opponent_time: None,
+            searcher: Arc::new(Searcher::new(SearchOptions::default())),
+            stop: StopSignal::new(),
+            cancelled: Arc::new(Mutex::new(false)),
+            search: None,
+        }
+    }
+
+    pub fn handle(&mut self, line: &str) -> bool {
+        // returns false on quit
+        let line = line.trim();
+        let (command, args) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+        if command == "?" {
+            // move now
+            self.stop.stop();
+            return true;
+        }
+        // these change the game, so the engine's move would be played in the wrong one;
+        // everything else waits until the engine has made its move
+        match command {
+            "new" | "force" | "result" | "quit" | "setboard" | "undo" | "remove" => {
+                self.abort_search()
+            }
+            _ => self.finish_search(),
+        }
+        match command {
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "ics" | "" => {}
+            "protover" => println!(
+                "feature myname=\"{}\" setboard=1 usermove=1 ping=1 playother=1 colors=0 analyze=0 sigint=0 sigterm=0 done=1",
+                ENGINE_NAME
+            ),
+            "new" => {
+                self.board = Board::default();
+                self.previous.clear();
+                self.engine_side = Some(Turn::Black);
+                self.max_depth = None;
+                self.searcher.clear();
+            }
+            "force" => self.engine_side = None,
+            "go" => {
+                self.engine_side = Some(self.board.to_move);
+                self.start_search();
+            }
+            "playother" => self.engine_side = Some(self.board.to_move.opposite_turn()),
+            "usermove" => self.user_move(args),
+            "setboard" => match Board::parse_fen(args) {
+                Ok(board) => {
+                    self.board = board;
+                    self.previous.clear();
+                }
+                Err(e) => println!("tellusererror Illegal position: {}", e),
+            },
+            "undo" => self.take_back(1),
+            "remove" => self.take_back(2),
+            "level" => match parse_level(args) {
+                Some(level) => {
+                    // both clocks start full until the GUI sends time and otim
+                    self.level = Some(level);
+                    self.move_time = None;
+                    self.engine_time = Some(level.base_ms);
+                    self.opponent_time = Some(level.base_ms);
+                }
+                None => println!("Error (bad level): {}", args),
+            },
+            "st" => match args.parse::<u64>() {
+                Ok(seconds) => self.move_time = Some(seconds * 1000),
+                Err(_) => println!("Error (bad time): {}", args),
+            },
+            "sd" => match args.parse::<u32>() {
+                Ok(depth) => self.max_depth = Some(depth),
+                Err(_) => println!("Error (bad depth): {}", args),
+            },
+            // clocks are sent in centiseconds
+            "time" => self.engine_time = args.parse::<u64>().ok().map(|t| t * 10),
+            "otim" => self.opponent_time = args.parse::<u64>().ok().map(|t| t * 10),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "result" => self.engine_side = None,
+            "ping" => println!("pong {}", args),
+            "quit" => return false,
+            _ => {
+                // protover 1 GUIs send bare moves
+                if self.board.parse_move(command).is_ok() {
+                    self.user_move(command);
+                } else {
+                    println!("Error (unknown command): {}", command);
+                }
+            }
+        }
+        return true;
+    }
+
+    fn user_move(&mut self, text: &str) {
+        let m = match self.board.parse_move(text) {
+            Ok(m) => m,
+            Err(_) => {
+                println!("Illegal move: {}", text);
+                return;
+            }
+        };
+        self.play(m);
+        if self.engine_side == Some(self.board.to_move) {
+            self.start_search();
+        }
+    }
+
+    fn play(&mut self, m: Move) {
+        self.previous.push(self.board);
+        self.board.play_move(m);
+    }
+
+    fn take_back(&mut self, plies: usize) {
+        for _ in 0..plies {
+            if let Some(board) = self.previous.pop() {
+                self.board = board;
+            }
+        }
+    }
+
+    fn history(&self) -> Vec<u64> {
+        return self.previous.iter().map(|b| b.hash()).collect();
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits {
+            depth: self.max_depth,
+            ..SearchLimits::default()
+        };
+        if let Some(t) = self.move_time {
+            limits.move_time = Some(t);
+            return limits;
+        }
+        let (mine, theirs) = (self.engine_time, self.opponent_time);
+        let (inc, moves_to_go) = match self.level {
+            Some(level) => {
+                // moves left until the next time control, counting only our own moves
+                let played = self.board.move_number.saturating_sub(1);
+                let to_go = if level.moves > 0 {
+                    Some(level.moves - played % level.moves)
+                } else {
+                    None
+                };
+                (Some(level.inc_ms), to_go)
+            }
+            None => (None, None),
+        };
+        limits.moves_to_go = moves_to_go;
+        match self.board.to_move {
+            Turn::White => {
+                limits.wtime = mine;
+                limits.btime = theirs;
+                limits.winc = inc;
+                limits.binc = inc;
+            }
+            Turn::Black => {
+                limits.btime = mine;
+                limits.wtime = theirs;
+                limits.binc = inc;
+                limits.winc = inc;
+            }
+        }
+        if mine.is_none() && self.max_depth.is_none() {
+            // no clock at all yet, don't think forever
+            limits.move_time = Some(5000);
+        }
+        return limits;
+    }
+
+    fn start_search(&mut self) {
+        if game_result(&self.board, &self.history()).is_some() {
+            return;
+        }
+        let searcher = Arc::clone(&self.searcher);
+        let board = self.board;
+        let history = self.history();
+        let limits = self.limits();
+        let post = self.post;
+        let stop = StopSignal::new();
+        self.stop = stop.clone();
+        let cancelled = Arc::new(Mutex::new(false));
+        self.cancelled = Arc::clone(&cancelled);
+        let handle = thread::Builder::new()
+            .stack_size(SEARCH_STACK_SIZE)
+            .spawn(move || {
+                let result = searcher.search(&board, &history, &limits, stop, |info| {
+                    if post && info.multipv == 1 {
+                        println!("{}", format_thinking(info));
+                    }
+                });
+                // holding the lock while printing means a move is either sent and played,
+                // or cancelled and dropped
+                let cancelled = cancelled.lock().unwrap();
+                if *cancelled {
+                    return None;
+                }
+                announce(&board, &history, &result);
+                result.best_move
+            })
+            .expect("failed to start the search thread");
+        self.search = Some(handle);
+    }
+
+    fn abort_search(&mut self) {
+        // stops the search and throws its move away, unless it was already sent
+        *self.cancelled.lock().unwrap() = true;
+        self.stop.stop();
+        self.finish_search();
+    }
+
+    fn finish_search(&mut self) {
+        // picks up the move the search thread already sent to the GUI
+        if let Some(handle) = self.search.take() {
+            if let Ok(Some(m)) = handle.join() {
+                self.play(m);
+            }
+        }
+    }
+}
+
+fn announce(board: &Board, history: &[u64], result: &SearchResult) {
+    let m = match result.best_move {
+        Some(m) => m,
+        None => return,
+    };
+    println!("move {}", m);
+    let mut after = *board;
+    after.play_move(m);
+    let mut history = history.to_vec();
+    history.push(board.hash());
+    if let Some(result) = game_result(&after, &history) {
+        println!("{}", result);
+    }
+}
+
+pub fn game_result(board: &Board, history: &[u64]) -> Option<String> {
+    // the result line to send when the game is over, in the form "1-0 {White mates}"
+    let (result, termination) = adjudicate(board, history)?;
+    return Some(format!(
+        "{} {{{}}}",
+        result.as_str(),
+        termination.reason(result)
+    ));
+}
+
+pub fn parse_level(args: &str) -> Option<Level> {
+    // level MPS BASE INC, where BASE is minutes or minutes:seconds
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let moves = parts[0].parse::<u32>().ok()?;
+    let base_ms = match parts[1].find(':') {
+        Some(i) => {
+            let minutes = parts[1][..i].parse::<u64>().ok()?;
+            let seconds = parts[1][i + 1..].parse::<u64>().ok()?;
+            (minutes * 60 + seconds) * 1000
+        }
+        None => parts[1].parse::<u64>().ok()? * 60 * 1000,
+    };
+    let inc_ms = (parts[2].parse::<f64>().ok()? * 1000.0) as u64;
+    return Some(Level {
+        moves,
+        base_ms,
+        inc_ms,
+    });
+}
+
+pub fn format_thinking(info: &SearchInfo) -> String {
+    // ply score time nodes pv, with the time in centiseconds
+    let score = match info.score {
+        Score::Cp(cp) => cp,
+        Score::Mate(moves) if moves > 0 => XBOARD_MATE + moves,
+        Score::Mate(moves) => -XBOARD_MATE + moves,
+    };
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_string()).collect();
+    return format!(
+        "{} {} {} {} {}",
+        info.depth,
+        score,
+        info.time / 10,
+        info.nodes,
+        pv.join(" ")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(
+            parse_level("40 5 0"),
+            Some(Level {
+                moves: 40,
+                base_ms: 300_000,
+                inc_ms: 0
+            })
+        );
+        assert_eq!(
+            parse_level("0 2:30 1.5"),
+            Some(Level {
+                moves: 0,
+                base_ms: 150_000,
+                inc_ms: 1500
+            })
+        );
+        assert_eq!(parse_level("40 5"), None);
+        assert_eq!(parse_level("x 5 0"), None);
+    }
+
+    #[test]
+    fn test_force_mode_moves() {
+        let mut xboard = XBoard::new();
+        xboard.handle("new");
+        xboard.handle("force");
+        xboard.handle("usermove e2e4");
+        xboard.handle("e7e5");
+        xboard.handle("usermove e1e3");
+        assert!(xboard.board.to_move == Turn::White);
+        assert_eq!(xboard.previous.len(), 2);
+        xboard.handle("undo");
+        assert!(xboard.board.to_move == Turn::Black);
+        xboard.handle("remove");
+        assert!(xboard.previous.is_empty());
+        assert_eq!(xboard.board.to_string(), Board::default().to_string());
+    }
+
+    #[test]
+    fn test_setboard_and_limits() {
+        let mut xboard = XBoard::new();
+        xboard.handle("force");
+        xboard.handle("setboard 4k3/8/8/8/8/8/8/4K2R b K - 0 40");
+        assert!(xboard.board.to_move == Turn::Black);
+        xboard.handle("setboard nonsense");
+        assert!(xboard.board.to_move == Turn::Black);
+
+        xboard.handle("level 40 5 2");
+        assert_eq!(xboard.limits().btime, Some(300_000));
+        xboard.handle("time 6000");
+        xboard.handle("otim 3000");
+        let limits = xboard.limits();
+        assert_eq!(limits.btime, Some(60_000));
+        assert_eq!(limits.wtime, Some(30_000));
+        assert_eq!(limits.binc, Some(2000));
+        // 39 moves played, one left in this time control
+        assert_eq!(limits.moves_to_go, Some(1));
+
+        xboard.handle("sd 4");
+        xboard.handle("st 2");
+        let limits = xboard.limits();
+        assert_eq!(limits.depth, Some(4));
+        assert_eq!(limits.move_time, Some(2000));
+        xboard.handle("new");
+        assert_eq!(xboard.limits().depth, None);
+    }
+
+    #[test]
+    fn test_engine_replies() {
+        let mut xboard = XBoard::new();
+        xboard.handle("new");
+        xboard.handle("sd 1");
+        xboard.handle("usermove e2e4");
+        // the next command waits for the engine's answer
+        xboard.handle("ping 1");
+        assert!(xboard.board.to_move == Turn::White);
+        assert_eq!(xboard.previous.len(), 2);
+        xboard.handle("result 1-0 {White resigns}");
+        xboard.handle("usermove d2d4");
+        xboard.handle("ping 2");
+        assert!(xboard.board.to_move == Turn::Black);
+    }
+
+    #[test]
+    fn test_new_cancels_search() {
+        let mut xboard = XBoard::new();
+        xboard.handle("new");
+        xboard.handle("st 3");
+        xboard.handle("go");
+        thread::sleep(std::time::Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        xboard.handle("new");
+        assert!(start.elapsed().as_millis() < 1000);
+        assert!(xboard.previous.is_empty());
+        assert_eq!(xboard.board.to_string(), Board::default().to_string());
+    }
+
+    #[test]
+    fn test_game_result() {
+        let mate = Board::from_fen(String::from("R6k/6pp/8/8/8/8/8/6K1 b - - 0 1"));
+        assert_eq!(
+            game_result(&mate, &[]),
+            Some(String::from("1-0 {White mates}"))
+        );
+        let stalemate = Board::from_fen(String::from("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1"));
+        assert_eq!(
+            game_result(&stalemate, &[]),
+            Some(String::from("1/2-1/2 {Stalemate}"))
+        );
+        let board = Board::default();
+        assert_eq!(game_result(&board, &[board.hash()]), None);
+        assert_eq!(
+            game_result(&board, &[board.hash(), 7, board.hash()]),
+            Some(String::from("1/2-1/2 {Draw by repetition}"))
+        );
+    }
+
+    #[test]
+    fn test_format_thinking() {
+        let info = SearchInfo {
+            depth: 4,
+            seldepth: 4,
+            multipv: 1,
+            score: Score::Mate(2),
+            nodes: 1234,
+            time: 1500,
+            hashfull: 0,
+            pv: Vec::new(),
+        };
+        assert_eq!(format_thinking(&info), "4 100002 150 1234 ");
+    }
+}